@@ -192,12 +192,15 @@ impl<'a, K: Serialize, V> Serialize for Keys<&'a BTreeMap<K, V>> {
     }
 }
 
-impl<'a, K: Serialize, V> Serialize for Keys<&'a HashMap<K, V>> {
+/// Keys of a [`HashMap`] are sorted, so that the output is deterministic
+impl<'a, K: Serialize + Ord, V> Serialize for Keys<&'a HashMap<K, V>> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.collect_seq(self.inner.keys())
+        let mut keys: Vec<&K> = self.inner.keys().collect();
+        keys.sort_unstable();
+        serializer.collect_seq(keys)
     }
 }
 
@@ -218,12 +221,12 @@ impl<K, F: Filter<K>> Filter<K> for &F {
 }
 
 /// [Serialize] adapter that shows only those keys from map `M` that are in `K`
-pub(crate) struct Filtered<M: 'static, K> {
-    pub(crate) inner: &'static M,
+pub(crate) struct Filtered<'m, M, K> {
+    pub(crate) inner: &'m M,
     pub(crate) keys: K,
 }
 
-impl<M: 'static, K: Filter<i32>, V: Serialize> Serialize for Filtered<M, K>
+impl<'m, M, K: Filter<i32>, V: Serialize> Serialize for Filtered<'m, M, K>
 where
     for<'a> &'a M: IntoIterator<Item = (&'a i32, &'a V)>,
 {
@@ -240,13 +243,13 @@ where
 }
 
 /// [Serialize] adapter that shows only those keys from map `M` that are in `K` (in either `keys1` or `keys2`)
-pub(crate) struct Filtered2<M: 'static, K> {
-    pub(crate) inner: &'static M,
+pub(crate) struct Filtered2<'m, M, K> {
+    pub(crate) inner: &'m M,
     pub(crate) keys1: K,
     pub(crate) keys2: K,
 }
 
-impl<M: 'static, K: Filter<i32>, V: Serialize> Serialize for Filtered2<M, K>
+impl<'m, M, K: Filter<i32>, V: Serialize> Serialize for Filtered2<'m, M, K>
 where
     for<'a> &'a M: IntoIterator<Item = (&'a i32, &'a V)>,
 {
@@ -261,3 +264,20 @@ where
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Keys;
+
+    #[test]
+    fn test_hash_map_keys_sorted() {
+        let map: HashMap<i32, ()> = (0..100).rev().map(|k| (k, ())).collect();
+        let expected: Vec<i32> = (0..100).collect();
+        assert_eq!(
+            serde_json::to_value(Keys::new(&map)).unwrap(),
+            serde_json::to_value(expected).unwrap()
+        );
+    }
+}
@@ -1,12 +1,18 @@
+use hyper::body::Bytes;
 use openapiv3::{OpenAPI, SecurityRequirement, Server};
 use std::sync::Arc;
 
-use crate::auth::AuthKind;
+use super::rev;
+use crate::{auth::AuthKind, config::DocsOptions};
+
+const SWAGGER_UI_HTML: &str = include_str!("../../res/api.html");
 
 #[derive(Clone)]
 pub struct OpenApiService {
     /// The openapi structure
     inner: Arc<OpenAPI>,
+    /// The Swagger UI page, with the configured title
+    html: Bytes,
 }
 
 impl AsRef<OpenAPI> for OpenApiService {
@@ -15,22 +21,196 @@ impl AsRef<OpenAPI> for OpenApiService {
     }
 }
 
+/// Escape text for use in HTML
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 impl OpenApiService {
-    pub fn new(url: &str, auth_kind: AuthKind) -> Result<Self, serde_yaml::Error> {
-        let text = include_str!("../../res/api.yaml");
-        let mut data: OpenAPI = serde_yaml::from_str(text)?;
-        data.servers.push(Server {
-            url: url.to_string(),
-            description: Some(String::from("The current server")),
-            ..Default::default()
-        });
+    /// Create a new instance
+    ///
+    /// `api_urls` are listed as the `servers` of the document, the first one is
+    /// the current server.
+    pub fn new(
+        api_urls: &[String],
+        docs: &DocsOptions,
+        auth_kind: AuthKind,
+    ) -> Result<Self, serde_yaml::Error> {
+        let text = include_str!("../../res/api.yaml")
+            .replace("{{rev_alias_deprecation}}", &rev::alias_deprecation());
+        let mut data: OpenAPI = serde_yaml::from_str(&text)?;
+        data.info.title = docs.title.clone();
+        #[cfg(not(feature = "graphql"))]
+        data.paths
+            .paths
+            .retain(|path, _| !path.starts_with("/v0/graphql/"));
+        for (i, url) in api_urls.iter().chain(&docs.servers).enumerate() {
+            data.servers.push(Server {
+                url: url.to_string(),
+                description: Some(String::from(match i {
+                    0 => "The current server",
+                    _ => "An alternative server",
+                })),
+                ..Default::default()
+            });
+        }
         if auth_kind == AuthKind::Basic {
             let mut req = SecurityRequirement::new();
             req.insert("basic_auth".to_string(), vec![]);
             data.security = Some(vec![req]);
         }
+        let html = SWAGGER_UI_HTML.replace("{{title}}", &escape_html(&docs.title));
         Ok(Self {
             inner: Arc::new(data),
+            html: Bytes::from(html),
         })
     }
+
+    /// The HTML of the Swagger UI
+    pub fn html(&self) -> Bytes {
+        self.html.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{auth::AuthKind, config::DocsOptions};
+
+    use super::{
+        super::{rev, ApiRoute},
+        escape_html, OpenApiService,
+    };
+
+    /// One concrete path for every [`ApiRoute`] and [`super::super::rev::Route`]
+    const EXAMPLES: &[&str] = &[
+        "/v0/tables",
+        "/v0/tables/Objects/def",
+        "/v0/tables/Objects/columns",
+        "/v0/tables/Objects/all",
+        "/v0/tables/Objects/count",
+        "/v0/tables/Objects/stats",
+        "/v0/tables/Objects/export.sqlite",
+        "/v0/tables/Objects/1",
+        "/v0/query/SELECT%201",
+        "/v0/query/explain/SELECT%201",
+        "/v0/locale/Objects",
+        "/v0/locale/Objects/$all",
+        "/v0/locale/by-key/Missions_1_name",
+        "/v0/locale/languages",
+        "/v0/crc/1",
+        "/v0/openapi.json",
+        "/v0/events",
+        "/v0/meta",
+        "/v0/objects/1/components",
+        "/v0/objects/1/image",
+        "/v0/rev",
+        "/v0/rev/activity",
+        "/v0/rev/activity/1",
+        "/v0/rev/behaviors/1",
+        "/v0/rev/behaviors/1/tree",
+        "/v0/rev/component_types",
+        "/v0/rev/component_types/1",
+        "/v0/rev/component_types/1/1",
+        "/v0/rev/emotes",
+        "/v0/rev/emotes/1",
+        "/v0/rev/faction",
+        "/v0/rev/faction/1",
+        "/v0/rev/gate_version",
+        "/v0/rev/gate_version/diff",
+        "/v0/rev/gate_version/live",
+        "/v0/rev/loot_matrix_index/1",
+        "/v0/rev/loot_table_index/1",
+        "/v0/rev/missions",
+        "/v0/rev/missions/1",
+        "/v0/rev/mission_types",
+        "/v0/rev/mission_types/full",
+        "/v0/rev/mission_types/Achievement",
+        "/v0/rev/mission_types/Achievement/Collection",
+        "/v0/rev/objects",
+        "/v0/rev/objects/1",
+        "/v0/rev/objects/1/full",
+        "/v0/rev/objects/1/components",
+        "/v0/rev/objects/1/image",
+        "/v0/rev/objects/search",
+        "/v0/rev/objects/search_index",
+        "/v0/rev/object_types",
+        "/v0/rev/object_types/counts",
+        "/v0/rev/object_types/Enemies",
+        "/v0/rev/skills",
+        "/v0/rev/skill_ids/1",
+        "/v0/rev/preconditions",
+        "/v0/rev/preconditions/1",
+        "/v0/rev/skill_ids/cooldowngroups",
+        "/v0/rev/skill_ids/cooldowngroups/1",
+        "/v1/tables",
+        "/v1/res/ls",
+        "/v1/res/maps/nimbusstation.luz",
+        "/v1/files/1/raw",
+    ];
+
+    #[cfg(feature = "graphql")]
+    const GRAPHQL_EXAMPLES: &[&str] = &[
+        "/v0/graphql/%7BObjects%7Bid%7D%7D",
+        "/v0/graphql/",
+        "/v0/graphql/schema",
+        "/v0/graphql/tables",
+        "/v0/graphql/bulk",
+    ];
+    #[cfg(not(feature = "graphql"))]
+    const GRAPHQL_EXAMPLES: &[&str] = &[];
+
+    fn matches(template: &str, path: &str) -> bool {
+        let mut t = template.split('/');
+        let mut p = path.split('/');
+        loop {
+            match (t.next(), p.next()) {
+                (None, None) => return true,
+                // `{path}` may span multiple segments
+                (Some("{path}"), Some(_)) => return t.next().is_none(),
+                (Some(t), Some(p)) if t == p || (t.starts_with('{') && !p.is_empty()) => {}
+                _ => return false,
+            }
+        }
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("<b>\"A&B\"</b>"),
+            "&lt;b&gt;&quot;A&amp;B&quot;&lt;/b&gt;"
+        );
+    }
+
+    #[test]
+    fn test_every_route_is_documented() {
+        let urls = [String::from("http://localhost/api/")];
+        let docs = DocsOptions::default();
+        let openapi = OpenApiService::new(&urls, &docs, AuthKind::None).unwrap();
+        assert_eq!(openapi.as_ref().info.title, "LU-Explorer API");
+        let paths = &openapi.as_ref().paths.paths;
+        let rev = serde_json::to_string(&paths["/v0/rev"]).unwrap();
+        assert!(rev.contains(&format!("`Deprecation: {}`", rev::alias_deprecation())));
+        for example in EXAMPLES.iter().chain(GRAPHQL_EXAMPLES) {
+            assert!(
+                ApiRoute::from_str(example).is_ok(),
+                "{} is not a route",
+                example
+            );
+            assert!(
+                paths.keys().any(|template| matches(template, example)),
+                "{} is not documented",
+                example
+            );
+        }
+    }
 }
@@ -0,0 +1,65 @@
+//! # Server-Sent Events
+//!
+//! `/v0/events` is a `text/event-stream` with an `event: reload` whenever a data file
+//! was reloaded, e.g. `data: {"reload":"db"}`. Clients can use it to invalidate their
+//! caches instead of polling.
+
+use std::{convert::Infallible, time::Duration};
+
+use futures_util::{stream, StreamExt};
+use hyper::body::Bytes;
+use serde::Serialize;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+use crate::data::events::Reload;
+
+/// How long the stream may be idle before a comment is sent to keep it open
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+#[derive(Serialize)]
+struct ReloadData {
+    reload: Reload,
+}
+
+/// Format a single event
+fn reload_event(reload: Reload) -> Bytes {
+    let data = serde_json::to_string(&ReloadData { reload }).unwrap();
+    Bytes::from(format!("event: reload\ndata: {}\n\n", data))
+}
+
+/// The body of the event stream, which ends when the server shuts down
+pub(super) fn event_stream(rx: Receiver<Reload>) -> hyper::Body {
+    let open = stream::once(async { Ok(Bytes::from_static(b": connected\n\n")) });
+    let events = stream::unfold(rx, |mut rx| async move {
+        loop {
+            let chunk = match tokio::time::timeout(KEEP_ALIVE, rx.recv()).await {
+                Err(_elapsed) => Bytes::from_static(b": keep-alive\n\n"),
+                Ok(Ok(reload)) => reload_event(reload),
+                // Missed events are only older reloads, just continue
+                Ok(Err(RecvError::Lagged(_))) => continue,
+                Ok(Err(RecvError::Closed)) => return None,
+            };
+            return Some((Ok::<_, Infallible>(chunk), rx));
+        }
+    });
+    hyper::Body::wrap_stream(open.chain(events))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::events::Reload;
+
+    use super::reload_event;
+
+    #[test]
+    fn test_reload_event() {
+        assert_eq!(
+            &reload_event(Reload::Db)[..],
+            b"event: reload\ndata: {\"reload\":\"db\"}\n\n"
+        );
+        assert_eq!(
+            &reload_event(Reload::Sqlite)[..],
+            b"event: reload\ndata: {\"reload\":\"sqlite\"}\n\n"
+        );
+    }
+}
@@ -1,12 +1,17 @@
 use assembly_pack::pki::core::PackFileRef;
 use serde::Serialize;
 use std::{
-    fmt, io,
-    path::{Path, PathBuf},
+    fmt,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
     sync::Arc,
 };
 
-use crate::data::fs::{Loader, Node};
+use crate::data::fs::{DirListing, Loader, Node, NodeKind};
+
+/// The directory that the res tree is loaded into
+const RES_ROOT: &str = "client/res";
 
 #[derive(Serialize)]
 pub(crate) struct CRCReply<'a> {
@@ -38,9 +43,12 @@ impl fmt::Display for Error {
 }
 
 impl PackService {
-    pub fn new(res_path: &Path, pki_path: Option<&Path>) -> Result<Self, Error> {
+    /// Load the files of all `res_roots`, where later roots replace files of earlier ones
+    pub fn new(res_roots: &[&Path], pki_path: Option<&Path>) -> Result<Self, Error> {
         let mut loader = Loader::new();
-        loader.load_dir(Path::new("client/res"), res_path);
+        for res_path in res_roots {
+            loader.load_dir(Path::new(RES_ROOT), res_path);
+        }
         tracing::info!("PKI Path: {:?}", pki_path);
         if let Some(pki_path) = pki_path {
             loader.load_pki(pki_path).map_err(|inner| Error {
@@ -59,4 +67,149 @@ impl PackService {
         let pk = loader.get_pki(crc);
         CRCReply { fs, pk }
     }
+
+    /// Get the server side path and the kind of a loose file in the res tree
+    pub fn file_path(&self, crc: u32) -> Option<(&Path, NodeKind)> {
+        self.inner
+            .get(crc)
+            .map(|e| (e.abs_path.as_path(), e.public.kind))
+    }
+
+    /// List a directory, given as a client path like `client\res\maps`
+    ///
+    /// Returns `Err` if the path is not within `client/res`, and `Ok(None)` if
+    /// there are no files in it.
+    pub fn list(&self, dir: &str) -> Result<Option<DirListing>, ()> {
+        let dir = normalize_res_dir(dir)?;
+        Ok(self.inner.list_dir(&dir))
+    }
+}
+
+/// A range of bytes in a file, both ends inclusive
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Parse the value of a `Range` header for a file that is `len` bytes long
+///
+/// Returns `Ok(None)` if the header should be ignored, i.e. if it is malformed,
+/// not in bytes or asks for more than one range, and `Err(())` if the range
+/// can't be satisfied.
+fn parse_range(header: &str, len: u64) -> Result<Option<ByteRange>, ()> {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return Ok(None);
+    };
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return Err(()),
+            Ok(n) => (len.saturating_sub(n), len.saturating_sub(1)),
+            Err(_) => return Ok(None),
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, len.saturating_sub(1)),
+            Err(_) => return Ok(None),
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+            _ => return Ok(None),
+        },
+    };
+    if start >= len {
+        return Err(());
+    }
+    Ok(Some(ByteRange { start, end }))
+}
+
+/// The (partial) content of a file
+pub(crate) enum RawFile {
+    Full(Vec<u8>),
+    Partial {
+        range: ByteRange,
+        len: u64,
+        bytes: Vec<u8>,
+    },
+    Unsatisfiable {
+        len: u64,
+    },
+}
+
+/// Read the file at `path`, or just the part selected by the `Range` header
+pub(crate) fn read_file(path: &Path, range: Option<&str>) -> io::Result<RawFile> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let range = match range.map(|range| parse_range(range, len)) {
+        None | Some(Ok(None)) => {
+            let mut bytes = Vec::with_capacity(len as usize);
+            file.read_to_end(&mut bytes)?;
+            return Ok(RawFile::Full(bytes));
+        }
+        Some(Ok(Some(range))) => range,
+        Some(Err(())) => return Ok(RawFile::Unsatisfiable { len }),
+    };
+    let count = range.end - range.start + 1;
+    let mut bytes = Vec::with_capacity(count as usize);
+    file.seek(SeekFrom::Start(range.start))?;
+    file.take(count).read_to_end(&mut bytes)?;
+    Ok(RawFile::Partial { range, len, bytes })
+}
+
+/// Turn a client path into a path below [`RES_ROOT`], rejecting `..` and absolute paths
+fn normalize_res_dir(dir: &str) -> Result<PathBuf, ()> {
+    let dir = dir.replace('\\', "/").to_ascii_lowercase();
+    let dir = dir.trim_matches('/');
+    let rest = dir
+        .strip_prefix(RES_ROOT)
+        .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+        .unwrap_or(dir);
+    let mut path = PathBuf::from(RES_ROOT);
+    for component in Path::new(rest).components() {
+        match component {
+            Component::Normal(segment) => path.push(segment),
+            Component::CurDir | Component::RootDir => {}
+            Component::ParentDir | Component::Prefix(_) => return Err(()),
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{normalize_res_dir, parse_range, ByteRange};
+
+    #[test]
+    fn test_normalize_res_dir() {
+        let maps = Path::new("client/res/maps");
+        assert_eq!(normalize_res_dir("client\\res\\maps").unwrap(), maps);
+        assert_eq!(normalize_res_dir("Client/Res/Maps/").unwrap(), maps);
+        assert_eq!(normalize_res_dir("maps").unwrap(), maps);
+        assert_eq!(normalize_res_dir("/maps").unwrap(), maps);
+        assert_eq!(normalize_res_dir("").unwrap(), Path::new("client/res"));
+        assert!(normalize_res_dir("maps/../../secrets").is_err());
+        assert!(normalize_res_dir("client\\res\\..").is_err());
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let range = |start, end| Ok(Some(ByteRange { start, end }));
+        assert_eq!(parse_range("bytes=0-9", 100), range(0, 9));
+        assert_eq!(parse_range("bytes=90-", 100), range(90, 99));
+        assert_eq!(parse_range("bytes=-10", 100), range(90, 99));
+        assert_eq!(parse_range("bytes=-200", 100), range(0, 99));
+        assert_eq!(parse_range("bytes=50-200", 100), range(50, 99));
+        assert_eq!(parse_range("bytes=100-", 100), Err(()));
+        assert_eq!(parse_range("bytes=-0", 100), Err(()));
+        assert_eq!(parse_range("bytes=0-1,5-6", 100), Ok(None));
+        assert_eq!(parse_range("bytes=9-0", 100), Ok(None));
+        assert_eq!(parse_range("items=0-9", 100), Ok(None));
+    }
 }
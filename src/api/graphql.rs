@@ -1,22 +1,51 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 use std::fmt::Write;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 
-use rusqlite::{types::ValueRef, Connection, OpenFlags};
+use arc_swap::ArcSwap;
+use rusqlite::{
+    types::{Value as SqlValue, ValueRef},
+    Connection, OpenFlags,
+};
+use serde::Serialize;
+use tracing::{error, info};
+
+use crate::data::{
+    events::{Reload, ReloadEvents},
+    fs::spawn_file_watcher,
+};
 
 use graphql_parser::{
     parse_query,
-    query::{Definition, Field, OperationDefinition, Selection},
+    query::{Definition, Document, Field, OperationDefinition, Query, Selection, Value},
 };
 
+/// Who is responsible for a [`QueryError`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryErrorKind {
+    /// The query is malformed or refers to tables or fields that don't exist
+    Client,
+    /// The query was fine, but running it failed
+    Server,
+}
+
 #[derive(Debug)]
 pub struct QueryError {
+    pub kind: QueryErrorKind,
     pub error: String,
     pub message: String,
 }
 
+impl QueryError {
+    /// Whether this error was caused by the query (i.e. should be a `4XX`)
+    pub fn is_client_error(&self) -> bool {
+        self.kind == QueryErrorKind::Client
+    }
+}
+
 impl fmt::Display for QueryError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         write!(f, "{}: {}", self.error, self.message)
@@ -28,6 +57,7 @@ impl std::error::Error for QueryError {}
 impl From<graphql_parser::query::ParseError> for QueryError {
     fn from(value: graphql_parser::query::ParseError) -> Self {
         QueryError {
+            kind: QueryErrorKind::Client,
             error: String::from("GraphQL parsing error"),
             message: format!("{}", value),
         }
@@ -37,6 +67,7 @@ impl From<graphql_parser::query::ParseError> for QueryError {
 impl From<rusqlite::Error> for QueryError {
     fn from(value: rusqlite::Error) -> Self {
         QueryError {
+            kind: QueryErrorKind::Server,
             error: String::from("rusqlite error"),
             message: format!("{}", value),
         }
@@ -45,13 +76,14 @@ impl From<rusqlite::Error> for QueryError {
 
 fn invalid_query(message: String) -> QueryError {
     QueryError {
+        kind: QueryErrorKind::Client,
         error: "invalid graphql query".to_string(),
         message,
     }
 }
 
 /// A relation between two SQL tables, a foreign key or its reverse.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct TableRel {
     unique: bool,
     from_col: String,
@@ -59,22 +91,32 @@ pub struct TableRel {
     to_col: String,
 }
 
-pub type TableRels = HashMap<String, HashMap<String, TableRel>>;
+pub type TableRels = BTreeMap<String, BTreeMap<String, TableRel>>;
+/// The values of GraphQL variables, by name without the `$`
+pub type Variables = serde_json::Map<String, serde_json::Value>;
 pub type Fragments<'a> = HashMap<&'a str, &'a Vec<Selection<'a, String>>>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct TableQuery {
     name: String,
     cols: Vec<Column>,
     constraints: Vec<String>,
     joins: Vec<Join>,
+    /// Only set on the top-level table
+    order_by: Option<OrderBy>,
     // buffer for table_to_json
     rowid: i64,
     // buffer for table_to_json
     flushed_outputs: Vec<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+struct OrderBy {
+    column: String,
+    desc: bool,
+}
+
+#[derive(Debug, Clone)]
 struct Column {
     name: String,
     alias: Option<String>,
@@ -82,7 +124,7 @@ struct Column {
     value: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Join {
     unique: bool,
     graphql_name: String,
@@ -91,11 +133,172 @@ struct Join {
     to_table: TableQuery,
 }
 
+/// A top-level field of a GraphQL operation, translated to SQL
+#[derive(Debug)]
+struct CompiledField {
+    /// The key in the JSON output, i.e. the alias or table name
+    key: String,
+    sql: String,
+    /// The table query with empty buffers, cloned for every execution
+    table_query: TableQuery,
+}
+
+/// A query operation of a GraphQL document, translated to SQL
+#[derive(Debug)]
+struct CompiledOperation {
+    /// The name of the operation, only used by [`graphql_bulk`]
+    name: String,
+    /// The default values of the declared variables
+    defaults: Variables,
+    fields: Vec<CompiledField>,
+}
+
+#[derive(Default)]
+struct QueryCacheInner {
+    /// Incremented on every access, to find the least recently used entry
+    tick: u64,
+    /// The last access and compiled operations, by bulk flag and query text
+    entries: HashMap<(bool, String), (u64, Arc<Vec<CompiledOperation>>)>,
+}
+
+/// A cache of GraphQL queries that were translated to SQL
+///
+/// Parsing and translating a query is skipped when the exact same query text was
+/// seen before. When the cache is full, the least recently used entry is evicted.
+pub struct QueryCache {
+    capacity: usize,
+    lang: String,
+    inner: Mutex<QueryCacheInner>,
+}
+
+impl QueryCache {
+    /// Create a cache that keeps at most `capacity` queries, `0` disables it
+    ///
+    /// Queries are translated with `_loc` columns in `lang`, e.g. `name_loc` is `name_en_US`.
+    pub fn new(capacity: usize, lang: &str) -> Self {
+        Self {
+            capacity,
+            lang: lang.to_string(),
+            inner: Mutex::new(QueryCacheInner::default()),
+        }
+    }
+
+    /// The language of the `_loc` columns
+    pub fn lang(&self) -> &str {
+        &self.lang
+    }
+
+    /// The number of cached queries
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// Whether no query is cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get_or_compile<F>(
+        &self,
+        bulk: bool,
+        query: &str,
+        compile: F,
+    ) -> Result<Arc<Vec<CompiledOperation>>, QueryError>
+    where
+        F: FnOnce() -> Result<Vec<CompiledOperation>, QueryError>,
+    {
+        let key = (bulk, query.to_string());
+        {
+            let inner = &mut *self.inner.lock().unwrap();
+            inner.tick += 1;
+            if let Some((used, ops)) = inner.entries.get_mut(&key) {
+                *used = inner.tick;
+                return Ok(ops.clone());
+            }
+        }
+
+        // compile without holding the lock, errors are not cached
+        let ops = Arc::new(compile()?);
+        if self.capacity > 0 {
+            let inner = &mut *self.inner.lock().unwrap();
+            if inner.entries.len() >= self.capacity && !inner.entries.contains_key(&key) {
+                let oldest = inner
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, (used, _))| *used)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    inner.entries.remove(&oldest);
+                }
+            }
+            inner.tick += 1;
+            inner.entries.insert(key, (inner.tick, ops.clone()));
+        }
+        Ok(ops)
+    }
+}
+
+/// A table of the SQLite DB, as listed by `/graphql/tables`.
+#[derive(Debug, Serialize)]
+pub struct TableInfo {
+    name: String,
+    columns: Vec<ColumnInfo>,
+    relations: BTreeMap<String, TableRel>,
+}
+
+/// A column of an SQLite table.
+#[derive(Debug, Serialize)]
+pub struct ColumnInfo {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    not_null: bool,
+    primary_key: bool,
+}
+
+/// Lists all tables with their columns and the (reverse) relations a query can follow.
+pub fn read_out_tables(
+    conn: &Connection,
+    table_rels: &TableRels,
+) -> Result<Vec<TableInfo>, rusqlite::Error> {
+    let mut tables = vec![];
+
+    let mut tables_stmt =
+        conn.prepare("select name from sqlite_master where type = 'table' order by name")?;
+    let mut tables_rows = tables_stmt.query([])?;
+
+    while let Some(tables_row) = tables_rows.next()? {
+        let name: String = tables_row.get(0)?;
+
+        let mut cols_stmt =
+            conn.prepare("select name, type, \"notnull\", pk from pragma_table_info(?1)")?;
+        let mut cols_rows = cols_stmt.query([&name])?;
+
+        let mut columns = vec![];
+        while let Some(cols_row) = cols_rows.next()? {
+            columns.push(ColumnInfo {
+                name: cols_row.get(0)?,
+                ty: cols_row.get(1)?,
+                not_null: cols_row.get(2)?,
+                primary_key: cols_row.get::<_, i64>(3)? > 0,
+            });
+        }
+
+        let relations = table_rels.get(&name).cloned().unwrap_or_default();
+        tables.push(TableInfo {
+            name,
+            columns,
+            relations,
+        });
+    }
+    Ok(tables)
+}
+
 /// Reads out foreign key and reverse relations from an SQLite DB.
 pub fn read_out_table_rels(sqlite_path: &Path) -> Result<TableRels, rusqlite::Error> {
     let conn = Connection::open_with_flags(sqlite_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
 
-    let mut table_rels: TableRels = HashMap::new();
+    let mut table_rels: TableRels = BTreeMap::new();
 
     let mut tables_stmt = conn.prepare("select name from sqlite_master")?;
     let mut tables_rows = tables_stmt.query([])?;
@@ -106,7 +309,7 @@ pub fn read_out_table_rels(sqlite_path: &Path) -> Result<TableRels, rusqlite::Er
 
         // temporary store for reverse relations
         // we don't insert them into table_rels immediately so we can insert shorthands when there is only one rel for a table
-        let mut rev_rels: TableRels = HashMap::new();
+        let mut rev_rels: TableRels = BTreeMap::new();
 
         let mut fk_stmt =
             conn.prepare("select \"from\", \"table\", \"to\"  from pragma_foreign_key_list(?1)")?;
@@ -159,33 +362,184 @@ pub fn read_out_table_rels(sqlite_path: &Path) -> Result<TableRels, rusqlite::Er
     Ok(table_rels)
 }
 
+/// The [`TableRels`] of the current SQLite file, shared by all requests
+///
+/// This also holds the [`QueryCache`], which is replaced along with the relations,
+/// since the translated queries depend on them and the tables of the file.
+#[derive(Clone)]
+pub struct SharedTableRels {
+    inner: Arc<ArcSwap<TableRels>>,
+    cache: Arc<ArcSwap<QueryCache>>,
+    cache_size: usize,
+    lang: String,
+}
+
+impl SharedTableRels {
+    /// Read the relations from the SQLite file at `sqlite_path`
+    ///
+    /// Up to `cache_size` translated GraphQL queries are kept in the cache, with
+    /// `_loc` columns in `lang`.
+    pub fn load(
+        sqlite_path: &Path,
+        cache_size: usize,
+        lang: &str,
+    ) -> Result<Self, rusqlite::Error> {
+        let table_rels = read_out_table_rels(sqlite_path)?;
+        Ok(Self {
+            inner: Arc::new(ArcSwap::from_pointee(table_rels)),
+            cache: Arc::new(ArcSwap::from_pointee(QueryCache::new(cache_size, lang))),
+            cache_size,
+            lang: lang.to_string(),
+        })
+    }
+
+    /// Get the current relations
+    pub fn get(&self) -> Arc<TableRels> {
+        self.inner.load_full()
+    }
+
+    /// Get the current query cache
+    pub fn cache(&self) -> Arc<QueryCache> {
+        self.cache.load_full()
+    }
+
+    /// Re-read the relations from the SQLite file and replace the current ones
+    pub fn refresh(&self, sqlite_path: &Path) -> Result<(), rusqlite::Error> {
+        let table_rels = read_out_table_rels(sqlite_path)?;
+        self.inner.store(Arc::new(table_rels));
+        self.cache
+            .store(Arc::new(QueryCache::new(self.cache_size, &self.lang)));
+        Ok(())
+    }
+
+    /// Refresh the relations whenever the SQLite file at `sqlite_path` changes
+    pub fn spawn_watcher(
+        &self,
+        sqlite_path: &Path,
+        events: ReloadEvents,
+    ) -> Result<(), notify::Error> {
+        let this = self.clone();
+        spawn_file_watcher(sqlite_path, move |path| {
+            info!("(re-)loading table relations from '{}'", path.display());
+            match this.refresh(path) {
+                Ok(()) => events.send(Reload::Sqlite),
+                Err(e) => error!("Failed to re-load table relations: {}", e),
+            }
+        })
+    }
+}
+
 /// Parses a GraphQl query, transforms it into equivalent SQL, runs it against the DB, and returns the output transformed to matching json.
+///
+/// Variables like `$id` in arguments are bound as SQL parameters, so the translation
+/// can be cached independently of their `variables`.
 pub(super) fn graphql(
-    sqlite_path: &Path,
+    conn: &Connection,
+    table_rels: &TableRels,
+    cache: &QueryCache,
+    query: &str,
+    variables: &Variables,
+) -> Result<String, QueryError> {
+    let ops = cache.get_or_compile(false, query, || {
+        let doc = parse_query::<String>(query)?;
+        let (defs, fragments) = split_definitions(&doc);
+        let def = match defs.last() {
+            Some(x) => x,
+            None => {
+                return Err(invalid_query("no operation found".to_string()));
+            }
+        };
+        let fields = compile_operation(conn, table_rels, cache.lang(), &fragments, def)?;
+        Ok(vec![CompiledOperation {
+            name: String::new(),
+            defaults: variable_defaults(def)?,
+            fields,
+        }])
+    })?;
+    run_fields(conn, &ops[0], variables)
+}
+
+/// Runs every named query operation of a GraphQL document, and returns a JSON object
+/// with the result of each operation by name.
+///
+/// The operations are resolved one after the other, each with its own statements,
+/// so they can query unrelated tables. Fragments are shared between them.
+pub(super) fn graphql_bulk(
+    conn: &Connection,
     table_rels: &TableRels,
+    cache: &QueryCache,
     query: &str,
+    variables: &Variables,
 ) -> Result<String, QueryError> {
-    let doc = parse_query::<String>(query)?;
+    let ops = cache.get_or_compile(true, query, || {
+        let doc = parse_query::<String>(query)?;
+        let (defs, fragments) = split_definitions(&doc);
+        if defs.is_empty() {
+            return Err(invalid_query("no operation found".to_string()));
+        }
+
+        let mut names = HashSet::new();
+        let mut ops = vec![];
+        for def in defs {
+            let name = match def {
+                OperationDefinition::Query(Query {
+                    name: Some(name), ..
+                }) => name,
+                _ => {
+                    return Err(invalid_query(format!(
+                        "Every operation needs to be a named query: {def}"
+                    )));
+                }
+            };
+            if !names.insert(name) {
+                return Err(invalid_query(format!("Duplicate operation name {name}")));
+            }
+            ops.push(CompiledOperation {
+                name: name.clone(),
+                defaults: variable_defaults(def)?,
+                fields: compile_operation(conn, table_rels, cache.lang(), &fragments, def)?,
+            });
+        }
+        Ok(ops)
+    })?;
+
+    let mut kv = vec![];
+    for op in ops.iter() {
+        kv.push(format!(
+            "\"{}\":{}",
+            op.name,
+            run_fields(conn, op, variables)?
+        ));
+    }
+    Ok(format!("{{{}}}", kv.join(",")))
+}
 
+/// Splits a GraphQL document into its operations and fragments
+fn split_definitions<'a>(
+    doc: &'a Document<'a, String>,
+) -> (Vec<&'a OperationDefinition<'a, String>>, Fragments<'a>) {
     let mut fragments: Fragments = HashMap::new();
-    let mut def = None;
+    let mut defs = vec![];
 
     for definition in &doc.definitions {
         match definition {
-            Definition::Operation(op_def) => def = Some(op_def),
+            Definition::Operation(op_def) => defs.push(op_def),
             Definition::Fragment(frag) => {
                 fragments.insert(&frag.name, &frag.selection_set.items);
             }
         }
     }
+    (defs, fragments)
+}
 
-    let def = match def {
-        Some(x) => x,
-        None => {
-            return Err(invalid_query("no operation found".to_string()));
-        }
-    };
-
+/// Translates the top-level fields of a single query operation to SQL
+fn compile_operation(
+    conn: &Connection,
+    table_rels: &TableRels,
+    lang: &str,
+    fragments: &Fragments,
+    def: &OperationDefinition<String>,
+) -> Result<Vec<CompiledField>, QueryError> {
     let selections = &match def {
         OperationDefinition::Query(query) => &query.selection_set,
         OperationDefinition::SelectionSet(sel) => sel,
@@ -195,9 +549,7 @@ pub(super) fn graphql(
     }
     .items;
 
-    let conn = Connection::open_with_flags(sqlite_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
-
-    let mut kv = vec![];
+    let mut fields = vec![];
     for selection in selections {
         match selection {
             Selection::FragmentSpread(_) => {
@@ -211,37 +563,161 @@ pub(super) fn graphql(
                 ));
             }
             Selection::Field(f) => {
-                let mut table_query = field_to_table_query(table_rels, &fragments, f)?;
-
-                let query = table_query_to_sql(&table_query);
+                let mut table_query = field_to_table_query(table_rels, lang, fragments, f)?;
+                table_query.order_by = order_by(conn, &table_query.name, f)?;
 
-                let mut stmt = conn.prepare(&query)?;
-                let mut rows = stmt.query([])?;
                 let key = if let Some(alias) = &f.alias {
                     alias
                 } else {
                     &f.name
                 };
-                kv.push(format!(
-                    "\"{}\":{}",
-                    key,
-                    table_to_json(&mut table_query, &mut rows)?
-                ));
+                fields.push(CompiledField {
+                    key: key.clone(),
+                    sql: table_query_to_sql(&table_query),
+                    table_query,
+                });
+            }
+        }
+    }
+    Ok(fields)
+}
+
+/// The default values of the variables declared by a query operation
+fn variable_defaults(def: &OperationDefinition<String>) -> Result<Variables, QueryError> {
+    let mut defaults = Variables::new();
+    if let OperationDefinition::Query(query) = def {
+        for var in &query.variable_definitions {
+            if let Some(value) = &var.default_value {
+                let value = literal_to_json(value).ok_or_else(|| {
+                    invalid_query(format!("Unsupported default value for ${}", var.name))
+                })?;
+                defaults.insert(var.name.clone(), value);
             }
         }
     }
+    Ok(defaults)
+}
+
+/// Converts a scalar GraphQL literal to JSON
+fn literal_to_json(value: &Value<String>) -> Option<serde_json::Value> {
+    match value {
+        Value::Int(n) => n.as_i64().map(serde_json::Value::from),
+        Value::Float(f) => Some(serde_json::Value::from(*f)),
+        Value::String(s) | Value::Enum(s) => Some(serde_json::Value::from(s.clone())),
+        Value::Boolean(b) => Some(serde_json::Value::from(*b)),
+        Value::Null => Some(serde_json::Value::Null),
+        _ => None,
+    }
+}
+
+/// Converts the JSON value of a variable to an SQL parameter
+fn variable_to_sql(name: &str, value: &serde_json::Value) -> Result<SqlValue, QueryError> {
+    match value {
+        serde_json::Value::Null => Ok(SqlValue::Null),
+        serde_json::Value::Bool(b) => Ok(SqlValue::Integer(i64::from(*b))),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Ok(SqlValue::Integer(i)),
+            None => Ok(SqlValue::Real(n.as_f64().unwrap_or(f64::NAN))),
+        },
+        serde_json::Value::String(s) => Ok(SqlValue::Text(s.clone())),
+        _ => Err(QueryError {
+            kind: QueryErrorKind::Client,
+            error: format!("invalid variable ${}", name),
+            message: "only scalar values are supported".to_string(),
+        }),
+    }
+}
+
+/// Runs the SQL of compiled top-level fields and returns them as a JSON object
+fn run_fields(
+    conn: &Connection,
+    op: &CompiledOperation,
+    variables: &Variables,
+) -> Result<String, QueryError> {
+    let mut kv = vec![];
+    for field in &op.fields {
+        // the buffers of the table query are per execution, so never use the cached one
+        let mut table_query = field.table_query.clone();
+        let mut stmt = conn.prepare(&field.sql)?;
+        for index in 1..=stmt.parameter_count() {
+            // all parameters are variables, named `$name` in the SQL as well
+            let name = stmt
+                .parameter_name(index)
+                .map(|name| name.trim_start_matches('$').to_string())
+                .unwrap_or_default();
+            let value = variables
+                .get(&name)
+                .or_else(|| op.defaults.get(&name))
+                .ok_or_else(|| QueryError {
+                    kind: QueryErrorKind::Client,
+                    error: format!("missing variable ${}", name),
+                    message: "the variable has no value and no default".to_string(),
+                })?;
+            stmt.raw_bind_parameter(index, variable_to_sql(&name, value)?)?;
+        }
+        let mut rows = stmt.raw_query();
+        kv.push(format!(
+            "\"{}\":{}",
+            field.key,
+            table_to_json(&mut table_query, &mut rows)?
+        ));
+    }
     Ok(format!("{{{}}}", kv.join(",")))
 }
 
+/// Parses the `orderBy: "column"` or `orderBy: "column_desc"` argument of a top-level field.
+fn order_by(
+    conn: &Connection,
+    table_name: &str,
+    field: &Field<String>,
+) -> Result<Option<OrderBy>, QueryError> {
+    let value = match field.arguments.iter().find(|(key, _)| key == "orderBy") {
+        Some((_, Value::String(value) | Value::Enum(value))) => value,
+        Some((_, value)) => {
+            return Err(invalid_query(format!(
+                "orderBy must be a column name, got {}",
+                value
+            )))
+        }
+        None => return Ok(None),
+    };
+
+    let mut stmt = conn.prepare("select name from pragma_table_info(?1)")?;
+    let columns = stmt
+        .query_map([table_name], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let has_column = |name: &str| columns.iter().any(|c| c == name);
+
+    if has_column(value) {
+        return Ok(Some(OrderBy {
+            column: value.clone(),
+            desc: false,
+        }));
+    }
+    match value.strip_suffix("_desc") {
+        Some(column) if has_column(column) => Ok(Some(OrderBy {
+            column: column.to_string(),
+            desc: true,
+        })),
+        _ => Err(QueryError {
+            kind: QueryErrorKind::Client,
+            error: format!("invalid orderBy {}", value),
+            message: format!("table {} has no such column", table_name),
+        }),
+    }
+}
+
 /// Recursively parses a GraphQL field into an abstract TableQuery.
 fn field_to_table_query(
     table_rels: &TableRels,
+    lang: &str,
     fragments: &Fragments,
     field: &Field<String>,
 ) -> Result<TableQuery, QueryError> {
     let mut fragment_calls = vec![];
     field_to_table_query_inner(
         table_rels,
+        lang,
         fragments,
         &mut fragment_calls,
         field,
@@ -251,6 +727,7 @@ fn field_to_table_query(
 
 fn field_to_table_query_inner<'a>(
     table_rels: &TableRels,
+    lang: &str,
     fragments: &'a Fragments,
     fragment_calls: &mut Vec<&'a str>,
     field: &'a Field<String>,
@@ -262,19 +739,28 @@ fn field_to_table_query_inner<'a>(
         constraints: vec![],
         rowid: 0,
         joins: vec![],
+        order_by: None,
         flushed_outputs: vec![],
     };
 
     for (key, value) in &field.arguments {
-        table_query
-            .constraints
-            .push(format!("{} == {}", key, value));
+        if key == "orderBy" {
+            // handled for the top-level field by `order_by`
+            continue;
+        }
+        let constraint = match value {
+            // bound as a parameter by `run_fields`
+            Value::Variable(name) => format!("{} == ${}", key, name),
+            _ => format!("{} == {}", key, value),
+        };
+        table_query.constraints.push(constraint);
     }
 
     let this_table_rels = if let Some(rels) = table_rels.get(&table_query.name) {
         rels
     } else {
         return Err(QueryError {
+            kind: QueryErrorKind::Client,
             error: format!("invalid table name {}", table_query.name),
             message: "table does not exist".to_string(),
         });
@@ -282,6 +768,7 @@ fn field_to_table_query_inner<'a>(
 
     process_selections(
         table_rels,
+        lang,
         fragments,
         fragment_calls,
         this_table_rels,
@@ -294,9 +781,10 @@ fn field_to_table_query_inner<'a>(
 
 fn process_selections<'a>(
     table_rels: &TableRels,
+    lang: &str,
     fragments: &'a Fragments,
     fragment_calls: &mut Vec<&'a str>,
-    this_table_rels: &HashMap<String, TableRel>,
+    this_table_rels: &BTreeMap<String, TableRel>,
     table_query: &mut TableQuery,
     selections: &'a Vec<Selection<String>>,
 ) -> Result<(), QueryError> {
@@ -309,7 +797,7 @@ fn process_selections<'a>(
                     table_query.cols.push(if f.name.ends_with("_loc") {
                         let mut localized_name = f.name.clone();
                         localized_name.truncate(localized_name.len() - "loc".len());
-                        localized_name.push_str("en_US"); // todo: proper localization support
+                        localized_name.push_str(lang);
                         Column {
                             name: localized_name,
                             alias: Some(f.name.clone()),
@@ -323,12 +811,18 @@ fn process_selections<'a>(
                             value: None,
                         }
                     });
+                } else if f.arguments.iter().any(|(key, _)| key == "orderBy") {
+                    return Err(invalid_query(format!(
+                        "orderBy is only supported on top-level fields, not on {}",
+                        f.name
+                    )));
                 } else {
                     // curly braces, this requires the field to be a valid relation
                     let rel = if let Some(rel) = this_table_rels.get(&f.name) {
                         rel
                     } else {
                         return Err(QueryError {
+                            kind: QueryErrorKind::Client,
                             error: format!("field {} is not a foreign key", &f.name),
                             message: "field has items but is not a FK according to the DB"
                                 .to_string(),
@@ -338,6 +832,7 @@ fn process_selections<'a>(
                     // recurse with the fields in curly braces
                     let tq = field_to_table_query_inner(
                         table_rels,
+                        lang,
                         fragments,
                         fragment_calls,
                         f,
@@ -372,6 +867,7 @@ fn process_selections<'a>(
                     fragment_calls.push(&spread.fragment_name);
                     process_selections(
                         table_rels,
+                        lang,
                         fragments,
                         fragment_calls,
                         this_table_rels,
@@ -406,9 +902,15 @@ fn table_query_to_sql(table_query: &TableQuery) -> String {
     if !constraints.is_empty() {
         write!(query, " where {}", constraints.join(" and ")).unwrap();
     }
+    query.push_str(" order by ");
+    if let Some(order_by) = &table_query.order_by {
+        let dir = if order_by.desc { " desc" } else { "" };
+        let column = order_by.column.replace('"', "\"\"");
+        write!(query, "t0.\"{}\"{}, ", column, dir).unwrap();
+    }
     write!(
         query,
-        " order by {}",
+        "{}",
         (0..tables.len())
             .map(|x| format!("t{}.rowid", x))
             .collect::<Vec<String>>()
@@ -453,26 +955,44 @@ fn table_query_to_sql_inner(
 }
 
 /// Formats a sqlite value as json.
+///
+/// Blobs are not necessarily valid UTF-8, so they are base64 encoded.
 fn valueref_to_json(valueref: &ValueRef) -> Result<String, rusqlite::Error> {
     let mut str = String::new();
     match valueref {
         ValueRef::Null => str.push_str("null"),
         ValueRef::Integer(x) => str.push_str(&x.to_string()),
         ValueRef::Real(x) => str.push_str(&x.to_string()),
-        ValueRef::Text(x) | ValueRef::Blob(x) => {
-            str.push('"');
-            str.push_str(
-                &std::str::from_utf8(x)
-                    .map_err(rusqlite::Error::Utf8Error)?
-                    .replace('\\', "\\\\")
-                    .replace('"', "\\\""),
-            );
-            str.push('"');
-        }
+        ValueRef::Text(x) => push_json_str(
+            &mut str,
+            std::str::from_utf8(x).map_err(rusqlite::Error::Utf8Error)?,
+        ),
+        ValueRef::Blob(x) => push_json_str(&mut str, &base64::encode(x)),
     }
     Ok(str)
 }
 
+/// Appends `value` as a quoted and escaped JSON string.
+fn push_json_str(buf: &mut String, value: &str) {
+    buf.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            '\u{08}' => buf.push_str("\\b"),
+            '\u{0c}' => buf.push_str("\\f"),
+            c if c < '\u{20}' => {
+                let _ = write!(buf, "\\u{:04x}", c as u32);
+            }
+            c => buf.push(c),
+        }
+    }
+    buf.push('"');
+}
+
 /// Given an SQLite query result `rows`, use the structure info from `table_query` to transform it to hierarchical JSON.
 fn table_to_json(
     table_query: &mut TableQuery,
@@ -507,7 +1027,17 @@ fn table_to_json_inner(
         if let rusqlite::types::ValueRef::Integer(rowid) = rowid_col {
             // since SQL joins duplicate values, we use the sorted rowid to deduplicate
             match rowid.cmp(&table_query.rowid) {
-                Ordering::Greater => {
+                Ordering::Equal => {
+                    // already encountered this entry in a previous join, skip this table but not subtables, which might have new subentries
+                    *icol += table_query.cols.len();
+                }
+                // with an `orderBy`, top-level rows are grouped but not sorted by rowid
+                Ordering::Less if table_query.order_by.is_none() => {
+                    // already encountered this entry in a previous join, skip this and all subtables
+                    skip = true;
+                    *icol += table_query.cols.len();
+                }
+                Ordering::Greater | Ordering::Less => {
                     // new row, read in data
                     if table_query.rowid > 0 {
                         // we read a row before, whose subbuffers need to be flushed out
@@ -522,15 +1052,6 @@ fn table_to_json_inner(
                         *icol += 1;
                     }
                 }
-                Ordering::Equal => {
-                    // already encountered this entry in a previous join, skip this table but not subtables, which might have new subentries
-                    *icol += table_query.cols.len();
-                }
-                Ordering::Less => {
-                    // already encountered this entry in a previous join, skip this and all subtables
-                    skip = true;
-                    *icol += table_query.cols.len();
-                }
             }
         } else {
             // left join is null, skip
@@ -589,3 +1110,148 @@ fn flush_table_data(table_query: &mut TableQuery) -> String {
     table_query.rowid = 0;
     format!("{{{}}}", kv.join(","))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rusqlite::{types::ValueRef, Connection};
+
+    use super::{graphql, graphql_bulk, valueref_to_json, QueryCache, TableRels, Variables};
+
+    fn objects_db() -> (Connection, TableRels) {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "create table Objects (id integer, name text);
+             insert into Objects values (1, 'b'), (2, 'c'), (3, 'a');",
+        )
+        .unwrap();
+        let mut table_rels = TableRels::new();
+        table_rels.insert("Objects".to_string(), BTreeMap::new());
+        (conn, table_rels)
+    }
+
+    #[test]
+    fn test_graphql_order_by() {
+        let (conn, rels) = objects_db();
+        let vars = Variables::new();
+        let cache = QueryCache::new(0, "en_US");
+        let asc = r#"{ Objects(orderBy: "name") { id } }"#;
+        let asc = graphql(&conn, &rels, &cache, asc, &vars).unwrap();
+        assert_eq!(asc, r#"{"Objects":[{"id":3},{"id":1},{"id":2}]}"#);
+        let desc = r#"{ Objects(orderBy: "name_desc") { id } }"#;
+        let desc = graphql(&conn, &rels, &cache, desc, &vars).unwrap();
+        assert_eq!(desc, r#"{"Objects":[{"id":2},{"id":1},{"id":3}]}"#);
+        let err = r#"{ Objects(orderBy: "size") { id } }"#;
+        let err = graphql(&conn, &rels, &cache, err, &vars).unwrap_err();
+        assert!(err.is_client_error());
+    }
+
+    #[test]
+    fn test_graphql_cache() {
+        let (conn, rels) = objects_db();
+        let vars = Variables::new();
+        let cache = QueryCache::new(2, "en_US");
+        let query = "{ Objects { id name } }";
+        let expected =
+            r#"{"Objects":[{"id":1,"name":"b"},{"id":2,"name":"c"},{"id":3,"name":"a"}]}"#;
+        // the second run uses the cached translation, with fresh buffers
+        assert_eq!(
+            graphql(&conn, &rels, &cache, query, &vars).unwrap(),
+            expected
+        );
+        assert_eq!(
+            graphql(&conn, &rels, &cache, query, &vars).unwrap(),
+            expected
+        );
+        assert_eq!(cache.len(), 1);
+
+        graphql(&conn, &rels, &cache, "{ Objects { id } }", &vars).unwrap();
+        graphql(&conn, &rels, &cache, query, &vars).unwrap();
+        graphql(&conn, &rels, &cache, "{ Objects { name } }", &vars).unwrap();
+        // `{ Objects { id } }` was the least recently used
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            graphql(&conn, &rels, &cache, query, &vars).unwrap(),
+            expected
+        );
+
+        assert!(graphql(&conn, &rels, &cache, "{ Missions { id } }", &vars).is_err());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_graphql_bulk() {
+        let (conn, rels) = objects_db();
+        let vars = Variables::new();
+        let query = r#"query first { Objects(id: 1) { name } }
+            query last { Objects(orderBy: "id_desc") { id } }"#;
+        let cache = QueryCache::new(0, "en_US");
+        let json = graphql_bulk(&conn, &rels, &cache, query, &vars).unwrap();
+        assert_eq!(
+            json,
+            r#"{"first":{"Objects":[{"name":"b"}]},"last":{"Objects":[{"id":3},{"id":2},{"id":1}]}}"#
+        );
+        let unnamed = graphql_bulk(&conn, &rels, &cache, "{ Objects { id } }", &vars).unwrap_err();
+        assert!(unnamed.is_client_error());
+        let duplicate = "query a { Objects { id } } query a { Objects { name } }";
+        assert!(graphql_bulk(&conn, &rels, &cache, duplicate, &vars).is_err());
+    }
+
+    #[test]
+    fn test_graphql_loc() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "create table Names (id integer, name_en_US text, name_de_DE text);
+             insert into Names values (1, 'Bubble', 'Blase');",
+        )
+        .unwrap();
+        let mut rels = TableRels::new();
+        rels.insert("Names".to_string(), BTreeMap::new());
+        let vars = Variables::new();
+        let query = "{ Names { id name_loc } }";
+        let en = graphql(&conn, &rels, &QueryCache::new(0, "en_US"), query, &vars).unwrap();
+        assert_eq!(en, r#"{"Names":[{"id":1,"name_loc":"Bubble"}]}"#);
+        let de = graphql(&conn, &rels, &QueryCache::new(0, "de_DE"), query, &vars).unwrap();
+        assert_eq!(de, r#"{"Names":[{"id":1,"name_loc":"Blase"}]}"#);
+    }
+
+    #[test]
+    fn test_graphql_variables() {
+        let (conn, rels) = objects_db();
+        let cache = QueryCache::new(1, "en_US");
+        let query = "query($id: Int = 3) { Objects(id: $id) { name } }";
+        let run = |json: &str| {
+            let vars: Variables = serde_json::from_str(json).unwrap();
+            graphql(&conn, &rels, &cache, query, &vars)
+        };
+        assert_eq!(run(r#"{"id":1}"#).unwrap(), r#"{"Objects":[{"name":"b"}]}"#);
+        // same cached translation, different value
+        assert_eq!(run(r#"{"id":2}"#).unwrap(), r#"{"Objects":[{"name":"c"}]}"#);
+        assert_eq!(run("{}").unwrap(), r#"{"Objects":[{"name":"a"}]}"#);
+        assert!(run(r#"{"id":[1]}"#).unwrap_err().is_client_error());
+
+        // strings are bound as parameters, not spliced into the SQL
+        let query = "query($name: String) { Objects(name: $name) { id } }";
+        let vars: Variables = serde_json::from_str(r#"{"name":"a' or 1 == 1 --"}"#).unwrap();
+        let json = graphql(&conn, &rels, &cache, query, &vars).unwrap();
+        assert_eq!(json, r#"{"Objects":[]}"#);
+        let missing = graphql(&conn, &rels, &cache, query, &Variables::new()).unwrap_err();
+        assert!(missing.is_client_error());
+    }
+
+    #[test]
+    fn test_valueref_to_json_escapes_text() {
+        let text = ValueRef::Text(b"line \"one\"\nline\ttwo\\\x01");
+        let json = valueref_to_json(&text).unwrap();
+        assert_eq!(json, r#""line \"one\"\nline\ttwo\\\u0001""#);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value, "line \"one\"\nline\ttwo\\\x01");
+    }
+
+    #[test]
+    fn test_valueref_to_json_encodes_blobs() {
+        let blob = ValueRef::Blob(&[0xff, 0x00, 0x80]);
+        assert_eq!(valueref_to_json(&blob).unwrap(), "\"/wCA\"");
+    }
+}
@@ -0,0 +1,127 @@
+//! # Response size limit
+//!
+//! `general.max_response_bytes` is enforced while a response is written, so a
+//! response over the limit is never buffered as a whole. Serializing into a
+//! [`LimitedBuf`] fails as soon as the limit is exceeded, which the API turns into
+//! a `413 Payload Too Large`.
+
+use std::{
+    fmt, io,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The configured limit, `usize::MAX` if there is none
+static MAX_RESPONSE_BYTES: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Set the limit for all responses of this process
+pub(super) fn set_max_response_bytes(max: Option<usize>) {
+    MAX_RESPONSE_BYTES.store(max.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+/// The limit for all responses, if there is one
+pub(super) fn max_response_bytes() -> Option<usize> {
+    match MAX_RESPONSE_BYTES.load(Ordering::Relaxed) {
+        usize::MAX => None,
+        max => Some(max),
+    }
+}
+
+/// The error of writing more than `max` bytes
+#[derive(Debug)]
+pub(super) struct TooLarge {
+    pub max: usize,
+}
+
+impl fmt::Display for TooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the response is larger than {} bytes", self.max)
+    }
+}
+
+impl std::error::Error for TooLarge {}
+
+/// A buffer that refuses to grow beyond the response limit
+pub(super) struct LimitedBuf {
+    buf: Vec<u8>,
+    max: usize,
+    exceeded: bool,
+}
+
+impl LimitedBuf {
+    /// Create a buffer with the configured limit
+    pub fn new() -> Self {
+        Self::with_max(MAX_RESPONSE_BYTES.load(Ordering::Relaxed))
+    }
+
+    fn with_max(max: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            max,
+            exceeded: false,
+        }
+    }
+
+    /// The error to report if writing failed, if it failed because of the limit
+    pub fn too_large(&self) -> Option<TooLarge> {
+        self.exceeded.then_some(TooLarge { max: self.max })
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn try_extend(&mut self, bytes: &[u8]) -> bool {
+        if self.buf.len() + bytes.len() > self.max {
+            self.exceeded = true;
+            return false;
+        }
+        self.buf.extend_from_slice(bytes);
+        true
+    }
+}
+
+impl io::Write for LimitedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.try_extend(buf) {
+            true => Ok(buf.len()),
+            false => Err(io::Error::new(
+                io::ErrorKind::Other,
+                TooLarge { max: self.max },
+            )),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Write for LimitedBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.try_extend(s.as_bytes()) {
+            true => Ok(()),
+            false => Err(fmt::Error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write as _;
+
+    use super::LimitedBuf;
+
+    #[test]
+    fn test_limited_buf() {
+        let mut buf = LimitedBuf::with_max(4);
+        assert!(write!(buf, "abcd").is_ok());
+        assert!(buf.too_large().is_none());
+        assert!(write!(buf, "e").is_err());
+        assert_eq!(buf.too_large().map(|e| e.max), Some(4));
+        assert_eq!(buf.into_inner(), b"abcd");
+
+        let mut buf = LimitedBuf::with_max(4);
+        assert!(serde_json::to_writer(&mut buf, &[1, 2, 3]).is_err());
+        assert!(buf.too_large().is_some());
+    }
+}
@@ -2,9 +2,8 @@ use std::{collections::btree_map, fmt, ops::Deref, str};
 
 use assembly_xml::localization::{Interner, Key, LocaleNode, LocaleNodeRef};
 use http::StatusCode;
-use hyper::body::{Buf, Bytes};
+use hyper::body::Bytes;
 use serde::{
-    de::DeserializeSeed,
     ser::{SerializeMap, SerializeStruct},
     Serialize,
 };
@@ -13,7 +12,7 @@ use crate::data::locale::LocaleRoot;
 
 use self::query::{CompositeKey, IntOrKey, IntOrStr, IntStringSet, VecIntStringSetSeed};
 
-use super::{adapter::Keys, Accept, ApiFuture, RestPath};
+use super::{adapter::Keys, Accept, ApiFuture, BodyFormat, RestPath};
 
 mod query;
 
@@ -89,16 +88,21 @@ impl<'a, T: fmt::Display + Serialize> Serialize for WithSuffix<'a, T> {
     }
 }
 
+/// The full subtree of a node
+///
+/// With a `depth`, only that many levels of children are expanded. Deeper nodes
+/// that have children are a `$truncated` marker with the number of children.
 pub(super) struct All<'a, 's> {
     inner: LocaleNodeRef<'a, 's>,
+    depth: Option<usize>,
 }
 
 impl<'a, 's: 'a> All<'a, 's> {
-    pub fn new(inner: LocaleNodeRef<'a, 's>) -> Self {
-        Self { inner }
+    pub fn new(inner: LocaleNodeRef<'a, 's>, depth: Option<usize>) -> Self {
+        Self { inner, depth }
     }
 
-    pub fn new_inner(mut inner: LocaleNodeRef<'a, 's>) -> (String, Self) {
+    pub fn new_inner(mut inner: LocaleNodeRef<'a, 's>, depth: Option<usize>) -> (String, Self) {
         let mut suffix = String::new();
         loop {
             let node = inner.node();
@@ -118,7 +122,7 @@ impl<'a, 's: 'a> All<'a, 's> {
             }
             break;
         }
-        (suffix, Self { inner })
+        (suffix, Self { inner, depth })
     }
 }
 
@@ -133,17 +137,27 @@ impl<'a, 's> Serialize for All<'a, 's> {
         let count = v_count + i_count + s_count;
 
         let sub_count = i_count + s_count;
-        if sub_count > 0 {
+        if sub_count > 0 && self.depth == Some(0) {
+            let mut m = serializer.serialize_map(Some(v_count + 3))?;
+            if let Some(v) = self.inner.value() {
+                m.serialize_entry(&"$value", v)?;
+            }
+            m.serialize_entry(&"$truncated", &true)?;
+            m.serialize_entry(&"$int_children", &i_count)?;
+            m.serialize_entry(&"$str_children", &s_count)?;
+            m.end()
+        } else if sub_count > 0 {
+            let depth = self.depth.map(|d| d - 1);
             let mut m = serializer.serialize_map(Some(count))?;
             if let Some(v) = self.inner.value() {
                 m.serialize_entry(&"$value", v)?;
             }
             for (key, inner) in self.inner.int_child_iter() {
-                let value = All::new(inner);
+                let value = All::new(inner, depth);
                 m.serialize_entry(&key, &value)?;
             }
             for (key, inner) in self.inner.str_child_iter() {
-                let (suffix, value) = All::new_inner(inner);
+                let (suffix, value) = All::new_inner(inner, depth);
                 m.serialize_entry(&WithSuffix::new(&key.deref(), &suffix), &value)?;
             }
             m.end()
@@ -191,7 +205,6 @@ pub(super) fn select_node<'a, 's>(
         }
         return match (seg, rest.0.next()) {
             ("$all", None) => Some((node, Mode::All)),
-            ("", None) => Some((node, Mode::Pod)),
             _ => None,
         };
     }
@@ -201,6 +214,60 @@ pub(super) fn select_node<'a, 's>(
 struct Query<'l, 's, 'q> {
     layers: &'q [IntStringSet],
     node: LocaleNodeRef<'l, 's>,
+    /// Leave out entries that have no value at the end of the query
+    prune: bool,
+}
+
+impl<'l, 's, 'q> Query<'l, 's, 'q> {
+    /// The nodes selected by `first`, with a query for the `rest` of the layers
+    fn children<'a>(
+        &'a self,
+        first: &'q IntStringSet,
+        rest: &'q [IntStringSet],
+    ) -> impl Iterator<Item = (IntOrStr<'a>, Query<'l, 's, 'q>)> + 'a {
+        let node = &self.node;
+        let int_nodes = first
+            .int_keys
+            .iter()
+            .filter_map(move |&int| node.get_int(int).map(|n| (IntOrStr::Int(int), n)));
+        let str_nodes = first.str_keys.iter().filter_map(move |&s| {
+            let string = node.strs().lookup(s);
+            node.get_str(s).map(|n| (IntOrStr::Str(string), n))
+        });
+        let vec_nodes = first.vec_keys.iter().filter_map(move |s| {
+            node_get_vec(node.clone(), s).map(|n| (IntOrStr::Str(&s.full), n))
+        });
+        let prune = self.prune;
+        int_nodes
+            .chain(str_nodes)
+            .chain(vec_nodes)
+            .map(move |(k, node)| {
+                let query = Query {
+                    layers: rest,
+                    node,
+                    prune,
+                };
+                (k, query)
+            })
+            .filter(move |(_, query)| !prune || query.is_populated())
+    }
+
+    /// Whether any value is reached by this query
+    fn is_populated(&self) -> bool {
+        match self.layers.split_first() {
+            Some((first, rest)) => self.children(first, rest).next().is_some(),
+            None => self.node.value().is_some(),
+        }
+    }
+}
+
+/// Select a node by a full underscore-joined key like `Missions_1_name`
+pub(super) fn select_by_key<'l, 's>(
+    node: LocaleNodeRef<'l, 's>,
+    key: &str,
+) -> Option<LocaleNodeRef<'l, 's>> {
+    let key = CompositeKey::new(key.to_owned(), node.strs())?;
+    node_get_vec(node, &key)
 }
 
 fn node_get_vec<'l, 's>(
@@ -226,21 +293,7 @@ impl<'l, 's, 'q> Serialize for Query<'l, 's, 'q> {
         S: serde::Serializer,
     {
         if let Some((first, rest)) = self.layers.split_first() {
-            let int_nodes = first
-                .int_keys
-                .iter()
-                .filter_map(|&int| self.node.get_int(int).map(|n| (IntOrStr::Int(int), n)));
-            let str_nodes = first.str_keys.iter().filter_map(|&s| {
-                let string = self.node.strs().lookup(s);
-                self.node.get_str(s).map(|n| (IntOrStr::Str(string), n))
-            });
-            let node = &self.node;
-            let vec_nodes = first
-                .vec_keys
-                .iter()
-                .filter_map(|s| node_get_vec(node.clone(), s).map(|n| (IntOrStr::Str(&s.full), n)));
-            let nodes = int_nodes.chain(str_nodes).chain(vec_nodes);
-            serializer.collect_map(nodes.map(|(k, node)| (k, Query { layers: rest, node })))
+            serializer.collect_map(self.children(first, rest))
         } else if let Some(v) = self.node.value() {
             serializer.serialize_str(v)
         } else {
@@ -253,30 +306,29 @@ impl<'l, 's, 'q> Serialize for Query<'l, 's, 'q> {
 pub(super) fn locale_query<ReqBody>(
     root: &LocaleRoot,
     accept: Accept,
+    format: BodyFormat,
     rest: RestPath,
+    prune: bool,
     body: ReqBody,
 ) -> ApiFuture
 where
     ReqBody: http_body::Body<Data = Bytes> + Send + Unpin + 'static,
-    ReqBody::Error: fmt::Display,
+    ReqBody::Error: Into<super::BoxError>,
 {
     let key = rest.join('_');
     let root = root.root.clone();
     ApiFuture::boxed(async move {
-        let rdr = match hyper::body::aggregate(body).await {
-            Ok(buf) => buf.reader(),
-            Err(e) => {
-                return super::reply_400(accept, "Failed to decode body", e);
-            }
+        let bytes = match super::read_body(accept, body).await {
+            Ok(bytes) => bytes,
+            Err(r) => return r,
         };
         let node = root.node();
         let strs = node.strs();
-        let mut de = serde_json::Deserializer::from_reader(rdr);
         let query_layers: Vec<query::IntStringSet> =
-            match VecIntStringSetSeed(strs).deserialize(&mut de) {
+            match format.deserialize_seed(VecIntStringSetSeed(strs), &bytes) {
                 Ok(q) => q,
                 Err(e) => {
-                    return super::reply_400(accept, "Failed to parse body as JSON", e);
+                    return super::reply_400(accept, format.parse_error(), e);
                 }
             };
         let rest = key.split('_');
@@ -284,9 +336,66 @@ where
             Some((node, _)) => Query {
                 layers: &query_layers[..],
                 node,
+                prune,
             },
             None => return Ok(super::reply_404()),
         };
         super::reply(accept, &query, StatusCode::OK)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use assembly_xml::localization::load_locale;
+    use serde_json::json;
+
+    use crate::{api::RestPath, data::locale::LocaleRoot};
+
+    use super::{select_node, All, Mode};
+
+    const LOCALE_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<localization version="1.7">
+<locales count="1"><locale>en_US</locale></locales>
+<phrases count="3">
+<phrase id="Objects_1_name"><translation locale="en_US">Brick</translation></phrase>
+<phrase id="Objects_1_description"><translation locale="en_US">A brick</translation></phrase>
+<phrase id="Objects_2_name"><translation locale="en_US">Plate</translation></phrase>
+</phrases>
+</localization>
+"#;
+
+    #[test]
+    fn test_all_depth() {
+        let path = std::env::temp_dir().join("paradox-test-all-depth.xml");
+        fs::write(&path, LOCALE_XML).unwrap();
+        let root = load_locale(&path).map(LocaleRoot::new);
+        fs::remove_file(&path).unwrap();
+        let root = root.unwrap();
+
+        let all = |depth| {
+            let rest = RestPath("Objects/$all".split('/'));
+            let (node, mode) = select_node(root.root.node(), rest).unwrap();
+            assert!(matches!(mode, Mode::All));
+            serde_json::to_value(All::new(node, depth)).unwrap()
+        };
+        let full = json!({
+            "1": { "name": "Brick", "description": "A brick" },
+            "2": { "name": "Plate" },
+        });
+        assert_eq!(all(None), full);
+        assert_eq!(all(Some(2)), full);
+        assert_eq!(
+            all(Some(1)),
+            json!({
+                "1": { "$truncated": true, "$int_children": 0, "$str_children": 2 },
+                "2": { "$truncated": true, "$int_children": 0, "$str_children": 1 },
+            })
+        );
+        assert_eq!(
+            all(Some(0)),
+            json!({ "$truncated": true, "$int_children": 2, "$str_children": 0 })
+        );
+    }
+}
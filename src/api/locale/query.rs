@@ -125,13 +125,35 @@ pub(super) struct CompositeKey {
     pub(super) full: String,
 }
 
+impl CompositeKey {
+    /// Resolve a full key like `Missions_1_name` against the interned strings
+    pub(super) fn new(full: String, strs: &Interner) -> Option<Self> {
+        let parts = key_parts(&full, strs)?;
+        Some(Self { parts, full })
+    }
+}
+
+/// Split a composite key on `_` into its parts.
+///
+/// Interned strings may themselves contain underscores (e.g. `en_US`), so at
+/// each position the longest run of segments that is a known key wins before
+/// falling back to a single (integer) segment.
 fn key_parts(s: &str, strs: &Interner) -> Option<Vec<IntOrKey>> {
+    let segments: Vec<&str> = s.split('_').collect();
     let mut vec = Vec::new();
-    for part in s.split('_') {
-        vec.push(match part.parse() {
-            Ok(i) => IntOrKey::Int(i),
-            Err(_) => strs.get(part).map(IntOrKey::Key)?,
+    let mut start = 0;
+    while start < segments.len() {
+        let longest = (start + 1..=segments.len()).rev().find_map(|end| {
+            let candidate = segments[start..end].join("_");
+            strs.get(candidate.as_str()).map(|key| (end, key))
         });
+        if let Some((end, key)) = longest {
+            vec.push(IntOrKey::Key(key));
+            start = end;
+        } else {
+            vec.push(IntOrKey::Int(segments[start].parse().ok()?));
+            start += 1;
+        }
     }
     Some(vec)
 }
@@ -171,8 +193,8 @@ impl<'s, 'de> DeserializeSeed<'de> for IntStringSetSeed<'s> {
                 }
                 let mut vec_keys = Vec::with_capacity(full_keys.len());
                 for full in full_keys {
-                    if let Some(parts) = key_parts(&full, self.0) {
-                        vec_keys.push(CompositeKey { parts, full })
+                    if let Some(key) = CompositeKey::new(full, self.0) {
+                        vec_keys.push(key)
                     }
                 }
                 Ok(IntStringSet {
@@ -230,9 +252,12 @@ mod tests {
     use assembly_xml::localization::Interner;
     use serde::de::DeserializeSeed;
 
-    use crate::api::locale::query::IntStringSetSeed;
+    use crate::api::{
+        locale::query::{IntStringSetSeed, VecIntStringSetSeed},
+        BodyFormat,
+    };
 
-    use super::IntStringSet;
+    use super::{key_parts, IntOrKey, IntStringSet};
 
     #[test]
     fn test_deserialize() {
@@ -250,4 +275,49 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_deserialize_yaml() {
+        let mut interner = Interner::with_capacity(100);
+        let key_a = interner.intern("a");
+        let body = b"- [1, 2, a]\n- []\n";
+        assert_eq!(
+            BodyFormat::Yaml
+                .deserialize_seed(VecIntStringSetSeed(&interner), body)
+                .unwrap(),
+            vec![
+                IntStringSet {
+                    int_keys: BTreeSet::from([1, 2]),
+                    str_keys: [key_a].iter().copied().collect(),
+                    vec_keys: Vec::new()
+                },
+                IntStringSet::default(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_parts_underscore_segment() {
+        let mut interner = Interner::with_capacity(100);
+        let mission_text = interner.intern("MissionText");
+        let name = interner.intern("name");
+        let en_us = interner.intern("en_US");
+        assert_eq!(
+            key_parts("MissionText_name_en_US", &interner),
+            Some(vec![
+                IntOrKey::Key(mission_text),
+                IntOrKey::Key(name),
+                IntOrKey::Key(en_us)
+            ])
+        );
+        assert_eq!(
+            key_parts("MissionText_42_name", &interner),
+            Some(vec![
+                IntOrKey::Key(mission_text),
+                IntOrKey::Int(42),
+                IntOrKey::Key(name)
+            ])
+        );
+        assert_eq!(key_parts("MissionText_en", &interner), None);
+    }
 }
@@ -1,52 +1,72 @@
 use std::{
-    borrow::Borrow,
+    borrow::{Borrow, Cow},
     fmt,
     future::{ready, Ready},
     io,
-    path::Path,
+    marker::PhantomData,
+    path::{Path, PathBuf},
     str::{FromStr, Split, Utf8Error},
     task::{self, Poll},
+    time::Duration,
 };
 
 use assembly_core::buffer::CastError;
 use assembly_fdb::mem::Database;
 use futures_util::{future::BoxFuture, Future, FutureExt};
 use http::{
-    header::{ACCEPT, ALLOW, CONTENT_LENGTH, CONTENT_TYPE, LOCATION},
-    HeaderValue, Method, Request, Response, StatusCode, Uri,
+    header::{
+        ACCEPT, ACCEPT_RANGES, ALLOW, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+        IF_MODIFIED_SINCE, LAST_MODIFIED, LINK, LOCATION, RANGE, RETRY_AFTER,
+    },
+    HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode, Uri,
 };
+use http_body::{LengthLimitError, Limited};
+use httpdate::HttpDate;
 use hyper::body::Bytes;
-use paradox_typed_db::TypedDatabase;
 use percent_encoding::percent_decode_str;
 use pin_project::pin_project;
-use serde::{Deserialize, Serialize};
+use rusqlite::{Connection, OpenFlags};
+use serde::{de::DeserializeSeed, Deserialize, Serialize};
+use tokio::{sync::oneshot, task::JoinError};
 use tower::Service;
 
 use crate::{
     auth::AuthKind,
-    config::DataOptions,
+    config::{DataOptions, DocsOptions, Maintenance, QueryOptions, TableFilter},
     data::{
-        fs::{spawn_handler, EventSender},
-        locale::LocaleRoot,
+        db::{DbSnapshot, SharedDb},
+        events::ReloadEvents,
+        fs::{spawn_handler, EventSender, LuRes},
+        locale::{LocaleRoot, Locales},
     },
-    services::router,
+    services::router::RouterPaths,
 };
 
 use self::{
     docs::OpenApiService,
     files::PackService,
-    rev::{RevService, ReverseLookup},
+    limit::{LimitedBuf, TooLarge},
+    rev::RevService,
 };
 
 pub mod adapter;
 pub mod docs;
+mod events;
 pub mod files;
+#[cfg(feature = "graphql")]
 pub mod graphql;
+mod limit;
 mod locale;
+mod pretty;
 mod query;
 pub mod rev;
 pub mod tables;
 
+/// A percent decoded path segment
+///
+/// This is strict path decoding, so `+` stays a plus sign (e.g. in
+/// `/query/SELECT%201+1`). Only in the query string, which is read with
+/// [`query_param`], does `+` stand for a space.
 #[derive(Clone, Debug)]
 pub struct PercentDecoded(pub String);
 
@@ -77,13 +97,19 @@ impl fmt::Display for PercentDecoded {
     }
 }
 
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
 /// This enum is for server side errors (i.e. `5XX`) only!
 pub enum ApiError {
     DB(CastError),
     Sqlite(rusqlite::Error),
+    #[cfg(feature = "graphql")]
     GraphQl(graphql::QueryError),
     Json(serde_json::Error),
     Yaml(serde_yaml::Error),
+    Task(JoinError),
+    Io(io::Error),
+    TooLarge(TooLarge),
 }
 
 pub type ApiResponse = Response<hyper::Body>;
@@ -101,6 +127,7 @@ impl From<rusqlite::Error> for ApiError {
     }
 }
 
+#[cfg(feature = "graphql")]
 impl From<graphql::QueryError> for ApiError {
     fn from(value: graphql::QueryError) -> Self {
         Self::GraphQl(value)
@@ -119,14 +146,36 @@ impl From<serde_yaml::Error> for ApiError {
     }
 }
 
+impl From<JoinError> for ApiError {
+    fn from(value: JoinError) -> Self {
+        Self::Task(value)
+    }
+}
+
+impl From<io::Error> for ApiError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<TooLarge> for ApiError {
+    fn from(value: TooLarge) -> Self {
+        Self::TooLarge(value)
+    }
+}
+
 impl From<ApiError> for io::Error {
     fn from(value: ApiError) -> Self {
         match value {
             ApiError::DB(e) => into_other_io_error(e),
             ApiError::Sqlite(e) => into_other_io_error(e),
+            #[cfg(feature = "graphql")]
             ApiError::GraphQl(e) => into_other_io_error(e),
             ApiError::Json(e) => into_other_io_error(e),
             ApiError::Yaml(e) => into_other_io_error(e),
+            ApiError::Task(e) => into_other_io_error(e),
+            ApiError::Io(e) => e,
+            ApiError::TooLarge(e) => into_other_io_error(e),
         }
     }
 }
@@ -156,21 +205,93 @@ impl<'r> fmt::Debug for RestPath<'r> {
 enum ApiRoute<'r> {
     Tables,
     TableByName(&'r str),
+    TableColumns(&'r str),
     AllTableRows(&'r str),
+    TableCount(&'r str),
+    TableStats(&'r str),
     TableRowsByPK(&'r str, &'r str),
+    TableExport(&'r str),
     Query(PercentDecoded),
+    QueryExplain(PercentDecoded),
+    #[cfg(feature = "graphql")]
     GraphQl(PercentDecoded),
+    #[cfg(feature = "graphql")]
+    GraphQlSchema,
+    #[cfg(feature = "graphql")]
+    GraphQlTables,
+    #[cfg(feature = "graphql")]
+    GraphQlBulk,
     Locale(RestPath<'r>),
+    LocaleByKey(&'r str),
+    LocaleLanguages,
     Crc(u32),
     Rev(rev::Route),
     OpenApiV0,
     SwaggerUI,
     SwaggerUIRedirect,
     Res(RestPath<'r>),
+    ResList,
+    FileRaw(u32),
+    Events,
+    Meta,
+}
+
+/// Why a path is not an [`ApiRoute`]
+#[derive(Debug)]
+enum RouteError {
+    /// There is no such route
+    NotFound,
+    /// The path is below `/v0/rev`, but not a valid reverse lookup route
+    Rev(rev::RouteError),
 }
 
 impl<'r> ApiRoute<'r> {
-    fn v0(mut parts: Split<'r, char>) -> Result<Self, ()> {
+    /// The value of the `Allow` header for this route
+    fn allow(&self) -> &'static HeaderValue {
+        match self {
+            Self::AllTableRows(_) | Self::Locale(_) => &ALLOW_GET_HEAD_QUERY,
+            #[cfg(feature = "graphql")]
+            Self::GraphQl(_) => &ALLOW_GET_HEAD_POST,
+            #[cfg(feature = "graphql")]
+            Self::GraphQlBulk => &ALLOW_POST,
+            Self::Rev(rev::Route::ObjectsSearchIndex) => &ALLOW_GET_HEAD_POST_QUERY,
+            _ => &ALLOW_GET_HEAD,
+        }
+    }
+
+    /// The FDB table this route is about, if any
+    fn table_name(&self) -> Option<&'r str> {
+        match self {
+            Self::TableByName(name)
+            | Self::TableColumns(name)
+            | Self::AllTableRows(name)
+            | Self::TableCount(name)
+            | Self::TableStats(name)
+            | Self::TableRowsByPK(name, _)
+            | Self::TableExport(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Whether the response is derived from the CDClient database only
+    fn is_data(&self) -> bool {
+        !matches!(
+            self,
+            Self::Locale(_)
+                | Self::LocaleByKey(_)
+                | Self::LocaleLanguages
+                | Self::Crc(_)
+                | Self::OpenApiV0
+                | Self::SwaggerUI
+                | Self::SwaggerUIRedirect
+                | Self::Res(_)
+                | Self::ResList
+                | Self::FileRaw(_)
+                | Self::Events
+        )
+    }
+
+    fn v0(mut parts: Split<'r, char>) -> Result<Self, RouteError> {
         match parts.next() {
             Some("tables") => match parts.next() {
                 None => Ok(Self::Tables),
@@ -178,78 +299,167 @@ impl<'r> ApiRoute<'r> {
                     None => Ok(Self::TableByName(name)),
                     Some("def") => match parts.next() {
                         None => Ok(Self::TableByName(name)),
-                        _ => Err(()),
+                        _ => Err(RouteError::NotFound),
+                    },
+                    Some("columns") => match parts.next() {
+                        None => Ok(Self::TableColumns(name)),
+                        _ => Err(RouteError::NotFound),
                     },
                     Some("all") => match parts.next() {
                         None => Ok(Self::AllTableRows(name)),
-                        _ => Err(()),
+                        _ => Err(RouteError::NotFound),
+                    },
+                    Some("count") => match parts.next() {
+                        None => Ok(Self::TableCount(name)),
+                        _ => Err(RouteError::NotFound),
+                    },
+                    Some("stats") => match parts.next() {
+                        None => Ok(Self::TableStats(name)),
+                        _ => Err(RouteError::NotFound),
+                    },
+                    Some("export.sqlite") => match parts.next() {
+                        None => Ok(Self::TableExport(name)),
+                        _ => Err(RouteError::NotFound),
                     },
                     Some(key) => match parts.next() {
                         None => Ok(Self::TableRowsByPK(name, key)),
-                        _ => Err(()),
+                        _ => Err(RouteError::NotFound),
                     },
                 },
             },
             Some("query") => match parts.next() {
+                Some("explain") => match (parts.next(), parts.next()) {
+                    (Some(query), None) => Ok(Self::QueryExplain(
+                        PercentDecoded::from_str(query).map_err(|_e| RouteError::NotFound)?,
+                    )),
+                    _ => Err(RouteError::NotFound),
+                },
                 Some(query) => Ok(Self::Query(
-                    PercentDecoded::from_str(query).map_err(|_e| ())?,
+                    PercentDecoded::from_str(query).map_err(|_e| RouteError::NotFound)?,
                 )),
-                None => Err(()),
+                None => Err(RouteError::NotFound),
             },
+            #[cfg(feature = "graphql")]
             Some("graphql") => match parts.next() {
+                Some("schema") => match parts.next() {
+                    None => Ok(Self::GraphQlSchema),
+                    _ => Err(RouteError::NotFound),
+                },
+                Some("tables") => match parts.next() {
+                    None => Ok(Self::GraphQlTables),
+                    _ => Err(RouteError::NotFound),
+                },
+                Some("bulk") => match parts.next() {
+                    None => Ok(Self::GraphQlBulk),
+                    _ => Err(RouteError::NotFound),
+                },
                 Some(query) => Ok(Self::GraphQl(
-                    PercentDecoded::from_str(query).map_err(|_e| ())?,
+                    PercentDecoded::from_str(query).map_err(|_e| RouteError::NotFound)?,
                 )),
-                None => Err(()),
+                // `POST /v0/graphql/` has the query in the body
+                None => Ok(Self::GraphQl(PercentDecoded(String::new()))),
             },
-            Some("locale") => Ok(Self::Locale(RestPath(parts))),
-            Some("rev") => rev::Route::from_parts(parts).map(ApiRoute::Rev),
-            Some("crc") => match parts.next() {
-                Some(crc) => match crc.parse() {
+            Some("locale") => {
+                let mut rest = parts.clone();
+                match (rest.next(), rest.next(), rest.next()) {
+                    (Some("by-key"), Some(key), None) if !key.is_empty() => {
+                        Ok(Self::LocaleByKey(key))
+                    }
+                    (Some("languages"), None, None) => Ok(Self::LocaleLanguages),
+                    _ => Ok(Self::Locale(RestPath(parts))),
+                }
+            }
+            // Shorter paths for some routes of `/v0/rev/objects`
+            Some("objects") => match (parts.next(), parts.next(), parts.next()) {
+                (Some(id), Some("components"), None) => match id.parse() {
+                    Ok(id) => Ok(Self::Rev(rev::Route::ObjectComponentsById(id))),
+                    Err(_) => Err(RouteError::NotFound),
+                },
+                (Some(id), Some("image"), None) => match id.parse() {
+                    Ok(id) => Ok(Self::Rev(rev::Route::ObjectImageById(id))),
+                    Err(_) => Err(RouteError::NotFound),
+                },
+                _ => Err(RouteError::NotFound),
+            },
+            Some("rev") => rev::Route::from_parts(parts)
+                .map(ApiRoute::Rev)
+                .map_err(RouteError::Rev),
+            Some("crc") => match (parts.next(), parts.next()) {
+                (Some(crc), None) => match crc.parse() {
                     Ok(crc) => Ok(Self::Crc(crc)),
-                    _ => Err(()),
+                    _ => Err(RouteError::NotFound),
                 },
-                _ => Err(()),
+                _ => Err(RouteError::NotFound),
             },
             Some("openapi.json") => match parts.next() {
                 None => Ok(Self::OpenApiV0),
-                _ => Err(()),
+                _ => Err(RouteError::NotFound),
+            },
+            Some("events") => match parts.next() {
+                None => Ok(Self::Events),
+                _ => Err(RouteError::NotFound),
             },
-            _ => Err(()),
+            Some("meta") => match parts.next() {
+                None => Ok(Self::Meta),
+                _ => Err(RouteError::NotFound),
+            },
+            _ => Err(RouteError::NotFound),
         }
     }
 
-    fn v1(mut parts: Split<'r, char>) -> Result<Self, ()> {
+    fn v1(mut parts: Split<'r, char>) -> Result<Self, RouteError> {
         match parts.next() {
             Some("tables") => match parts.next() {
                 None => Ok(Self::Tables),
-                _ => Err(()),
+                _ => Err(RouteError::NotFound),
+            },
+            Some("files") => match (parts.next(), parts.next(), parts.next()) {
+                (Some(crc), Some("raw"), None) => match crc.parse() {
+                    Ok(crc) => Ok(Self::FileRaw(crc)),
+                    Err(_) => Err(RouteError::NotFound),
+                },
+                _ => Err(RouteError::NotFound),
             },
-            Some("res") => Ok(Self::Res(RestPath(parts))),
-            _ => Err(()),
+            Some("res") => {
+                let mut rest = parts.clone();
+                match (rest.next(), rest.next()) {
+                    (Some("ls"), None) => Ok(Self::ResList),
+                    _ => Ok(Self::Res(RestPath(parts))),
+                }
+            }
+            _ => Err(RouteError::NotFound),
         }
     }
 
-    fn from_str(s: &'r str) -> Result<Self, ()> {
+    /// Parse the path below the API prefix
+    ///
+    /// This is the only place that handles trailing slashes: a single one is ignored
+    /// on every route, so e.g. `/v0/rev/factions/` is the same as `/v0/rev/factions`.
+    /// Only the root differs, `/` is the Swagger UI and the empty path redirects there.
+    fn from_str(s: &'r str) -> Result<Self, RouteError> {
         if s.is_empty() {
             return Ok(Self::SwaggerUIRedirect);
         }
-        let mut parts = s.trim_start_matches('/').split('/');
+        let s = s.trim_start_matches('/');
+        if s.is_empty() {
+            return Ok(Self::SwaggerUI);
+        }
+        let s = s.strip_suffix('/').unwrap_or(s);
+        let mut parts = s.split('/');
         match parts.next() {
             Some("v0") => Self::v0(parts),
             Some("v1") => Self::v1(parts),
-            Some("") => match parts.next() {
-                None => Ok(Self::SwaggerUI),
-                _ => Err(()),
-            },
-            _ => Err(()),
+            _ => Err(RouteError::NotFound),
         }
     }
 }
 
+#[derive(Clone, Copy)]
 enum Accept {
     Json,
     Yaml,
+    /// Only supported by the tabular routes, see [`reply_table`]
+    Csv,
 }
 
 impl Accept {
@@ -257,22 +467,172 @@ impl Accept {
         match self {
             Accept::Json => APPLICATION_JSON,
             Accept::Yaml => APPLICATION_YAML,
+            Accept::Csv => TEXT_CSV,
+        }
+    }
+}
+
+/// The format of a request body, selected by its `Content-Type`
+#[derive(Clone, Copy)]
+enum BodyFormat {
+    Json,
+    Yaml,
+}
+
+impl BodyFormat {
+    fn of(headers: &HeaderMap) -> Self {
+        let mime = headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next());
+        match mime.map(str::trim) {
+            Some("application/yaml") => Self::Yaml,
+            _ => Self::Json,
+        }
+    }
+
+    /// The `error` of the `400` reply when the body can't be parsed
+    fn parse_error(&self) -> &'static str {
+        match self {
+            Self::Json => "Failed to parse body as JSON",
+            Self::Yaml => "Failed to parse body as YAML",
+        }
+    }
+
+    fn deserialize_seed<'de, S: DeserializeSeed<'de>>(
+        &self,
+        seed: S,
+        bytes: &'de [u8],
+    ) -> Result<S::Value, BodyError> {
+        match self {
+            Self::Json => {
+                let mut de = serde_json::Deserializer::from_slice(bytes);
+                let value = seed.deserialize(&mut de)?;
+                de.end()?;
+                Ok(value)
+            }
+            Self::Yaml => Ok(seed.deserialize(serde_yaml::Deserializer::from_slice(bytes))?),
         }
     }
+
+    fn deserialize<'de, T: Deserialize<'de>>(&self, bytes: &'de [u8]) -> Result<T, BodyError> {
+        self.deserialize_seed(PhantomData, bytes)
+    }
+}
+
+/// Failed to deserialize a request body
+#[derive(Debug)]
+enum BodyError {
+    Json(serde_json::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl From<serde_json::Error> for BodyError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<serde_yaml::Error> for BodyError {
+    fn from(e: serde_yaml::Error) -> Self {
+        Self::Yaml(e)
+    }
+}
+
+impl fmt::Display for BodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(e) => e.fmt(f),
+            Self::Yaml(e) => e.fmt(f),
+        }
+    }
+}
+
+/// Get the (first) value of a query parameter
+fn query_param<'a>(uri: &'a Uri, key: &str) -> Option<Cow<'a, str>> {
+    let query = uri.query()?;
+    form_urlencoded::parse(query.as_bytes())
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v)
 }
 
 fn into_other_io_error<E: std::error::Error + Send + Sync + 'static>(error: E) -> io::Error {
     io::Error::new(io::ErrorKind::Other, error)
 }
 
-fn reply_static(body: &'static str) -> http::Response<hyper::Body> {
+/// Reply with a stream of Server-Sent Events
+fn reply_event_stream(body: hyper::Body) -> http::Response<hyper::Body> {
+    let mut r = Response::new(body);
+    r.headers_mut().append(CONTENT_TYPE, TEXT_EVENT_STREAM);
+    r.headers_mut()
+        .append(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    r
+}
+
+fn reply_html(body: Bytes) -> http::Response<hyper::Body> {
+    let len = body.len();
     let mut r = Response::new(hyper::Body::from(body));
     r.headers_mut()
-        .append(CONTENT_LENGTH, HeaderValue::from(body.len()));
+        .append(CONTENT_LENGTH, HeaderValue::from(len));
     r.headers_mut().append(CONTENT_TYPE, TEXT_HTML);
     r
 }
 
+fn reply_bytes(
+    body: Vec<u8>,
+    content_type: HeaderValue,
+    status: StatusCode,
+) -> http::Response<hyper::Body> {
+    let content_length = HeaderValue::from(body.len());
+    let mut r = Response::new(hyper::Body::from(body));
+
+    *r.status_mut() = status;
+    r.headers_mut().append(CONTENT_LENGTH, content_length);
+    r.headers_mut().append(CONTENT_TYPE, content_type);
+    r
+}
+
+/// Reply with a body of known length that is sent as it is produced
+fn reply_stream(
+    body: hyper::Body,
+    len: u64,
+    content_type: HeaderValue,
+) -> http::Response<hyper::Body> {
+    let mut r = Response::new(body);
+    r.headers_mut()
+        .append(CONTENT_LENGTH, HeaderValue::from(len));
+    r.headers_mut().append(CONTENT_TYPE, content_type);
+    r
+}
+
+/// Reply with the content of a file, see [`files::read_file`]
+fn reply_raw(file: files::RawFile, content_type: HeaderValue) -> http::Response<hyper::Body> {
+    let mut r = match file {
+        files::RawFile::Full(bytes) => reply_bytes(bytes, content_type, StatusCode::OK),
+        files::RawFile::Partial { range, len, bytes } => {
+            let mut r = reply_bytes(bytes, content_type, StatusCode::PARTIAL_CONTENT);
+            let content_range = format!("bytes {}-{}/{}", range.start, range.end, len);
+            r.headers_mut().insert(
+                CONTENT_RANGE,
+                HeaderValue::from_str(&content_range).unwrap(),
+            );
+            r
+        }
+        files::RawFile::Unsatisfiable { len } => {
+            let mut r = Response::new(hyper::Body::empty());
+            *r.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+            let content_range = format!("bytes */{}", len);
+            r.headers_mut().insert(
+                CONTENT_RANGE,
+                HeaderValue::from_str(&content_range).unwrap(),
+            );
+            r
+        }
+    };
+    r.headers_mut().insert(ACCEPT_RANGES, BYTES);
+    r
+}
+
 fn reply_string(
     body: String,
     content_type: HeaderValue,
@@ -303,87 +663,341 @@ fn reply<T: Serialize>(
     match accept {
         Accept::Json => reply_json(v, status),
         Accept::Yaml => reply_yaml(v, status),
+        Accept::Csv => reply_406(),
+    }
+}
+
+/// Like [`reply`], but also supports CSV
+fn reply_table<T: tables::Tabular>(accept: Accept, v: &T, status: StatusCode) -> ApiResult {
+    match accept {
+        Accept::Csv => {
+            let body = write_limited(|out| {
+                v.write_csv(out)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            })?;
+            Ok(reply_bytes(body, TEXT_CSV, status))
+        }
+        _ => reply(accept, v, status),
     }
 }
 
+fn reply_table_opt<T: tables::Tabular>(accept: Accept, v: Option<&T>) -> ApiResult {
+    v.map(|v| reply_table(accept, v, StatusCode::OK))
+        .unwrap_or_else(|| Ok(reply_404()))
+}
+
 fn reply_json<T: Serialize>(
     v: &T,
     status: StatusCode,
 ) -> Result<http::Response<hyper::Body>, ApiError> {
-    let body = serde_json::to_string(&v)?;
-    Ok(reply_string(body, APPLICATION_JSON, status))
+    let body = write_limited(|out| serde_json::to_writer(out, &v))?;
+    Ok(reply_bytes(body, APPLICATION_JSON, status))
 }
 
 fn reply_yaml<T: Serialize>(
     v: &T,
     status: StatusCode,
 ) -> Result<http::Response<hyper::Body>, ApiError> {
-    let body = serde_yaml::to_string(&v)?;
-    Ok(reply_string(body, APPLICATION_YAML, status))
+    let body = write_limited(|out| serde_yaml::to_writer(out, &v))?;
+    Ok(reply_bytes(body, APPLICATION_YAML, status))
 }
 
-/// Reply with a `200 OK` without any content
-fn reply_200(a: Accept) -> http::Response<hyper::Body> {
-    let mut r = Response::new(hyper::Body::empty());
-    *r.status_mut() = http::StatusCode::OK;
-    r.headers_mut().append(CONTENT_TYPE, a.content_type());
+/// Serialize a body with `f`, failing with [`ApiError::TooLarge`] once it exceeds the limit
+fn write_limited<E: Into<ApiError>>(
+    f: impl FnOnce(&mut LimitedBuf) -> Result<(), E>,
+) -> Result<Vec<u8>, ApiError> {
+    let mut out = LimitedBuf::new();
+    match f(&mut out) {
+        Ok(()) => Ok(out.into_inner()),
+        Err(e) => Err(out.too_large().map_or_else(|| e.into(), ApiError::TooLarge)),
+    }
+}
+
+/// Turn the response to a `GET` request into the response to a `HEAD` request
+///
+/// This keeps all headers, adding `Content-Length` if the size of the body is known.
+fn strip_body(mut r: ApiResponse) -> ApiResponse {
+    if !r.headers().contains_key(CONTENT_LENGTH) {
+        if let Some(len) = http_body::Body::size_hint(r.body()).exact() {
+            r.headers_mut()
+                .insert(CONTENT_LENGTH, HeaderValue::from(len));
+        }
+    }
+    *r.body_mut() = hyper::Body::empty();
     r
 }
 
+/// Reply with a `404 Not Found`
+///
+/// The body is filled in by [`ApiService::call`], see [`describe_error`].
 fn reply_404() -> http::Response<hyper::Body> {
-    let mut r = Response::new(hyper::Body::from("404"));
+    let mut r = Response::new(hyper::Body::empty());
     *r.status_mut() = http::StatusCode::NOT_FOUND;
-
-    let content_length = HeaderValue::from(3);
-    r.headers_mut().append(CONTENT_LENGTH, content_length);
-    r.headers_mut().append(CONTENT_TYPE, APPLICATION_JSON);
     r
 }
 
 #[derive(Serialize)]
 pub struct ErrorPayload {
     status: u16,
-    error: &'static str,
+    error: Cow<'static, str>,
     reason: String,
 }
 
 impl ErrorPayload {
-    pub fn new<R: fmt::Display>(status: StatusCode, error: &'static str, reason: R) -> Self {
+    pub fn new<E, R>(status: StatusCode, error: E, reason: R) -> Self
+    where
+        E: Into<Cow<'static, str>>,
+        R: fmt::Display,
+    {
         Self {
             status: status.as_u16(),
-            error,
+            error: error.into(),
             reason: reason.to_string(),
         }
     }
 }
 
+/// Reply with an [`ErrorPayload`], which is never CSV
+fn reply_error(accept: Accept, payload: &ErrorPayload, status: StatusCode) -> ApiResult {
+    match accept {
+        Accept::Yaml => reply_yaml(payload, status),
+        Accept::Json | Accept::Csv => reply_json(payload, status),
+    }
+}
+
 fn reply_400(accept: Accept, error: &'static str, reason: impl fmt::Display) -> ApiResult {
     let status = StatusCode::BAD_REQUEST;
-    reply(accept, &ErrorPayload::new(status, error, reason), status)
+    reply_error(accept, &ErrorPayload::new(status, error, reason), status)
+}
+
+/// Reply for a route that is not tabular, but CSV was requested
+fn reply_406() -> ApiResult {
+    let status = StatusCode::NOT_ACCEPTABLE;
+    let reason = "only the table rows routes support text/csv";
+    reply_json(&ErrorPayload::new(status, "not acceptable", reason), status)
+}
+
+/// Reply to a request for a data route while `general.maintenance` is set
+fn reply_503(accept: Accept) -> ApiResult {
+    let reason = "the data is being updated, please try again later";
+    reply_503_retry(accept, "maintenance", reason, MAINTENANCE_RETRY_AFTER)
+}
+
+fn reply_503_retry(
+    accept: Accept,
+    error: &'static str,
+    reason: &'static str,
+    retry_after: u64,
+) -> ApiResult {
+    let status = StatusCode::SERVICE_UNAVAILABLE;
+    let mut r = reply_error(accept, &ErrorPayload::new(status, error, reason), status)?;
+    r.headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+    Ok(r)
+}
+
+/// Reply to a reverse lookup route while the indices are still being built
+fn reply_503_loading(accept: Accept) -> ApiResult {
+    reply_503_retry(
+        accept,
+        "loading",
+        "the reverse lookup indices are still being built, please try again later",
+        LOADING_RETRY_AFTER,
+    )
+}
+
+fn reply_504(accept: Accept, error: &'static str, reason: impl fmt::Display) -> ApiResult {
+    let status = StatusCode::GATEWAY_TIMEOUT;
+    reply_error(accept, &ErrorPayload::new(status, error, reason), status)
+}
+
+/// Replace a response that is larger than `max_response_bytes`
+fn reply_413(accept: Accept, max: usize) -> ApiResult {
+    let status = StatusCode::PAYLOAD_TOO_LARGE;
+    let reason = format!("the response is larger than the limit of {} bytes", max);
+    reply_error(
+        accept,
+        &ErrorPayload::new(status, "response too large", reason),
+        status,
+    )
+}
+
+/// Reply to a request with a body that is larger than `max_request_bytes`
+fn reply_413_request(accept: Accept) -> ApiResult {
+    let status = StatusCode::PAYLOAD_TOO_LARGE;
+    let reason = "the request body is larger than the limit of the server";
+    reply_error(
+        accept,
+        &ErrorPayload::new(status, "request too large", reason),
+        status,
+    )
+}
+
+/// Read a request body to the end
+///
+/// The body is [`Limited`] by [`ApiService::route`], so a body that is too large
+/// is a `413`, other errors are a `400`. The `Err` is the reply in both cases.
+async fn read_body<B>(accept: Accept, body: B) -> Result<Bytes, ApiResult>
+where
+    B: http_body::Body<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    hyper::body::to_bytes(body).await.map_err(|e| {
+        let e: BoxError = e.into();
+        match e.is::<LengthLimitError>() {
+            true => reply_413_request(accept),
+            false => reply_400(accept, "Failed to read body", e),
+        }
+    })
+}
+
+/// Whether the request is for a route that is derived from the database
+fn is_data_route<B>(req: &Request<B>) -> bool {
+    ApiRoute::from_str(req.uri().path()).map_or(false, |route| route.is_data())
+}
+
+/// The `Content-Length` of a response, if it is set
+fn content_length(r: &ApiResponse) -> Option<usize> {
+    r.headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+/// Reply with the JSON result of a GraphQL query
+///
+/// Errors in the query itself are reported as `400 Bad Request`, everything else
+/// is a server error.
+#[cfg(feature = "graphql")]
+fn reply_graphql(accept: Accept, result: Result<String, graphql::QueryError>) -> ApiResult {
+    match result {
+        Ok(json) => Ok(reply_string(json, APPLICATION_JSON, StatusCode::OK)),
+        Err(e) if e.is_client_error() => {
+            let status = StatusCode::BAD_REQUEST;
+            let payload = ErrorPayload::new(status, e.error, e.message);
+            reply_error(accept, &payload, status)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Run `f` with a read-only connection to the SQLite database on the blocking thread pool
+///
+/// If this takes longer than `timeout`, the running statement is interrupted and
+/// the reply is a `504 Gateway Timeout`.
+async fn sqlite_job(
+    sqlite_path: &'static Path,
+    timeout: Duration,
+    accept: Accept,
+    f: impl FnOnce(&Connection) -> ApiResult + Send + 'static,
+) -> ApiResult {
+    let (interrupt_tx, mut interrupt_rx) = oneshot::channel();
+    let job = tokio::task::spawn_blocking(move || {
+        let conn = Connection::open_with_flags(sqlite_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let _ = interrupt_tx.send(conn.get_interrupt_handle());
+        f(&conn)
+    });
+    match tokio::time::timeout(timeout, job).await {
+        Ok(result) => result?,
+        Err(_) => {
+            if let Ok(interrupt) = interrupt_rx.try_recv() {
+                interrupt.interrupt();
+            }
+            let reason = format!("the query took longer than {}ms", timeout.as_millis());
+            reply_504(accept, "query timeout", reason)
+        }
+    }
+}
+
+/// Reply to a conditional request for data that didn't change
+fn reply_304(last_modified: HeaderValue) -> ApiResponse {
+    let mut r = Response::new(hyper::Body::empty());
+    *r.status_mut() = StatusCode::NOT_MODIFIED;
+    r.headers_mut().insert(LAST_MODIFIED, last_modified);
+    r
 }
 
+/// Reply to an `OPTIONS` request
+fn reply_204(allow: &HeaderValue) -> http::Response<hyper::Body> {
+    let mut r = Response::new(hyper::Body::empty());
+    *r.status_mut() = http::StatusCode::NO_CONTENT;
+    r.headers_mut().append(ALLOW, allow.clone());
+    r
+}
+
+/// Reply with a `405 Method Not Allowed`
+///
+/// The body is filled in by [`ApiService::call`], see [`describe_error`].
 fn reply_405(allow: &HeaderValue) -> http::Response<hyper::Body> {
-    let mut r = Response::new(hyper::Body::from("405"));
+    let mut r = Response::new(hyper::Body::empty());
     *r.status_mut() = http::StatusCode::METHOD_NOT_ALLOWED;
-
-    let content_length = HeaderValue::from(3);
     r.headers_mut().append(ALLOW, allow.clone());
-    r.headers_mut().append(CONTENT_LENGTH, content_length);
-    r.headers_mut().append(CONTENT_TYPE, APPLICATION_JSON);
     r
 }
 
+/// Re-indent a JSON response, see [`pretty::pretty_json`]
+async fn reply_pretty(r: ApiResponse) -> ApiResult {
+    let is_json = r
+        .headers()
+        .get(CONTENT_TYPE)
+        .map_or(false, |v| v.as_bytes().starts_with(b"application/json"));
+    if !is_json {
+        return Ok(r);
+    }
+    let (mut parts, body) = r.into_parts();
+    let bytes = hyper::body::to_bytes(body)
+        .await
+        .map_err(into_other_io_error)?;
+    let body = pretty::pretty_json(&bytes);
+    parts
+        .headers
+        .insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+    Ok(Response::from_parts(parts, hyper::Body::from(body)))
+}
+
+/// Give a `404` or `405` response an [`ErrorPayload`] that names the request
+///
+/// Other responses, and those that already have a body, are returned unchanged.
+fn describe_error(accept: Accept, r: ApiResponse, method: &Method, path: &str) -> ApiResult {
+    if r.headers().contains_key(CONTENT_TYPE) {
+        return Ok(r);
+    }
+    let status = r.status();
+    let (error, reason) = match status {
+        StatusCode::NOT_FOUND => ("not found", format!("nothing found at {}", path)),
+        StatusCode::METHOD_NOT_ALLOWED => (
+            "method not allowed",
+            format!("{} is not allowed for {}", method, path),
+        ),
+        _ => return Ok(r),
+    };
+    let mut described = reply_error(accept, &ErrorPayload::new(status, error, reason), status)?;
+    if let Some(allow) = r.headers().get(ALLOW) {
+        described.headers_mut().insert(ALLOW, allow.clone());
+    }
+    Ok(described)
+}
+
 #[derive(Clone)]
 pub struct ApiService {
-    pub db: Database<'static>,
+    data: SharedDb,
     pub locale_root: LocaleRoot,
+    locales: Locales,
     pub openapi: OpenApiService,
     pack: files::PackService,
     api_url: HeaderValue,
+    api_prefix: String,
     rev: rev::RevService,
     res: EventSender,
     sqlite_path: &'static Path,
-    db_table_rels: &'static graphql::TableRels,
+    query_timeout: Duration,
+    max_response_bytes: Option<usize>,
+    max_request_bytes: usize,
+    maintenance: Maintenance,
+    tables: TableFilter,
+    events: ReloadEvents,
+    #[cfg(feature = "graphql")]
+    db_table_rels: graphql::SharedTableRels,
 }
 
 #[allow(clippy::declare_interior_mutable_const)] // c.f. https://github.com/rust-lang/rust-clippy/issues/5812
@@ -394,74 +1008,199 @@ const APPLICATION_YAML: HeaderValue = HeaderValue::from_static("application/yaml
 const TEXT_HTML: HeaderValue = HeaderValue::from_static("text/html; charset=utf-8");
 #[allow(clippy::declare_interior_mutable_const)]
 const TEXT_CSV: HeaderValue = HeaderValue::from_static("text/csv; charset=utf-8");
+#[allow(clippy::declare_interior_mutable_const)]
+const TEXT_EVENT_STREAM: HeaderValue = HeaderValue::from_static("text/event-stream");
+#[allow(clippy::declare_interior_mutable_const)]
+const APPLICATION_SQLITE: HeaderValue = HeaderValue::from_static("application/vnd.sqlite3");
+
+#[allow(clippy::declare_interior_mutable_const)]
+const BYTES: HeaderValue = HeaderValue::from_static("bytes");
 
 impl ApiService {
     #[allow(clippy::too_many_arguments)] // FIXME
     pub(crate) fn new(
-        db: Database<'static>,
-        locale_root: LocaleRoot,
+        locales: Locales,
         pack: PackService,
         openapi: OpenApiService,
         api_uri: Uri,
-        tydb: &'static TypedDatabase,
-        rev: &'static ReverseLookup,
-        db_table_rels: &'static graphql::TableRels,
+        api_prefix: String,
+        data: SharedDb,
+        #[cfg(feature = "graphql")] db_table_rels: graphql::SharedTableRels,
         res_path: &Path,
         sqlite_path: &'static Path,
+        query_timeout: Duration,
+        max_response_bytes: Option<usize>,
+        max_request_bytes: usize,
+        maintenance: Maintenance,
+        tables: TableFilter,
+        events: ReloadEvents,
+        lu_res: LuRes,
     ) -> Self {
         let api_url = HeaderValue::from_str(&api_uri.to_string()).unwrap();
+        let locale_root = locales.default_locale().clone();
+        limit::set_max_response_bytes(max_response_bytes);
         Self {
             pack,
-            db,
+            data: data.clone(),
             locale_root: locale_root.clone(),
+            locales,
             openapi,
             api_url,
+            api_prefix,
             res: spawn_handler(res_path),
-            rev: RevService::new(tydb, locale_root, rev),
+            rev: RevService::new(data, locale_root, lu_res),
             sqlite_path,
+            query_timeout,
+            max_response_bytes,
+            max_request_bytes,
+            maintenance,
+            tables,
+            events,
+            #[cfg(feature = "graphql")]
             db_table_rels,
         }
     }
 
-    fn db_api<T: Serialize>(
-        &self,
+    /// The `Last-Modified` date for a `GET` request to a data route
+    fn last_modified<B>(&self, req: &Request<B>) -> Option<HttpDate> {
+        match req.method() == Method::GET && is_data_route(req) {
+            true => self.data.get().modified.map(HttpDate::from),
+            false => None,
+        }
+    }
+
+    fn db_api<'d, T: Serialize>(
+        data: &'d DbSnapshot,
         accept: Accept,
-        f: impl FnOnce(Database<'static>) -> Result<T, CastError>,
+        f: impl FnOnce(Database<'d>) -> Result<T, CastError>,
     ) -> Result<Response<hyper::Body>, ApiError> {
-        reply(accept, &f(self.db)?, StatusCode::OK)
+        reply(accept, &f(data.db())?, StatusCode::OK)
     }
 
-    fn db_api_opt<T: Serialize>(
-        &self,
+    fn db_api_opt<'d, T: Serialize>(
+        data: &'d DbSnapshot,
+        accept: Accept,
+        f: impl FnOnce(Database<'d>) -> Result<Option<T>, CastError>,
+    ) -> Result<Response<hyper::Body>, ApiError> {
+        reply_opt(accept, f(data.db())?.as_ref())
+    }
+
+    fn db_api_table_opt<'d, T: tables::Tabular>(
+        data: &'d DbSnapshot,
         accept: Accept,
-        f: impl FnOnce(Database<'static>) -> Result<Option<T>, CastError>,
+        f: impl FnOnce(Database<'d>) -> Result<Option<T>, CastError>,
     ) -> Result<Response<hyper::Body>, ApiError> {
-        reply_opt(accept, f(self.db)?.as_ref())
+        reply_table_opt(accept, f(data.db())?.as_ref())
     }
 
     fn query_api(
         &self,
-        f: impl FnOnce(&Path) -> Result<String, rusqlite::Error>,
-    ) -> Result<Response<hyper::Body>, ApiError> {
-        Ok(reply_string(f(self.sqlite_path)?, TEXT_CSV, StatusCode::OK))
+        accept: Accept,
+        f: impl FnOnce(&Connection) -> Result<String, rusqlite::Error> + Send + 'static,
+    ) -> ApiFuture {
+        ApiFuture::boxed(sqlite_job(
+            self.sqlite_path,
+            self.query_timeout,
+            accept,
+            move |conn| match f(conn) {
+                Ok(csv) => Ok(reply_string(csv, TEXT_CSV, StatusCode::OK)),
+                Err(e) => match query::missing_table(&e) {
+                    Some(table) => {
+                        let status = StatusCode::NOT_FOUND;
+                        let reason = format!("the table `{}` does not exist", table);
+                        reply_error(
+                            accept,
+                            &ErrorPayload::new(status, "no such table", reason),
+                            status,
+                        )
+                    }
+                    None if matches!(e, rusqlite::Error::InvalidQuery) => reply_400(
+                        accept,
+                        "invalid query",
+                        "only read-only statements are allowed",
+                    ),
+                    None => Err(e.into()),
+                },
+            },
+        ))
     }
 
+    #[cfg(feature = "graphql")]
     fn graphql_api(
         &self,
-        f: impl FnOnce(&Path, &graphql::TableRels) -> Result<String, graphql::QueryError>,
-    ) -> Result<Response<hyper::Body>, ApiError> {
-        Ok(reply_string(
-            f(self.sqlite_path, self.db_table_rels)?,
-            APPLICATION_JSON,
-            StatusCode::OK,
+        accept: Accept,
+        f: impl FnOnce(
+                &Connection,
+                &graphql::TableRels,
+                &graphql::QueryCache,
+            ) -> Result<String, graphql::QueryError>
+            + Send
+            + 'static,
+    ) -> ApiFuture {
+        let table_rels = self.db_table_rels.get();
+        let cache = self.db_table_rels.cache();
+        ApiFuture::boxed(sqlite_job(
+            self.sqlite_path,
+            self.query_timeout,
+            accept,
+            move |conn| reply_graphql(accept, f(conn, &table_rels, &cache)),
         ))
     }
 
+    /// Run `run` on the GraphQL query in the body of a `POST` request
+    #[cfg(feature = "graphql")]
+    fn graphql_post<B>(
+        &self,
+        accept: Accept,
+        format: BodyFormat,
+        body: B,
+        run: fn(
+            &Connection,
+            &graphql::TableRels,
+            &graphql::QueryCache,
+            &str,
+            &graphql::Variables,
+        ) -> Result<String, graphql::QueryError>,
+    ) -> ApiFuture
+    where
+        B: http_body::Body<Data = Bytes> + Send + 'static,
+        B::Error: Into<BoxError>,
+    {
+        let sqlite_path = self.sqlite_path;
+        let query_timeout = self.query_timeout;
+        let db_table_rels = self.db_table_rels.get();
+        let cache = self.db_table_rels.cache();
+        ApiFuture::boxed(async move {
+            let bytes = match read_body(accept, body).await {
+                Ok(x) => x,
+                Err(r) => return r,
+            };
+            let query: GraphQlRequest = match format.deserialize(&bytes) {
+                Ok(x) => x,
+                Err(e) => return reply_400(accept, "malformed POST body", e),
+            };
+            sqlite_job(sqlite_path, query_timeout, accept, move |conn| {
+                let variables = query.variables.unwrap_or_default();
+                reply_graphql(
+                    accept,
+                    run(conn, &db_table_rels, &cache, &query.query, &variables),
+                )
+            })
+            .await
+        })
+    }
+
     /// Get data from `locale.xml`
-    fn locale(&self, accept: Accept, rest: RestPath) -> Result<Response<hyper::Body>, ApiError> {
+    ///
+    /// `$all` expands at most `depth` levels of children, if that is set.
+    fn locale(&self, accept: Accept, rest: RestPath, depth: Option<&str>) -> ApiResult {
         match locale::select_node(self.locale_root.root.node(), rest) {
             Some((node, locale::Mode::All)) => {
-                reply(accept, &locale::All::new(node), StatusCode::OK)
+                let depth = match depth.map(str::parse).transpose() {
+                    Ok(depth) => depth,
+                    Err(e) => return reply_400(accept, "invalid depth", e),
+                };
+                reply(accept, &locale::All::new(node, depth), StatusCode::OK)
             }
             Some((node, locale::Mode::Pod)) => {
                 reply(accept, &locale::Pod::new(node), StatusCode::OK)
@@ -470,6 +1209,29 @@ impl ApiService {
         }
     }
 
+    fn locale_by_key(&self, accept: Accept, key: &str) -> ApiResult {
+        let node = locale::select_by_key(self.locale_root.root.node(), key);
+        match node.as_ref().and_then(|node| node.value()) {
+            Some(value) => reply(accept, &value, StatusCode::OK),
+            None => Ok(reply_404()),
+        }
+    }
+
+    /// Serve a loose file of the res tree, honoring a `Range` header
+    fn file_raw(&self, crc: u32, range: Option<String>) -> ApiFuture {
+        let Some((path, kind)) = self.pack.file_path(crc) else {
+            return ApiFuture::ready(Ok(reply_404()));
+        };
+        let path = path.to_owned();
+        let content_type = HeaderValue::from_static(kind.content_type());
+        ApiFuture::boxed(async move {
+            let file =
+                tokio::task::spawn_blocking(move || files::read_file(&path, range.as_deref()))
+                    .await??;
+            Ok(reply_raw(file, content_type))
+        })
+    }
+
     fn swagger_ui_redirect(&self) -> Result<http::Response<hyper::Body>, ApiError> {
         let mut r = http::Response::new(hyper::Body::empty());
         *r.status_mut() = StatusCode::PERMANENT_REDIRECT;
@@ -477,6 +1239,23 @@ impl ApiService {
         Ok(r)
     }
 
+    /// Mark a response to a `/rev` alias as deprecated, with a `Link` to the canonical `path`
+    fn deprecated_alias(&self, mut r: ApiResponse, path: &str, uri: &Uri) -> ApiResponse {
+        let api_url = self.api_url.to_str().unwrap();
+        let link = match uri.query() {
+            Some(query) => format!("<{}{}?{}>; rel=\"successor-version\"", api_url, path, query),
+            None => format!("<{}{}>; rel=\"successor-version\"", api_url, path),
+        };
+        let headers = r.headers_mut();
+        if let Ok(deprecation) = HeaderValue::from_str(&rev::alias_deprecation()) {
+            headers.insert(HeaderName::from_static(DEPRECATION), deprecation);
+        }
+        if let Ok(link) = HeaderValue::from_str(&link) {
+            headers.append(LINK, link);
+        }
+        r
+    }
+
     fn res_request(&self, accept: Accept, rest: RestPath) -> ApiFuture {
         ApiFuture::boxed({
             let sender = self.res.clone();
@@ -495,8 +1274,6 @@ impl ApiService {
     }
 }
 
-const SWAGGER_UI_HTML: &str = include_str!("../../res/api.html");
-
 #[pin_project(project = ApiFutureProj)]
 pub enum ApiFuture {
     Ready(#[pin] Ready<ApiResult>),
@@ -526,132 +1303,398 @@ impl std::future::Future for ApiFuture {
 
 static ALLOW_GET_HEAD: HeaderValue = HeaderValue::from_static("GET,HEAD");
 static ALLOW_GET_HEAD_QUERY: HeaderValue = HeaderValue::from_static("GET,HEAD,QUERY");
+#[cfg(feature = "graphql")]
+static ALLOW_POST: HeaderValue = HeaderValue::from_static("POST");
+#[cfg(feature = "graphql")]
+static ALLOW_GET_HEAD_POST: HeaderValue = HeaderValue::from_static("GET,HEAD,POST");
+static ALLOW_GET_HEAD_POST_QUERY: HeaderValue = HeaderValue::from_static("GET,HEAD,POST,QUERY");
 
+/// The `Deprecation` header (RFC 9745), which is not in [`http::header`]
+const DEPRECATION: &str = "deprecation";
+
+/// The `Retry-After` (in seconds) for data routes in maintenance mode
+const MAINTENANCE_RETRY_AFTER: u64 = 60;
+/// The `Retry-After` (in seconds) for `/rev` routes while the indices are built
+const LOADING_RETRY_AFTER: u64 = 5;
+
+#[cfg(feature = "graphql")]
 #[derive(Deserialize)]
 struct GraphQlRequest {
     query: String,
+    /// Clients commonly send `"variables": null` when there are none
+    #[serde(default)]
+    variables: Option<graphql::Variables>,
 }
 
-impl<ReqBody> Service<Request<ReqBody>> for ApiService
-where
-    ReqBody: http_body::Body<Data = Bytes> + Send + Unpin + 'static,
-    ReqBody::Error: fmt::Display,
-{
-    type Error = ApiError;
-    type Response = ApiResponse;
-    type Future = ApiFuture;
+impl Accept {
+    /// The response format for a request
+    ///
+    /// A `?format=` query parameter takes precedence over the `Accept` header, so
+    /// that all formats can be requested from a browser address bar.
+    fn of(uri: &Uri, headers: &HeaderMap) -> Self {
+        if let Some(accept) = query_param(uri, "format").and_then(|f| Self::from_param(&f)) {
+            return accept;
+        }
+        headers
+            .get(ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .map_or(Accept::Json, Self::negotiate)
+    }
 
-    fn poll_ready(&mut self, _cx: &mut task::Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
+    /// The format named by a `?format=` query parameter
+    fn from_param(format: &str) -> Option<Self> {
+        match format {
+            _ if format.eq_ignore_ascii_case("json") => Some(Accept::Json),
+            _ if format.eq_ignore_ascii_case("yaml") => Some(Accept::Yaml),
+            _ if format.eq_ignore_ascii_case("csv") => Some(Accept::Csv),
+            _ => None,
+        }
     }
 
-    /// This is the main entry point to the API service.
+    /// Pick the supported type with the highest `q` value from an `Accept` header
     ///
+    /// On a tie, the type listed first wins. Wildcards select JSON, and so does a
+    /// header without any supported type.
+    fn negotiate(header: &str) -> Self {
+        let mut best: Option<(f32, Self)> = None;
+        for item in header.split(',') {
+            let mut params = item.split(';');
+            let mime = params.next().unwrap_or_default().trim();
+            let accept = match mime {
+                _ if mime.eq_ignore_ascii_case("application/json") => Accept::Json,
+                _ if mime.eq_ignore_ascii_case("application/yaml") => Accept::Yaml,
+                _ if mime.eq_ignore_ascii_case("text/csv") => Accept::Csv,
+                "*/*" | "application/*" => Accept::Json,
+                _ => continue,
+            };
+            let q = params
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if q > 0.0 && best.map_or(true, |(best_q, _)| q > best_q) {
+                best = Some((q, accept));
+            }
+        }
+        best.map_or(Accept::Json, |(_, accept)| accept)
+    }
+}
+
+impl ApiService {
     /// Here, we turn [ApiRoute]s into [http::Response]s
-    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+    fn route<ReqBody>(&mut self, req: Request<ReqBody>) -> ApiFuture
+    where
+        ReqBody: http_body::Body<Data = Bytes> + Send + Unpin + 'static,
+        ReqBody::Error: Into<BoxError>,
+    {
         let (parts, body) = req.into_parts();
-        let accept = match parts.headers.get(ACCEPT) {
-            Some(s) if s == "application/yaml" => Accept::Yaml,
-            _ => Accept::Json,
-        };
+        let accept = Accept::of(&parts.uri, &parts.headers);
+        let request_len = parts
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok());
+        if matches!(request_len, Some(len) if len > self.max_request_bytes) {
+            return ApiFuture::ready(reply_413_request(accept));
+        }
+        // Bodies without a `Content-Length` are checked while they are read
+        let body = Limited::new(body, self.max_request_bytes);
+        let format = BodyFormat::of(&parts.headers);
         let route = match ApiRoute::from_str(parts.uri.path()) {
             Ok(route) => {
                 tracing::info!("API Route: {:?}", route);
                 route
             }
-            Err(()) => return ApiFuture::ready(Ok(reply_404())),
+            Err(RouteError::NotFound) => return ApiFuture::ready(Ok(reply_404())),
+            Err(RouteError::Rev(e)) => {
+                let status = StatusCode::NOT_FOUND;
+                let payload = ErrorPayload::new(status, "not found", e);
+                return ApiFuture::ready(reply_error(accept, &payload, status));
+            }
         };
+        if let Some(name) = route.table_name() {
+            if !self.tables.is_exposed(name) {
+                return ApiFuture::ready(Ok(reply_404()));
+            }
+        }
         let method = parts.method;
+        let data = self.data.get();
         let response = match (method, route) {
-            (Method::GET, ApiRoute::Tables) => self.db_api(accept, tables::tables_json),
+            (Method::OPTIONS, route) => Ok(reply_204(route.allow())),
+            (Method::GET, ApiRoute::Tables) => Self::db_api(&data, accept, |db| {
+                let mut names = tables::tables_json(db)?;
+                names.retain(|name| self.tables.is_exposed(name));
+                Ok(names)
+            }),
             (Method::GET, ApiRoute::TableByName(name)) => {
-                self.db_api(accept, |db| tables::table_def_json(db, name))
+                Self::db_api(&data, accept, |db| tables::table_def_json(db, name))
+            }
+            (Method::GET, ApiRoute::TableColumns(name)) => {
+                Self::db_api_opt(&data, accept, |db| tables::table_columns_json(db, name))
             }
             (method, ApiRoute::AllTableRows(name)) => match method.as_str() {
-                "GET" => self.db_api_opt(accept, |db| tables::table_all_get(db, name)),
+                "GET" => {
+                    Self::db_api_table_opt(&data, accept, |db| tables::table_all_get(db, name))
+                }
                 "QUERY" => {
                     let name = name.to_owned();
-                    let db = self.db;
                     return ApiFuture::boxed(async move {
-                        tables::table_all_query(db, accept, &name, body).await
+                        tables::table_all_query(data.db(), accept, format, &name, body).await
                     });
                 }
                 _ => Ok(reply_405(&ALLOW_GET_HEAD_QUERY)),
             },
+            (Method::GET, ApiRoute::TableCount(name)) => {
+                let keys = query_param(&parts.uri, "keys");
+                tables::table_count(data.db(), accept, name, keys.as_deref())
+            }
+            (Method::GET, ApiRoute::TableStats(name)) => {
+                Self::db_api_opt(&data, accept, |db| tables::table_stats_json(db, name))
+            }
             (Method::GET, ApiRoute::TableRowsByPK(name, key)) => {
-                self.db_api_opt(accept, |db| tables::table_key_json(db, name, key))
+                Self::db_api_table_opt(&data, accept, |db| tables::table_key_json(db, name, key))
+            }
+            (Method::GET, ApiRoute::TableExport(name)) => {
+                let name = name.to_owned();
+                let sqlite_path = self.sqlite_path;
+                return ApiFuture::boxed(sqlite_job(
+                    sqlite_path,
+                    self.query_timeout,
+                    accept,
+                    move |conn| match query::export_table(conn, sqlite_path, &name)? {
+                        Some((body, len)) => Ok(reply_stream(body, len, APPLICATION_SQLITE)),
+                        None => Ok(reply_404()),
+                    },
+                ));
             }
             (Method::GET, ApiRoute::Query(query)) => {
-                self.query_api(|sqlite_path| query::query(sqlite_path, query))
+                if let Err(reason) = query::check_select(query.borrow()) {
+                    return ApiFuture::ready(reply_400(accept, "invalid query", reason));
+                }
+                return self.query_api(accept, move |conn| query::query(conn, query.borrow()));
             }
-            (Method::GET, ApiRoute::GraphQl(query)) => {
-                self.graphql_api(|sqlite_path, table_rels| {
-                    graphql::graphql(sqlite_path, table_rels, query.borrow())
-                })
+            (Method::GET, ApiRoute::QueryExplain(query)) => {
+                return ApiFuture::boxed(sqlite_job(
+                    self.sqlite_path,
+                    self.query_timeout,
+                    accept,
+                    move |conn| match query::explain(conn, query.borrow()) {
+                        Ok(plan) => reply(accept, &plan, StatusCode::OK),
+                        Err(e) => reply_400(accept, "invalid query", e),
+                    },
+                ));
             }
-            (Method::POST, ApiRoute::GraphQl(_)) => {
-                let sqlite_path = self.sqlite_path;
-                let db_table_rels = self.db_table_rels;
-                return ApiFuture::boxed(async move {
-                    let bytes = match hyper::body::to_bytes(body).await {
-                        Ok(x) => x,
-                        Err(_) => {
-                            return reply_400(accept, "malformed POST body", "could not read body");
-                        }
-                    };
-                    let query = match std::str::from_utf8(&bytes) {
-                        Ok(x) => x,
-                        Err(_) => {
-                            return reply_400(
-                                accept,
-                                "malformed POST body",
-                                "could not parse body as utf8 string",
-                            );
+            #[cfg(feature = "graphql")]
+            (Method::GET, ApiRoute::GraphQl(query)) => {
+                let variables = match query_param(&parts.uri, "variables") {
+                    None => graphql::Variables::new(),
+                    Some(json) => match serde_json::from_str(&json) {
+                        Ok(variables) => variables,
+                        Err(e) => {
+                            return ApiFuture::ready(reply_400(accept, "invalid variables", e))
                         }
-                    };
-                    let query: GraphQlRequest = serde_json::from_str(query)?;
-                    Ok(reply_string(
-                        graphql::graphql(sqlite_path, db_table_rels, &query.query)?,
-                        APPLICATION_JSON,
-                        StatusCode::OK,
-                    ))
+                    },
+                };
+                return self.graphql_api(accept, move |conn, table_rels, cache| {
+                    graphql::graphql(conn, table_rels, cache, query.borrow(), &variables)
                 });
             }
+            #[cfg(feature = "graphql")]
+            (Method::GET, ApiRoute::GraphQlSchema) => {
+                reply(accept, &*self.db_table_rels.get(), StatusCode::OK)
+            }
+            #[cfg(feature = "graphql")]
+            (Method::GET, ApiRoute::GraphQlTables) => {
+                let table_rels = self.db_table_rels.get();
+                return ApiFuture::boxed(sqlite_job(
+                    self.sqlite_path,
+                    self.query_timeout,
+                    accept,
+                    move |conn| {
+                        let tables = graphql::read_out_tables(conn, &table_rels)?;
+                        reply(accept, &tables, StatusCode::OK)
+                    },
+                ));
+            }
+            #[cfg(feature = "graphql")]
+            (Method::POST, ApiRoute::GraphQl(_)) => {
+                return self.graphql_post(accept, format, body, graphql::graphql)
+            }
+            #[cfg(feature = "graphql")]
+            (Method::POST, ApiRoute::GraphQlBulk) => {
+                return self.graphql_post(accept, format, body, graphql::graphql_bulk)
+            }
+            (Method::GET, ApiRoute::LocaleByKey(key)) => self.locale_by_key(accept, key),
+            (Method::GET, ApiRoute::Events) => Ok(reply_event_stream(events::event_stream(
+                self.events.subscribe(),
+            ))),
+            (Method::GET, ApiRoute::Meta) => reply(accept, &data.meta, StatusCode::OK),
+            (Method::GET, ApiRoute::LocaleLanguages) => {
+                let languages: Vec<&str> = self.locales.languages().collect();
+                reply(accept, &languages, StatusCode::OK)
+            }
             (method, ApiRoute::Locale(rest)) => match method {
-                Method::GET => self.locale(accept, rest),
+                Method::GET => {
+                    let depth = query_param(&parts.uri, "depth");
+                    self.locale(accept, rest, depth.as_deref())
+                }
                 m if m.as_str() == "QUERY" => {
-                    return locale::locale_query(&self.locale_root, accept, rest, body)
+                    let prune = matches!(
+                        query_param(&parts.uri, "prune").as_deref(),
+                        Some("1" | "true")
+                    );
+                    return locale::locale_query(
+                        &self.locale_root,
+                        accept,
+                        format,
+                        rest,
+                        prune,
+                        body,
+                    );
                 }
                 _ => Ok(reply_405(&ALLOW_GET_HEAD_QUERY)),
             },
             (Method::GET, ApiRoute::OpenApiV0) => reply_json(self.openapi.as_ref(), StatusCode::OK),
-            (Method::GET, ApiRoute::SwaggerUI) => Ok(reply_static(SWAGGER_UI_HTML)),
+            (Method::GET, ApiRoute::SwaggerUI) => Ok(reply_html(self.openapi.html())),
             (Method::GET, ApiRoute::SwaggerUIRedirect) => self.swagger_ui_redirect(),
             (Method::GET, ApiRoute::Crc(crc)) => {
                 reply(accept, &self.pack.lookup(crc), StatusCode::OK)
             }
+            (method, ApiRoute::Rev(rev::Route::ObjectsSearchIndex))
+                if method == Method::POST || method.as_str() == "QUERY" =>
+            {
+                return self.rev.search_index_query(accept, format, body);
+            }
             (method, ApiRoute::Rev(route)) => {
-                return ApiFuture::Ready(self.rev.call((accept, method, route)))
+                let response = self
+                    .rev
+                    .call((accept, method, route, parts.uri.clone()))
+                    .into_inner();
+                match rev::canonical_path(parts.uri.path()) {
+                    Some(path) => response.map(|r| self.deprecated_alias(r, &path, &parts.uri)),
+                    None => response,
+                }
             }
             (Method::GET, ApiRoute::Res(rest)) => return self.res_request(accept, rest),
-            (_, _) => Ok(reply_405(&ALLOW_GET_HEAD)),
+            (Method::GET, ApiRoute::FileRaw(crc)) => {
+                let range = parts.headers.get(RANGE).and_then(|v| v.to_str().ok());
+                return self.file_raw(crc, range.map(str::to_owned));
+            }
+            (Method::GET, ApiRoute::ResList) => {
+                let dir = query_param(&parts.uri, "dir");
+                match self.pack.list(dir.as_deref().unwrap_or_default()) {
+                    Ok(listing) => reply_opt(accept, listing.as_ref()),
+                    Err(()) => reply_400(
+                        accept,
+                        "invalid directory",
+                        "`dir` must be a relative path within client\\res",
+                    ),
+                }
+            }
+            (_, route) => Ok(reply_405(route.allow())),
         };
         ApiFuture::ready(response)
     }
 }
 
+impl<ReqBody> Service<Request<ReqBody>> for ApiService
+where
+    ReqBody: http_body::Body<Data = Bytes> + Send + Unpin + 'static,
+    ReqBody::Error: Into<BoxError>,
+{
+    type Error = ApiError;
+    type Response = ApiResponse;
+    type Future = ApiFuture;
+
+    fn poll_ready(&mut self, _cx: &mut task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// This is the main entry point to the API service.
+    ///
+    /// `HEAD` requests are answered like `GET` without the body, and responses
+    /// that are larger than `max_response_bytes` are replaced with a `413`. Most
+    /// bodies stop being serialized at the limit already, see [`limit`].
+    ///
+    /// Data responses carry the modification time of the database file as
+    /// `Last-Modified`, and `If-Modified-Since` is answered with a `304`.
+    ///
+    /// `404` and `405` responses get an error body naming the method and path.
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let method = req.method().clone();
+        let path = format!("{}{}", self.api_prefix, req.uri().path());
+        let head = method == Method::HEAD;
+        if head {
+            *req.method_mut() = Method::GET;
+        }
+        let accept = Accept::of(req.uri(), req.headers());
+        let pretty = query_param(req.uri(), "pretty")
+            .as_deref()
+            .or_else(|| req.headers().get("x-pretty")?.to_str().ok())
+            .map_or(false, pretty::is_enabled);
+        if self.maintenance.is_enabled() && is_data_route(&req) {
+            let response = reply_503(accept);
+            return ApiFuture::ready(if head {
+                response.map(strip_body)
+            } else {
+                response
+            });
+        }
+        let last_modified = self.last_modified(&req).map(|date| {
+            let value = HeaderValue::from_str(&date.to_string()).unwrap();
+            (date, value)
+        });
+        if let Some((date, value)) = &last_modified {
+            let since = req
+                .headers()
+                .get(IF_MODIFIED_SINCE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| HttpDate::from_str(v).ok());
+            if matches!(since, Some(since) if *date <= since) {
+                return ApiFuture::ready(Ok(reply_304(value.clone())));
+            }
+        }
+        let max_response_bytes = self.max_response_bytes;
+        let response = self.route(req);
+        ApiFuture::boxed(async move {
+            let r = match response.await {
+                Err(ApiError::TooLarge(e)) => reply_413(accept, e.max)?,
+                r => r?,
+            };
+            let mut r = describe_error(accept, r, &method, &path)?;
+            if pretty {
+                r = reply_pretty(r).await?;
+            }
+            if let (Some(max), Some(len)) = (max_response_bytes, content_length(&r)) {
+                if len > max {
+                    r = reply_413(accept, max)?;
+                }
+            }
+            if let Some((_, value)) = last_modified {
+                if r.status().is_success() {
+                    r.headers_mut().insert(LAST_MODIFIED, value);
+                }
+            }
+            Ok(if head { strip_body(r) } else { r })
+        })
+    }
+}
+
 /// Make the API
 #[allow(clippy::too_many_arguments)]
 pub fn service(
     cfg: &DataOptions,
-    locale_root: LocaleRoot,
+    locales: Locales,
     auth_kind: AuthKind,
-    base_url: String,
-    db: Database<'static>,
-    tydb: &'static TypedDatabase<'static>,
-    rev: &'static ReverseLookup,
-    db_table_rels: &'static graphql::TableRels,
+    base_urls: &[String],
+    paths: &RouterPaths,
+    docs: &DocsOptions,
+    data: SharedDb,
     sqlite_path: &'static Path,
+    query_cfg: &QueryOptions,
+    max_response_bytes: Option<usize>,
+    max_request_bytes: usize,
+    maintenance: Maintenance,
+    events: ReloadEvents,
 ) -> Result<ApiService, color_eyre::Report> {
     // The pack service
     let res_path = cfg
@@ -659,22 +1702,234 @@ pub fn service(
         .as_deref()
         .unwrap_or_else(|| Path::new("client/res"));
     let pki_path = cfg.versions.as_ref().map(|x| x.join("primary.pki"));
-    let pack = files::PackService::new(res_path, pki_path.as_deref())?;
-
-    let api_url = base_url + router::API_PREFIX + "/";
-    let openapi = docs::OpenApiService::new(&api_url, auth_kind)?;
+    let res_roots: Vec<&Path> = std::iter::once(res_path)
+        .chain(cfg.res_overlays.iter().map(PathBuf::as_path))
+        .collect();
+    let pack = files::PackService::new(&res_roots, pki_path.as_deref())?;
+
+    let api_urls: Vec<String> = base_urls
+        .iter()
+        .map(|base_url| format!("{}{}/", base_url, paths.api()))
+        .collect();
+    let api_url = &api_urls[0];
+    let openapi = docs::OpenApiService::new(&api_urls, docs, auth_kind)?;
+
+    // The relations between SQL tables for GraphQL
+    #[cfg(feature = "graphql")]
+    let db_table_rels = graphql::SharedTableRels::load(
+        sqlite_path,
+        query_cfg.graphql_cache_size,
+        locales.default_lang(),
+    )?;
+    #[cfg(feature = "graphql")]
+    db_table_rels.spawn_watcher(sqlite_path, events.clone())?;
 
-    let api_uri = Uri::from_str(&api_url)?;
+    let api_uri = Uri::from_str(api_url)?;
     Ok(ApiService::new(
-        db,
-        locale_root,
+        locales,
         pack,
         openapi,
         api_uri,
-        tydb,
-        rev,
+        paths.api().to_owned(),
+        data,
+        #[cfg(feature = "graphql")]
         db_table_rels,
         res_path,
         sqlite_path,
+        query_cfg.timeout(),
+        max_response_bytes,
+        max_request_bytes,
+        maintenance,
+        cfg.tables.clone(),
+        events,
+        LuRes::from_config(cfg, &base_urls[0], paths),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use http::{header::ACCEPT, HeaderMap, HeaderValue, Uri};
+
+    use super::{query_param, rev, Accept, ApiRoute, PercentDecoded, RouteError};
+
+    #[test]
+    fn test_accept_negotiate() {
+        let negotiate = |header| match Accept::negotiate(header) {
+            Accept::Json => "json",
+            Accept::Yaml => "yaml",
+            Accept::Csv => "csv",
+        };
+        assert_eq!(negotiate("application/yaml"), "yaml");
+        assert_eq!(negotiate("text/csv; charset=utf-8"), "csv");
+        assert_eq!(
+            negotiate("application/yaml;q=0.9, application/json;q=1.0"),
+            "json"
+        );
+        assert_eq!(
+            negotiate("application/json;q=0.5, application/yaml"),
+            "yaml"
+        );
+        assert_eq!(
+            negotiate("text/html, application/yaml;q=0.8, */*;q=0.1"),
+            "yaml"
+        );
+        assert_eq!(negotiate("application/yaml, text/csv"), "yaml");
+        assert_eq!(negotiate("application/yaml;q=0"), "json");
+        assert_eq!(negotiate("text/html"), "json");
+        assert_eq!(negotiate(""), "json");
+    }
+
+    #[test]
+    fn test_accept_format_param() {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static("application/yaml"));
+        let of = |uri: &'static str| match Accept::of(&Uri::from_static(uri), &headers) {
+            Accept::Json => "json",
+            Accept::Yaml => "yaml",
+            Accept::Csv => "csv",
+        };
+        assert_eq!(of("/v0/tables?format=csv"), "csv");
+        assert_eq!(of("/v0/tables?pretty=1&format=JSON"), "json");
+        // unknown formats fall back to the header
+        assert_eq!(of("/v0/tables?format=cbor"), "yaml");
+        assert_eq!(of("/v0/tables"), "yaml");
+    }
+
+    /// The `Debug` output of the route for `path`, if there is one
+    fn route(path: &str) -> Option<String> {
+        ApiRoute::from_str(path).ok().map(|r| format!("{:?}", r))
+    }
+
+    #[test]
+    fn test_routes() {
+        let cases = [
+            ("/v0/tables", "Tables"),
+            ("/v0/tables/Objects", r#"TableByName("Objects")"#),
+            ("/v0/tables/Objects/def", r#"TableByName("Objects")"#),
+            ("/v0/tables/Objects/columns", r#"TableColumns("Objects")"#),
+            ("/v0/tables/Objects/all", r#"AllTableRows("Objects")"#),
+            ("/v0/tables/Objects/count", r#"TableCount("Objects")"#),
+            ("/v0/tables/Objects/stats", r#"TableStats("Objects")"#),
+            (
+                "/v0/tables/Objects/export.sqlite",
+                r#"TableExport("Objects")"#,
+            ),
+            ("/v0/tables/Objects/1", r#"TableRowsByPK("Objects", "1")"#),
+            (
+                "/v0/query/SELECT%201",
+                r#"Query(PercentDecoded("SELECT 1"))"#,
+            ),
+            (
+                "/v0/query/explain/SELECT%201",
+                r#"QueryExplain(PercentDecoded("SELECT 1"))"#,
+            ),
+            (
+                "/v0/locale/by-key/Missions_1_name",
+                r#"LocaleByKey("Missions_1_name")"#,
+            ),
+            ("/v0/locale/by-key/", r#"Locale(["by-key"])"#),
+            ("/v0/locale/languages", "LocaleLanguages"),
+            ("/v0/locale/Objects/$all", r#"Locale(["Objects", "$all"])"#),
+            ("/v0/rev", "Rev(Base)"),
+            ("/v0/rev/objects/1/", "Rev(ObjectById(1))"),
+            ("/v0/objects/1/components", "Rev(ObjectComponentsById(1))"),
+            ("/v0/objects/1/image", "Rev(ObjectImageById(1))"),
+            ("/v0/crc/1", "Crc(1)"),
+            ("/v0/openapi.json", "OpenApiV0"),
+            ("/v0/events", "Events"),
+            ("/v0/meta", "Meta"),
+            ("/v1/tables", "Tables"),
+            ("/v1/files/1/raw", "FileRaw(1)"),
+            ("/v1/res/ls", "ResList"),
+            ("/v1/res/maps/nimbus", r#"Res(["maps", "nimbus"])"#),
+            ("/v1/res/ls/x", r#"Res(["ls", "x"])"#),
+            ("/", "SwaggerUI"),
+            ("", "SwaggerUIRedirect"),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(route(path).as_deref(), Some(expected), "{}", path);
+        }
+        #[cfg(feature = "graphql")]
+        for (path, expected) in [
+            ("/v0/graphql", r#"GraphQl(PercentDecoded(""))"#),
+            (
+                "/v0/graphql/%7BObjects%7D",
+                r#"GraphQl(PercentDecoded("{Objects}"))"#,
+            ),
+            ("/v0/graphql/schema", "GraphQlSchema"),
+            ("/v0/graphql/tables", "GraphQlTables"),
+            ("/v0/graphql/bulk", "GraphQlBulk"),
+        ] {
+            assert_eq!(route(path).as_deref(), Some(expected), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_routes_not_found() {
+        for path in [
+            "/v0",
+            "/v2/tables",
+            "/v0/nope",
+            "/v0/tables/Objects/def/x",
+            "/v0/tables/Objects/1/x",
+            "/v0/query",
+            "/v0/crc",
+            "/v0/crc/x",
+            "/v0/crc/1/2",
+            "/v0/meta/x",
+            "/v0/objects/1",
+            "/v0/objects/x/components",
+            "/v0/objects/1/image/x",
+            "/v1/tables/Objects",
+            "/v1/files/1",
+            "/v1/files/x/raw",
+            "/v1/files/1/raw/x",
+        ] {
+            assert!(
+                matches!(ApiRoute::from_str(path), Err(RouteError::NotFound)),
+                "{}",
+                path
+            );
+        }
+        assert!(matches!(
+            ApiRoute::from_str("/v0/rev/objects/x"),
+            Err(RouteError::Rev(_))
+        ));
+    }
+
+    #[test]
+    fn test_trailing_slash() {
+        for path in ["/v0/rev/factions", "/v0/rev/factions/"] {
+            let route = ApiRoute::from_str(path).unwrap();
+            assert!(
+                matches!(route, ApiRoute::Rev(rev::Route::Factions)),
+                "{}",
+                path
+            );
+        }
+        for path in ["/v0/tables/Objects/count", "/v0/tables/Objects/count/"] {
+            let route = ApiRoute::from_str(path).unwrap();
+            assert!(matches!(route, ApiRoute::TableCount("Objects")), "{}", path);
+        }
+        assert!(matches!(
+            ApiRoute::from_str(""),
+            Ok(ApiRoute::SwaggerUIRedirect)
+        ));
+        assert!(matches!(ApiRoute::from_str("/"), Ok(ApiRoute::SwaggerUI)));
+        assert!(ApiRoute::from_str("/v0/rev/factions//").is_err());
+    }
+
+    #[test]
+    fn test_plus_in_path_and_query() {
+        // `+` is a plus sign in the path ...
+        let path = PercentDecoded::from_str("Some+Type%20A").unwrap();
+        assert_eq!(path.0, "Some+Type A");
+        let sql = PercentDecoded::from_str("SELECT%201+1").unwrap();
+        assert_eq!(sql.0, "SELECT 1+1");
+        // ... but a space in the query string
+        let uri = Uri::from_static("/v0/rev/objects/search?q=Some+Type%2B");
+        assert_eq!(query_param(&uri, "q").as_deref(), Some("Some Type+"));
+    }
+}
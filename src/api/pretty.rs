@@ -0,0 +1,90 @@
+//! # Pretty printed JSON
+//!
+//! Responses are serialized compactly. For `?pretty=1` (or `X-Pretty: 1`), the
+//! finished JSON body is re-indented the way `serde_json::to_string_pretty` would
+//! print it. This works on the bytes, so the order of keys is kept as is.
+
+const INDENT: &[u8] = b"  ";
+
+fn newline(out: &mut Vec<u8>, depth: usize) {
+    out.push(b'\n');
+    for _ in 0..depth {
+        out.extend_from_slice(INDENT);
+    }
+}
+
+/// Re-indent compact JSON
+pub(super) fn pretty_json(compact: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(compact.len() * 2);
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut iter = compact.iter().copied().peekable();
+    while let Some(b) = iter.next() {
+        if in_string {
+            out.push(b);
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match b {
+            b'"' => {
+                in_string = true;
+                out.push(b);
+            }
+            b'{' | b'[' => {
+                out.push(b);
+                match iter.peek() {
+                    Some(b'}' | b']') => out.push(iter.next().unwrap()),
+                    _ => {
+                        depth += 1;
+                        newline(&mut out, depth);
+                    }
+                }
+            }
+            b'}' | b']' => {
+                depth = depth.saturating_sub(1);
+                newline(&mut out, depth);
+                out.push(b);
+            }
+            b',' => {
+                out.push(b);
+                newline(&mut out, depth);
+            }
+            b':' => out.extend_from_slice(b": "),
+            b' ' | b'\t' | b'\n' | b'\r' => {}
+            _ => out.push(b),
+        }
+    }
+    out
+}
+
+/// Whether a `pretty` query parameter or `X-Pretty` header value asks for pretty output
+pub(super) fn is_enabled(value: &str) -> bool {
+    !matches!(value, "0" | "false" | "no")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pretty_json;
+
+    fn pretty(v: &serde_json::Value) -> String {
+        let compact = serde_json::to_vec(v).unwrap();
+        String::from_utf8(pretty_json(&compact)).unwrap()
+    }
+
+    #[test]
+    fn test_pretty_json() {
+        let v = serde_json::json!({
+            "a": [1, 2, {}],
+            "b": {"c": "x,y:{\"}", "d": []},
+            "e": null,
+        });
+        assert_eq!(pretty(&v), serde_json::to_string_pretty(&v).unwrap());
+        assert_eq!(pretty(&serde_json::json!("[1]")), "\"[1]\"");
+    }
+}
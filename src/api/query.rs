@@ -1,8 +1,16 @@
-use std::{borrow::Borrow, path::Path};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use rusqlite::{types::ValueRef, Connection, OpenFlags};
+use futures_util::stream;
+use hyper::body::Bytes;
+use rusqlite::{types::ValueRef, Connection, OptionalExtension};
+use serde::Serialize;
+use tokio::io::AsyncReadExt;
 
-use super::PercentDecoded;
+use super::{limit, ApiError};
 
 fn fmt_valueref(str: &mut String, valueref: &ValueRef) -> Result<(), rusqlite::Error> {
     match valueref {
@@ -22,10 +30,83 @@ fn fmt_valueref(str: &mut String, valueref: &ValueRef) -> Result<(), rusqlite::E
     Ok(())
 }
 
-pub(super) fn query(sqlite_path: &Path, query: PercentDecoded) -> Result<String, rusqlite::Error> {
-    dbg!(&query);
-    let conn = Connection::open_with_flags(sqlite_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
-    let mut stmt = conn.prepare(query.borrow())?;
+/// The name of the table in a `no such table` error from SQLite
+pub(super) fn missing_table(error: &rusqlite::Error) -> Option<&str> {
+    match error {
+        rusqlite::Error::SqliteFailure(_, Some(message)) => message.strip_prefix("no such table: "),
+        _ => None,
+    }
+}
+
+/// Remove comments and the contents of quoted strings and identifiers from `sql`
+fn strip_literals(sql: &str) -> String {
+    let mut code = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' | '[' => {
+                let end = if c == '[' { ']' } else { c };
+                code.push(' ');
+                while let Some(c) = chars.next() {
+                    if c == end {
+                        // quotes are escaped by doubling them
+                        if end != ']' && chars.peek() == Some(&end) {
+                            chars.next();
+                            continue;
+                        }
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                code.push(' ');
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                code.push(' ');
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            c => code.push(c),
+        }
+    }
+    code
+}
+
+/// Check that `sql` is a single `SELECT` statement (optionally with a `WITH` clause)
+pub(super) fn check_select(sql: &str) -> Result<(), &'static str> {
+    let code = strip_literals(sql);
+    let code = code.trim_end();
+    let code = code.strip_suffix(';').unwrap_or(code).trim();
+    if code.contains(';') {
+        return Err("only a single statement is allowed");
+    }
+    let keyword = code
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or_default();
+    if keyword.eq_ignore_ascii_case("select") || keyword.eq_ignore_ascii_case("with") {
+        Ok(())
+    } else {
+        Err("only SELECT statements are allowed")
+    }
+}
+
+pub(super) fn query(conn: &Connection, query: &str) -> Result<String, rusqlite::Error> {
+    let mut stmt = conn.prepare(query)?;
+    if !stmt.readonly() {
+        return Err(rusqlite::Error::InvalidQuery);
+    }
 
     let cols = stmt.column_count();
     let mut response = String::new();
@@ -44,3 +125,143 @@ pub(super) fn query(sqlite_path: &Path, query: PercentDecoded) -> Result<String,
     }
     Ok(response)
 }
+
+/// Counter to make the names of export files unique
+static EXPORT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Size of the chunks an export is sent in
+const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Deletes the file at the path when dropped
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// A row of the output of `EXPLAIN QUERY PLAN`
+#[derive(Debug, Serialize)]
+pub(super) struct PlanStep {
+    id: i64,
+    parent: i64,
+    detail: String,
+}
+
+/// Get the query plan of `query` without running it
+pub(super) fn explain(conn: &Connection, query: &str) -> Result<Vec<PlanStep>, rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", query))?;
+    let rows = stmt.query_map([], |row| {
+        Ok(PlanStep {
+            id: row.get(0)?,
+            parent: row.get(1)?,
+            detail: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Stream the file of `tmp` in chunks, deleting it when the stream ends or is dropped
+///
+/// The stream fails once it has sent more than the [`limit`] allows, which aborts
+/// the response mid-stream.
+fn export_body(file: fs::File, tmp: TempFile) -> hyper::Body {
+    let file = tokio::fs::File::from_std(file);
+    let max = limit::max_response_bytes();
+    let chunks = stream::try_unfold((file, tmp, 0), move |(mut file, tmp, sent)| async move {
+        let mut buf = vec![0; EXPORT_CHUNK_SIZE];
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            return Ok::<_, io::Error>(None);
+        }
+        let sent = sent + n;
+        if let Some(max) = max.filter(|max| sent > *max) {
+            let e = limit::TooLarge { max };
+            return Err(io::Error::new(io::ErrorKind::Other, e));
+        }
+        buf.truncate(n);
+        Ok(Some((Bytes::from(buf), (file, tmp, sent))))
+    });
+    hyper::Body::wrap_stream(chunks)
+}
+
+/// Copy a single table of the database at `sqlite_path` into a new SQLite file
+///
+/// Returns a body that streams the new file, along with its length. The name is
+/// only used after it was found in the `sqlite_master` of `conn`, so it can't refer
+/// to anything but a table. Returns `None` if there is no such table.
+pub(super) fn export_table(
+    conn: &Connection,
+    sqlite_path: &Path,
+    name: &str,
+) -> Result<Option<(hyper::Body, u64)>, ApiError> {
+    let create_sql: Option<String> = conn
+        .query_row(
+            "select sql from sqlite_master where type = 'table' and name = ?1",
+            [name],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(create_sql) = create_sql else {
+        return Ok(None);
+    };
+
+    let n = EXPORT_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let file_name = format!("paradox-export-{}-{}.sqlite", std::process::id(), n);
+    let tmp = TempFile(std::env::temp_dir().join(file_name));
+
+    let export = Connection::open(&tmp.0)?;
+    export.execute_batch(&create_sql)?;
+    export.execute(
+        "attach database ?1 as src",
+        [sqlite_path.to_string_lossy().into_owned()],
+    )?;
+    let quoted = name.replace('"', "\"\"");
+    export.execute_batch(&format!(
+        "insert into main.\"{0}\" select * from src.\"{0}\"; detach database src;",
+        quoted
+    ))?;
+    export.close().map_err(|(_, e)| e)?;
+
+    let file = fs::File::open(&tmp.0)?;
+    let len = file.metadata()?.len();
+    Ok(Some((export_body(file, tmp), len)))
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{check_select, explain, missing_table, query};
+
+    #[test]
+    fn test_check_select() {
+        assert!(check_select("SELECT * FROM Objects;").is_ok());
+        assert!(check_select("  with t as (select 1) select * from t").is_ok());
+        assert!(check_select("select ';' as x -- ; drop table y").is_ok());
+        assert!(check_select("select \"a;b\" from [c;d] /* ; */").is_ok());
+        assert!(check_select("select 1; select 2").is_err());
+        assert!(check_select("PRAGMA table_info(Objects)").is_err());
+        assert!(check_select("ATTACH 'x.sqlite' AS x").is_err());
+        assert!(check_select("delete from Objects").is_err());
+        assert!(check_select("").is_err());
+    }
+
+    #[test]
+    fn test_missing_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        let error = query(&conn, "select * from Objects").unwrap_err();
+        assert_eq!(missing_table(&error), Some("Objects"));
+    }
+
+    #[test]
+    fn test_explain() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("create table Objects (id integer primary key, name text);")
+            .unwrap();
+        let plan = explain(&conn, "select name from Objects where id = 1").unwrap();
+        assert_eq!(plan.len(), 1);
+        assert!(plan[0].detail.starts_with("SEARCH"));
+    }
+}
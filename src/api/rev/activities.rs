@@ -0,0 +1,74 @@
+use std::{
+    collections::{btree_map, BTreeMap},
+    iter::Copied,
+    slice,
+};
+
+use paradox_typed_db::{columns::ActivityRewardsColumn, rows::ActivityRewardsRow, TypedDatabase};
+use serde::Serialize;
+
+use super::{data::ActivityRev, Api, ReverseLookup};
+use crate::{
+    api::adapter::{AdapterLayout, LocaleTableAdapter, TypedTableIterAdapter},
+    data::locale::LocaleRoot,
+};
+
+type ActivityRewardsAdapter<'a, 'b> = TypedTableIterAdapter<
+    'a,
+    'b,
+    ActivityRewardsRow<'a, 'b>,
+    &'b BTreeMap<i32, i32>,
+    Copied<btree_map::Keys<'b, i32, i32>>,
+>;
+
+#[derive(Serialize)]
+struct ActivityLocale<'b> {
+    #[serde(rename = "Activities")]
+    activities: LocaleTableAdapter<'b>,
+}
+
+#[derive(Serialize)]
+pub(super) struct ActivityEmbedded<'a, 'b> {
+    #[serde(rename = "ActivityRewards", skip_serializing_if = "Option::is_none")]
+    activity_rewards: Option<ActivityRewardsAdapter<'a, 'b>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    locale: Option<ActivityLocale<'b>>,
+}
+
+type ActivityApiResult<'a, 'b> = Api<&'b ActivityRev, ActivityEmbedded<'a, 'b>>;
+
+pub(super) fn rev_activity<'a, 'b>(
+    db: &'b TypedDatabase<'a>,
+    rev: &'b ReverseLookup,
+    loc: &'b LocaleRoot,
+    id: i32,
+) -> Option<ActivityApiResult<'a, 'b>> {
+    let (key, data) = rev.activities().get_key_value(&id)?;
+    let activity_rewards = db
+        .activity_rewards
+        .as_ref()
+        .map(|table| TypedTableIterAdapter {
+            index: &data.activity_rewards,
+            keys: data.activity_rewards.keys().copied(),
+            table,
+            id_col: table
+                .get_col(ActivityRewardsColumn::ActivityRewardIndex)
+                .unwrap(),
+            layout: AdapterLayout::Seq,
+        });
+    let loc_keys = loc.root.keys();
+    let locale = loc
+        .root
+        .node()
+        .get_str(loc_keys.activities)
+        .map(|node| ActivityLocale {
+            activities: LocaleTableAdapter::new(node, slice::from_ref(key)),
+        });
+    Some(Api {
+        data,
+        embedded: ActivityEmbedded {
+            activity_rewards,
+            locale,
+        },
+    })
+}
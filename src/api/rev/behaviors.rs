@@ -50,37 +50,121 @@ impl Serialize for EmbeddedBehaviors<'_, '_> {
         S: serde::Serializer,
     {
         let mut m = serializer.serialize_map(Some(self.keys.len()))?;
-        let col_behavior_id = self
-            .table_templates
-            .get_col(BehaviorTemplateColumn::BehaviorId)
-            .unwrap();
         for &behavior_id in &self.keys {
             m.serialize_key(&behavior_id)?;
-            let b = Behavior {
-                template: BehaviorTemplateRow::get(
-                    self.table_templates,
-                    behavior_id,
-                    behavior_id,
-                    col_behavior_id,
-                ),
-                parameters: BehaviorParameters {
-                    key: behavior_id,
-                    table: self.table_parameters,
-                },
-            };
+            let b = Behavior::new(behavior_id, self.table_templates, self.table_parameters);
             m.serialize_value(&b)?;
         }
         m.end()
     }
 }
 
+impl<'a, 'b> Behavior<'a, 'b> {
+    fn new(
+        behavior_id: i32,
+        table_templates: &'b BehaviorTemplateTable<'a>,
+        table_parameters: &'b BehaviorParameterTable<'a>,
+    ) -> Self {
+        let col_behavior_id = table_templates
+            .get_col(BehaviorTemplateColumn::BehaviorId)
+            .unwrap();
+        Self {
+            template: BehaviorTemplateRow::get(
+                table_templates,
+                behavior_id,
+                behavior_id,
+                col_behavior_id,
+            ),
+            parameters: BehaviorParameters {
+                key: behavior_id,
+                table: table_parameters,
+            },
+        }
+    }
+}
+
+/// A behavior with all behaviors it uses, recursively
+///
+/// Every behavior is expanded only once, so the tree is never larger than the
+/// behavior graph, even if many behaviors share the same subtrees.
+#[derive(Serialize)]
+pub(super) struct BehaviorTree<'a, 'b> {
+    behavior_id: i32,
+    /// Set if this behavior is one of its own ancestors, in which case it is not expanded again
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cycle: bool,
+    /// Set if this behavior was already expanded earlier in the tree
+    #[serde(rename = "ref", skip_serializing_if = "std::ops::Not::not")]
+    is_ref: bool,
+    #[serde(flatten)]
+    behavior: Option<Behavior<'a, 'b>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    uses: Vec<BehaviorTree<'a, 'b>>,
+}
+
+/// Expand `behavior_id`, unless it is on the `path` from the root or was `expanded` before
+fn build_tree<'a, 'b>(
+    db: &'b TypedDatabase<'a>,
+    rev: &ReverseLookup,
+    path: &mut Vec<i32>,
+    expanded: &mut BTreeSet<i32>,
+    behavior_id: i32,
+) -> BehaviorTree<'a, 'b> {
+    let cycle = path.contains(&behavior_id);
+    if cycle || !expanded.insert(behavior_id) {
+        return BehaviorTree {
+            behavior_id,
+            cycle,
+            is_ref: !cycle,
+            behavior: None,
+            uses: Vec::new(),
+        };
+    }
+    path.push(behavior_id);
+    let uses = match rev.behaviors().get(&behavior_id) {
+        Some(index) => index
+            .uses
+            .iter()
+            .filter(|&&id| id > 0)
+            .map(|&id| build_tree(db, rev, path, expanded, id))
+            .collect(),
+        None => Vec::new(),
+    };
+    path.pop();
+    BehaviorTree {
+        behavior_id,
+        cycle: false,
+        is_ref: false,
+        behavior: Some(Behavior::new(
+            behavior_id,
+            &db.behavior_templates,
+            &db.behavior_parameters,
+        )),
+        uses,
+    }
+}
+
+/// Get the behavior with `behavior_id` as a nested tree of the behaviors it uses
+pub(super) fn tree<'db, 'd>(
+    db: &'d TypedDatabase<'db>,
+    rev: &ReverseLookup,
+    behavior_id: i32,
+) -> Option<BehaviorTree<'db, 'd>> {
+    let root = build_tree(db, rev, &mut Vec::new(), &mut BTreeSet::new(), behavior_id);
+    let exists = matches!(&root.behavior, Some(b) if b.template.is_some());
+    match exists || rev.behaviors().contains_key(&behavior_id) {
+        true => Some(root),
+        false => None,
+    }
+}
+
 pub(super) fn lookup<'db, 'd, 'r>(
     db: &'d TypedDatabase<'db>,
     rev: &'r ReverseLookup,
     behavior_id: i32,
 ) -> Api<Option<&'r BehaviorKeyIndex>, EmbeddedBehaviors<'db, 'd>> {
     Api {
-        data: rev.behaviors.get(&behavior_id),
+        data: rev.behaviors().get(&behavior_id),
         embedded: EmbeddedBehaviors {
             keys: rev.get_behavior_set(behavior_id),
             table_templates: &db.behavior_templates,
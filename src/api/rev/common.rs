@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use latin1str::Latin1Str;
 use paradox_typed_db::{
@@ -16,7 +16,7 @@ use super::data::MissionTaskUIDLookup;
 
 #[derive(Debug, Clone)]
 pub struct MapFilter<'a, E> {
-    base: &'a HashMap<i32, E>,
+    base: &'a BTreeMap<i32, E>,
     keys: &'a [i32],
 }
 
@@ -83,7 +83,7 @@ impl<'a, E: Serialize> Serialize for MapFilter<'a, E> {
     }
 }
 
-pub(super) type MissionTaskHash<'b> = &'b HashMap<i32, MissionTaskUIDLookup>;
+pub(super) type MissionTaskHash<'b> = &'b BTreeMap<i32, MissionTaskUIDLookup>;
 
 pub(super) type MissionTasks<'a, 'b> =
     TypedTableIterAdapter<'a, 'b, MissionTasksRow<'a, 'b>, MissionTaskHash<'b>, I32Slice<'b>>;
@@ -134,7 +134,7 @@ impl<'a, 'b> Serialize for MissionsTaskIconsAdapter<'a, 'b> {
     }
 }
 
-impl FindHash for HashMap<i32, MissionTaskUIDLookup> {
+impl FindHash for BTreeMap<i32, MissionTaskUIDLookup> {
     fn find_hash(&self, v: i32) -> Option<i32> {
         self.get(&v).map(|r| r.mission)
     }
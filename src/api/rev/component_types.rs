@@ -1,14 +1,19 @@
 use std::collections::BTreeMap;
 
+use assembly_core::buffer::CastError;
+use assembly_fdb::mem::Database;
 use paradox_typed_db::TypedDatabase;
 use serde::Serialize;
 
 use super::{
     common::{ObjectTypeEmbedded, ObjectsRefAdapter},
-    data::{ComponentUse, ComponentsUse, ReverseLookup},
+    data::{
+        ComponentUse, ComponentsUse, ReverseLookup, COMPONENT_ID_COLLECTIBLE,
+        COMPONENT_ID_DESTRUCTIBLE, COMPONENT_ID_INVENTORY, COMPONENT_ID_ITEM,
+    },
     Api,
 };
-use crate::api::adapter::Keys;
+use crate::api::{adapter::Keys, tables::table_key_json};
 
 #[derive(Serialize)]
 pub(super) struct Components<'a> {
@@ -18,7 +23,7 @@ pub(super) struct Components<'a> {
 impl<'a> Components<'a> {
     pub fn new(rev: &'a ReverseLookup) -> Self {
         Self {
-            components: Keys::new(&rev.component_use.0),
+            components: Keys::new(&rev.component_use().0),
         }
     }
 }
@@ -28,7 +33,7 @@ pub(super) fn rev_component_type<'r, 'db, 'd>(
     rev: &'r ReverseLookup,
     key: i32,
 ) -> Option<Api<&'r ComponentsUse, ObjectTypeEmbedded<'db, 'd, Vec<i32>>>> {
-    rev.component_use.ty(key).map(|data: &'r ComponentsUse| {
+    rev.component_use().ty(key).map(|data: &'r ComponentsUse| {
         // FIXME: improve this
         let keys: Vec<i32> = data
             .components
@@ -42,12 +47,65 @@ pub(super) fn rev_component_type<'r, 'db, 'd>(
     })
 }
 
-pub(super) fn rev_single_component(
-    rev: &ReverseLookup,
+/// The tables that hold the rows for a component type, by component type
+static COMPONENT_TABLES: &[(i32, &str)] = &[
+    (1, "ControllablePhysicsComponent"),
+    (2, "RenderComponent"),
+    (3, "SimplePhysicsComponent"),
+    (5, "ScriptComponent"),
+    (COMPONENT_ID_DESTRUCTIBLE, "DestructibleComponent"),
+    (COMPONENT_ID_ITEM, "ItemComponent"),
+    (16, "VendorComponent"),
+    (COMPONENT_ID_INVENTORY, "InventoryComponent"),
+    (COMPONENT_ID_COLLECTIBLE, "CollectibleComponent"),
+    (26, "PetComponent"),
+    (35, "MinifigComponent"),
+    (40, "PhantomPhysicsComponent"),
+    (43, "PropertyEntranceComponent"),
+    (48, "RebuildComponent"),
+    (60, "BaseCombatAIComponent"),
+    (73, "MissionNPCComponent"),
+];
+
+fn component_table(key: i32) -> Option<&'static str> {
+    COMPONENT_TABLES
+        .iter()
+        .find(|(ty, _)| *ty == key)
+        .map(|(_, table)| *table)
+}
+
+/// Get the LOTs that use a component, with the rows of the component table embedded
+pub(super) fn rev_single_component<'a>(
+    db: Database<'a>,
+    rev: &'a ReverseLookup,
     key: i32,
     cid: i32,
-) -> Option<&ComponentUse> {
-    rev.component_use
-        .ty(key)
-        .and_then(|c| c.components.get(&cid))
+) -> Result<Option<Api<&'a ComponentUse, BTreeMap<&'static str, impl Serialize + 'a>>>, CastError> {
+    let component_use = rev.component_use().ty(key);
+    let Some(data) = component_use.and_then(|c| c.components.get(&cid)) else {
+        return Ok(None);
+    };
+    let mut embedded = BTreeMap::new();
+    if let Some(table) = component_table(key) {
+        if let Some(rows) = table_key_json(db, table, &cid.to_string())? {
+            embedded.insert(table, rows);
+        }
+    }
+    Ok(Some(Api { data, embedded }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::component_table;
+
+    #[test]
+    fn test_component_table() {
+        assert_eq!(component_table(2), Some("RenderComponent"));
+        assert_eq!(component_table(7), Some("DestructibleComponent"));
+        assert_eq!(component_table(11), Some("ItemComponent"));
+        assert_eq!(component_table(42), None);
+        assert_eq!(component_table(43), Some("PropertyEntranceComponent"));
+        assert_eq!(component_table(48), Some("RebuildComponent"));
+        assert_eq!(component_table(73), Some("MissionNPCComponent"));
+    }
 }
@@ -5,17 +5,26 @@
 //! be used to access the data in the FDB - potentially faster than scanning
 //! it on every request.
 //!
-//! The [`ReverseLookup::new`] function is called once at startup of the server
-//! and the result is passed to the API filters.
+//! A [`ReverseLookup`] is created once at startup of the server and passed to
+//! the API filters. Each index is built by its own `build_*` function, either
+//! on first access ([`ReverseLookup::lazy`]) or concurrently on scoped threads
+//! ([`ReverseLookup::new`]).
 
 use std::{
     borrow::Cow,
     collections::{BTreeMap, BTreeSet, HashMap},
+    panic::{self, AssertUnwindSafe},
     str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
     time::Instant,
 };
 
 use latin1str::Latin1Str;
+use once_cell::sync::OnceCell;
 use paradox_typed_db::{
     columns::{
         ActivitiesColumn, DeletionRestrictionsColumn, DestructibleComponentColumn,
@@ -24,11 +33,11 @@ use paradox_typed_db::{
     TypedDatabase,
 };
 use serde::Serialize;
-use tracing::{info, log};
+use tracing::{debug, info, log};
 
 use crate::{
     api::adapter::{Filtered, Keys},
-    data::skill_system::match_action_key,
+    data::skill_system::is_behavior_edge,
 };
 
 use super::loot_matrix_index::LootMatrixIndexRev;
@@ -47,7 +56,7 @@ pub struct SkillIdLookup {
 #[derive(Debug, Default, Clone, Serialize)]
 pub struct BehaviorKeyIndex {
     skill: BTreeSet<i32>,
-    uses: BTreeSet<i32>,
+    pub(crate) uses: BTreeSet<i32>,
     used_by: BTreeSet<i32>,
 }
 
@@ -64,6 +73,7 @@ pub struct ComponentsUse {
 
 pub const COMPONENT_ID_DESTRUCTIBLE: i32 = 7;
 pub const COMPONENT_ID_ITEM: i32 = 11;
+pub const COMPONENT_ID_INVENTORY: i32 = 17;
 pub const COMPONENT_ID_COLLECTIBLE: i32 = 23;
 
 #[derive(Debug, Clone, Serialize)]
@@ -76,6 +86,8 @@ pub struct MissionTaskUIDLookup {
 pub struct ActivityRev {
     /// IDs of the RebuildComponent with matching `activityID`
     rebuild: Vec<i32>,
+    /// Map from `ActivityRewards::ActivityRewardIndex` to `ActivityRewards::objectTemplate`
+    pub(crate) activity_rewards: BTreeMap<i32, i32>,
 }
 
 #[derive(Debug, Default, Clone, Serialize)]
@@ -97,13 +109,13 @@ pub struct FactionRev {
 #[derive(Debug, Clone, Serialize)]
 pub struct ObjectStrings {
     /// name
-    n: String,
+    pub(super) n: String,
     /// description
     #[serde(skip_serializing_if = "Option::is_none")]
-    d: Option<String>,
+    pub(super) d: Option<String>,
     /// display_name
     #[serde(skip_serializing_if = "Option::is_none")]
-    i: Option<String>,
+    pub(super) i: Option<String>,
     /// internal_notes
     #[serde(skip_serializing_if = "Option::is_none")]
     t: Option<String>,
@@ -111,9 +123,9 @@ pub struct ObjectStrings {
 
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct ObjectItemComponentUse {
-    currency_lot: BTreeSet<i32>,
-    commendation_lot: BTreeSet<i32>,
-    subitems: BTreeSet<i32>,
+    pub(super) currency_lot: BTreeSet<i32>,
+    pub(super) commendation_lot: BTreeSet<i32>,
+    pub(super) subitems: BTreeSet<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Default)]
@@ -124,7 +136,7 @@ pub struct ObjectJetPackUse {
 
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct ObjectMissionUse {
-    reward_items: BTreeSet<i32>,
+    pub(super) reward_items: BTreeSet<i32>,
     // ignore offer, target for now, should be inverse to MissionNPCComponent
 }
 
@@ -134,21 +146,33 @@ pub struct ObjectPetTamingUse {
     npc_lot: BTreeSet<i32>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct ObjectSkillUse {
+    /// `ObjectSkills::castOnType`
+    cast_on_type: i32,
+    /// `ObjectSkills::AICombatWeight`
+    ai_combat_weight: i32,
+}
+
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct ObjectsUse {
     /// The `CurrencyDenominations.value` matching this LOT
     currency_denomination: Option<i32>,
     deletion_restrictions: BTreeSet<i32>,
-    inventory_component: BTreeSet<i32>,
-    item_component: ObjectItemComponentUse,
-    item_sets: BTreeSet<i32>,
+    pub(super) inventory_component: BTreeSet<i32>,
+    pub(super) item_component: ObjectItemComponentUse,
+    pub(super) item_sets: BTreeSet<i32>,
     jet_pack_pad_component: ObjectJetPackUse,
     loot_table_index: BTreeSet<i32>,
+    /// The `LootMatrixIndex`es that contain a `LootTableIndex` with this LOT
+    dropped_by_loot_matrix: BTreeSet<i32>,
     npc_icons_lot: BTreeSet<i32>,
     rebuild_sections: BTreeSet<i32>,
-    missions: ObjectMissionUse,
+    pub(super) missions: ObjectMissionUse,
     reward_codes: BTreeSet<i32>,
     pet_taming_puzzles: ObjectPetTamingUse,
+    /// Map from `ObjectSkills::skillID` to the row for this LOT
+    skills: BTreeMap<i32, ObjectSkillUse>,
 }
 
 #[derive(Debug, Clone, Default, Serialize)]
@@ -187,6 +211,48 @@ pub struct GateVersionUse {
     zones: BTreeSet<i32>,
 }
 
+fn added(to: &BTreeSet<i32>, from: &BTreeSet<i32>) -> BTreeSet<i32> {
+    to.difference(from).copied().collect()
+}
+
+impl GateVersionUse {
+    /// The IDs in `self` that are not in `from`, per category
+    pub(crate) fn added_since(&self, from: &GateVersionUse) -> GateVersionUse {
+        GateVersionUse {
+            activities: added(&self.activities, &from.activities),
+            deletion_restrictions: added(&self.deletion_restrictions, &from.deletion_restrictions),
+            emotes: added(&self.emotes, &from.emotes),
+            item_sets: added(&self.item_sets, &from.item_sets),
+            missions: added(&self.missions, &from.missions),
+            mission_tasks: added(&self.mission_tasks, &from.mission_tasks),
+            objects: added(&self.objects, &from.objects),
+            player_statistics: added(&self.player_statistics, &from.player_statistics),
+            preconditions: added(&self.preconditions, &from.preconditions),
+            property_template: added(&self.property_template, &from.property_template),
+            reward_codes: added(&self.reward_codes, &from.reward_codes),
+            speedchat_menu: added(&self.speedchat_menu, &from.speedchat_menu),
+            skills: added(&self.skills, &from.skills),
+            ug_behavior_sounds: added(&self.ug_behavior_sounds, &from.ug_behavior_sounds),
+            whats_cool_item_spotlight: added(
+                &self.whats_cool_item_spotlight,
+                &from.whats_cool_item_spotlight,
+            ),
+            whats_cool_news_and_tips: added(
+                &self.whats_cool_news_and_tips,
+                &from.whats_cool_news_and_tips,
+            ),
+            zone_loading_tips: added(&self.zone_loading_tips, &from.zone_loading_tips),
+            zones: added(&self.zones, &from.zones),
+            loot_matrix: self
+                .loot_matrix
+                .iter()
+                .filter(|(index, _)| !from.loot_matrix.contains_key(index))
+                .map(|(&index, &id)| (index, id))
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct GateVersionsUse {
     inner: BTreeMap<String, GateVersionUse>,
@@ -223,7 +289,40 @@ impl serde::Serialize for GateVersionsUse {
 
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct SkillCooldownGroup {
-    skills: BTreeSet<i32>,
+    pub skills: BTreeSet<i32>,
+    /// The cooldowns of the skills in this group
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooldown: Option<CooldownStats>,
+}
+
+/// Summary of the `SkillBehavior.cooldown` values in a [`SkillCooldownGroup`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CooldownStats {
+    pub min: f64,
+    pub max: f64,
+    /// The most common cooldown, the smallest one on a tie
+    pub representative: f64,
+}
+
+impl CooldownStats {
+    fn new(mut values: Vec<f64>) -> Option<Self> {
+        values.sort_by(f64::total_cmp);
+        let (&min, &max) = (values.first()?, values.last()?);
+        let mut representative = (min, 0);
+        let mut rest = &values[..];
+        while let Some(&value) = rest.first() {
+            let count = rest.iter().take_while(|&&v| v == value).count();
+            if count > representative.1 {
+                representative = (value, count);
+            }
+            rest = &rest[count..];
+        }
+        Some(Self {
+            min,
+            max,
+            representative: representative.0,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Default)]
@@ -246,11 +345,36 @@ pub struct MissionRevMissions {
 
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct MissionRev {
+    /// The `prereq_mission_id` of this mission, as a list of alternatives that must all be met
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<Vec<i32>>,
     pub collectible_components: MissionRevCollectibleComponents,
     pub item_components: MissionRevItemComponents,
     pub missions: MissionRevMissions,
 }
 
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct EmoteRevMissions {
+    /// Set of `Missions` that have this emote in one of their `reward_emote` columns
+    pub reward_emote: BTreeSet<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct EmoteRev {
+    pub missions: EmoteRevMissions,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct PreconditionRevItemComponents {
+    /// Set of `ItemComponent`s that have this precondition in `reqPrecondition`
+    pub req_precondition: BTreeSet<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct PreconditionRev {
+    pub item_components: PreconditionRevItemComponents,
+}
+
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct ComponentRegistryRev(pub BTreeMap<i32, ComponentsUse>);
 
@@ -264,10 +388,10 @@ impl ComponentRegistryRev {
     }
 
     pub(crate) fn filter<K>(
-        &'static self,
+        &self,
         type_id: i32,
         keys: K,
-    ) -> Option<Filtered<BTreeMap<i32, ComponentUse>, K>> {
+    ) -> Option<Filtered<'_, BTreeMap<i32, ComponentUse>, K>> {
         self.ty(type_id).map(|cu| Filtered {
             inner: &cu.components,
             keys,
@@ -275,253 +399,633 @@ impl ComponentRegistryRev {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// The component IDs of an object, by component type
+pub type ObjectComponents = BTreeMap<i32, Vec<i32>>;
+
+/// One of the indices of a [`ReverseLookup`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Index {
+    MissionTaskUids,
+    SkillCooldownGroups,
+    SkillIds,
+    Behaviors,
+    MissionTypes,
+    Missions,
+    Factions,
+    Objects,
+    ObjectTypes,
+    ComponentUse,
+    ObjectComponents,
+    Activities,
+    LootTableIndex,
+    LootMatrixIndex,
+    GateVersions,
+    Preconditions,
+    Emotes,
+}
+
+/// The reverse indices over a [`TypedDatabase`]
+///
+/// Each index is built on first access, or all at once with [`ReverseLookup::warm`].
 pub struct ReverseLookup {
-    pub mission_task_uids: HashMap<i32, MissionTaskUIDLookup>,
-    pub skill_cooldown_groups: BTreeMap<i32, SkillCooldownGroup>,
-    pub skill_ids: HashMap<i32, SkillIdLookup>,
-    pub behaviors: BTreeMap<i32, BehaviorKeyIndex>,
-    pub mission_types: BTreeMap<String, BTreeMap<String, Vec<i32>>>,
-    pub missions: BTreeMap<i32, MissionRev>,
-    pub factions: BTreeMap<i32, FactionRev>,
-    pub objects: ObjectsRevData,
-    pub object_types: BTreeMap<String, Vec<i32>>,
-    pub component_use: ComponentRegistryRev,
-    pub activities: BTreeMap<i32, ActivityRev>,
-    pub loot_table_index: BTreeMap<i32, LootTableIndexRev>,
-    pub loot_matrix_index: BTreeMap<i32, LootMatrixIndexRev>,
-    pub gate_versions: GateVersionsUse,
+    db: &'static TypedDatabase<'static>,
+    /// Set while [`ReverseLookup::warm_in_background`] is running
+    warming: AtomicBool,
+    mission_task_uids: OnceCell<BTreeMap<i32, MissionTaskUIDLookup>>,
+    skill_cooldown_groups: OnceCell<BTreeMap<i32, SkillCooldownGroup>>,
+    skill_ids: OnceCell<BTreeMap<i32, SkillIdLookup>>,
+    behaviors: OnceCell<BTreeMap<i32, BehaviorKeyIndex>>,
+    mission_types: OnceCell<BTreeMap<String, BTreeMap<String, Vec<i32>>>>,
+    missions: OnceCell<BTreeMap<i32, MissionRev>>,
+    factions: OnceCell<BTreeMap<i32, FactionRev>>,
+    objects: OnceCell<ObjectsRevData>,
+    object_types: OnceCell<BTreeMap<String, Vec<i32>>>,
+    component_use: OnceCell<ComponentRegistryRev>,
+    object_components: OnceCell<BTreeMap<i32, ObjectComponents>>,
+    activities: OnceCell<BTreeMap<i32, ActivityRev>>,
+    loot_table_index: OnceCell<BTreeMap<i32, LootTableIndexRev>>,
+    loot_matrix_index: OnceCell<BTreeMap<i32, LootMatrixIndexRev>>,
+    gate_versions: OnceCell<GateVersionsUse>,
+    preconditions: OnceCell<BTreeMap<i32, PreconditionRev>>,
+    emotes: OnceCell<BTreeMap<i32, EmoteRev>>,
 }
 
 impl ReverseLookup {
-    pub fn new(db: &'_ TypedDatabase<'_>) -> Self {
+    /// Create a reverse lookup and build all indices eagerly
+    pub fn new(db: &'static TypedDatabase<'static>) -> Self {
+        let this = Self::lazy(db);
+        this.warm();
+        this
+    }
+
+    /// Create a reverse lookup that builds every index on first access
+    pub fn lazy(db: &'static TypedDatabase<'static>) -> Self {
+        Self {
+            db,
+            warming: AtomicBool::new(false),
+            mission_task_uids: OnceCell::new(),
+            skill_cooldown_groups: OnceCell::new(),
+            skill_ids: OnceCell::new(),
+            behaviors: OnceCell::new(),
+            mission_types: OnceCell::new(),
+            missions: OnceCell::new(),
+            factions: OnceCell::new(),
+            objects: OnceCell::new(),
+            object_types: OnceCell::new(),
+            component_use: OnceCell::new(),
+            object_components: OnceCell::new(),
+            activities: OnceCell::new(),
+            loot_table_index: OnceCell::new(),
+            loot_matrix_index: OnceCell::new(),
+            gate_versions: OnceCell::new(),
+            preconditions: OnceCell::new(),
+            emotes: OnceCell::new(),
+        }
+    }
+
+    /// Build all indices on a new thread, see [`ReverseLookup::is_ready`]
+    ///
+    /// The thread keeps `owner` alive until it is done.
+    pub fn warm_in_background<O>(owner: Arc<O>)
+    where
+        O: AsRef<Self> + Send + Sync + 'static,
+    {
+        O::as_ref(&owner).warming.store(true, Ordering::Release);
+        thread::spawn(move || {
+            let this = O::as_ref(&owner);
+            let result = panic::catch_unwind(AssertUnwindSafe(|| this.warm()));
+            this.warming.store(false, Ordering::Release);
+            if let Err(e) = result {
+                panic::resume_unwind(e);
+            }
+        });
+    }
+
+    /// Whether requests can be answered without waiting for a background build
+    pub fn is_ready(&self) -> bool {
+        !self.warming.load(Ordering::Acquire)
+    }
+
+    /// Whether a single index has been built
+    pub fn is_built(&self, index: Index) -> bool {
+        match index {
+            Index::MissionTaskUids => self.mission_task_uids.get().is_some(),
+            Index::SkillCooldownGroups => self.skill_cooldown_groups.get().is_some(),
+            Index::SkillIds => self.skill_ids.get().is_some(),
+            Index::Behaviors => self.behaviors.get().is_some(),
+            Index::MissionTypes => self.mission_types.get().is_some(),
+            Index::Missions => self.missions.get().is_some(),
+            Index::Factions => self.factions.get().is_some(),
+            Index::Objects => self.objects.get().is_some(),
+            Index::ObjectTypes => self.object_types.get().is_some(),
+            Index::ComponentUse => self.component_use.get().is_some(),
+            Index::ObjectComponents => self.object_components.get().is_some(),
+            Index::Activities => self.activities.get().is_some(),
+            Index::LootTableIndex => self.loot_table_index.get().is_some(),
+            Index::LootMatrixIndex => self.loot_matrix_index.get().is_some(),
+            Index::GateVersions => self.gate_versions.get().is_some(),
+            Index::Preconditions => self.preconditions.get().is_some(),
+            Index::Emotes => self.emotes.get().is_some(),
+        }
+    }
+
+    /// Whether a request that uses `indices` can be answered without waiting for a background build
+    pub fn can_answer(&self, indices: &[Index]) -> bool {
+        self.is_ready() || indices.iter().all(|&index| self.is_built(index))
+    }
+
+    /// Build all indices that have not been built yet
+    pub fn warm(&self) {
         let time = Instant::now();
         info!("Starting to load ReverseLookup");
-        let mut skill_ids: HashMap<i32, SkillIdLookup> = HashMap::new();
-        let mut skill_cooldown_groups = BTreeMap::<i32, SkillCooldownGroup>::new();
-        let mut mission_task_uids = HashMap::new();
-        let mut mission_types: BTreeMap<String, BTreeMap<String, Vec<i32>>> = BTreeMap::new();
-        let mut gate_versions = GateVersionsUse::default();
-        let mut loot_table_index: BTreeMap<i32, LootTableIndexRev> = BTreeMap::new();
-        let mut loot_matrix_index: BTreeMap<i32, LootMatrixIndexRev> = BTreeMap::new();
-        let mut objects = ObjectsRevData::default();
-        let mut missions = BTreeMap::<i32, MissionRev>::new();
-
-        let activities_has_gate_version = db
-            .activities
-            .get_col(ActivitiesColumn::GateVersion)
-            .is_some();
-        if activities_has_gate_version {
-            // TODO: if we add more revs here, move the if into the loop
-            for a in db.activities.row_iter() {
-                let id = a.activity_id();
-                if let Some(gate) = a.gate_version() {
-                    gate_versions.get_or_default(gate).activities.insert(id);
+
+        // Every index is built from its own pass(es) over the tables, so they can run concurrently
+        thread::scope(|s| {
+            s.spawn(|| self.mission_task_uids());
+            s.spawn(|| self.skill_cooldown_groups());
+            s.spawn(|| self.skill_ids());
+            s.spawn(|| self.behaviors());
+            s.spawn(|| self.mission_types());
+            s.spawn(|| self.missions());
+            s.spawn(|| self.factions());
+            s.spawn(|| self.objects());
+            s.spawn(|| self.object_types());
+            s.spawn(|| self.component_use());
+            s.spawn(|| self.object_components());
+            s.spawn(|| self.activities());
+            s.spawn(|| self.loot_table_index());
+            s.spawn(|| self.loot_matrix_index());
+            s.spawn(|| self.gate_versions());
+            s.spawn(|| self.preconditions());
+            s.spawn(|| self.emotes());
+        });
+
+        let duration = time.elapsed();
+        info!("Done loading ReverseLookup ({}ms)", duration.as_millis());
+    }
+
+    pub fn mission_task_uids(&self) -> &BTreeMap<i32, MissionTaskUIDLookup> {
+        get_or_build(
+            &self.mission_task_uids,
+            "mission_task_uids",
+            self.db,
+            build_mission_task_uids,
+        )
+    }
+
+    pub fn skill_cooldown_groups(&self) -> &BTreeMap<i32, SkillCooldownGroup> {
+        get_or_build(
+            &self.skill_cooldown_groups,
+            "skill_cooldown_groups",
+            self.db,
+            build_skill_cooldown_groups,
+        )
+    }
+
+    pub fn skill_ids(&self) -> &BTreeMap<i32, SkillIdLookup> {
+        get_or_build(&self.skill_ids, "skill_ids", self.db, build_skill_ids)
+    }
+
+    pub fn behaviors(&self) -> &BTreeMap<i32, BehaviorKeyIndex> {
+        get_or_build(&self.behaviors, "behaviors", self.db, build_behaviors)
+    }
+
+    pub fn mission_types(&self) -> &BTreeMap<String, BTreeMap<String, Vec<i32>>> {
+        get_or_build(
+            &self.mission_types,
+            "mission_types",
+            self.db,
+            build_mission_types,
+        )
+    }
+
+    pub fn missions(&self) -> &BTreeMap<i32, MissionRev> {
+        get_or_build(&self.missions, "missions", self.db, build_missions)
+    }
+
+    pub fn factions(&self) -> &BTreeMap<i32, FactionRev> {
+        get_or_build(&self.factions, "factions", self.db, build_factions)
+    }
+
+    pub fn objects(&self) -> &ObjectsRevData {
+        get_or_build(&self.objects, "objects", self.db, build_objects)
+    }
+
+    pub fn object_types(&self) -> &BTreeMap<String, Vec<i32>> {
+        get_or_build(
+            &self.object_types,
+            "object_types",
+            self.db,
+            build_object_types,
+        )
+    }
+
+    pub fn component_use(&self) -> &ComponentRegistryRev {
+        get_or_build(
+            &self.component_use,
+            "component_use",
+            self.db,
+            build_component_use,
+        )
+    }
+
+    /// The components of every object, from the `ComponentsRegistry`
+    pub fn object_components(&self) -> &BTreeMap<i32, ObjectComponents> {
+        get_or_build(
+            &self.object_components,
+            "object_components",
+            self.db,
+            build_object_components,
+        )
+    }
+
+    pub fn activities(&self) -> &BTreeMap<i32, ActivityRev> {
+        get_or_build(&self.activities, "activities", self.db, build_activities)
+    }
+
+    pub fn loot_table_index(&self) -> &BTreeMap<i32, LootTableIndexRev> {
+        get_or_build(
+            &self.loot_table_index,
+            "loot_table_index",
+            self.db,
+            build_loot_table_index,
+        )
+    }
+
+    pub fn loot_matrix_index(&self) -> &BTreeMap<i32, LootMatrixIndexRev> {
+        get_or_build(
+            &self.loot_matrix_index,
+            "loot_matrix_index",
+            self.db,
+            build_loot_matrix_index,
+        )
+    }
+
+    pub fn gate_versions(&self) -> &GateVersionsUse {
+        get_or_build(
+            &self.gate_versions,
+            "gate_versions",
+            self.db,
+            build_gate_versions,
+        )
+    }
+
+    pub fn preconditions(&self) -> &BTreeMap<i32, PreconditionRev> {
+        get_or_build(
+            &self.preconditions,
+            "preconditions",
+            self.db,
+            build_preconditions,
+        )
+    }
+
+    pub fn emotes(&self) -> &BTreeMap<i32, EmoteRev> {
+        get_or_build(&self.emotes, "emotes", self.db, build_emotes)
+    }
+
+    pub(crate) fn get_behavior_set(&self, root: i32) -> BTreeSet<i32> {
+        let mut todo = Vec::new();
+        let mut all = BTreeSet::new();
+        todo.push(root);
+
+        while let Some(next) = todo.pop() {
+            if !all.contains(&next) {
+                all.insert(next);
+                if let Some(data) = self.behaviors().get(&next) {
+                    todo.extend(data.uses.iter().filter(|&&x| x > 0));
                 }
             }
         }
+        all
+    }
+}
+
+fn get_or_build<'r, T>(
+    cell: &'r OnceCell<T>,
+    name: &str,
+    db: &TypedDatabase<'_>,
+    build: fn(&TypedDatabase<'_>) -> T,
+) -> &'r T {
+    cell.get_or_init(|| {
+        let time = Instant::now();
+        let value = build(db);
+        debug!(
+            "Built ReverseLookup index {} ({}ms)",
+            name,
+            time.elapsed().as_millis()
+        );
+        value
+    })
+}
 
-        if let Some(activity_rewards) = &db.activity_rewards {
-            for row in activity_rewards.row_iter() {
-                if let Some(lmi) = row.loot_matrix_index() {
-                    loot_matrix_index
-                        .entry(lmi)
+fn build_skill_ids(db: &TypedDatabase<'_>) -> BTreeMap<i32, SkillIdLookup> {
+    let mut skill_ids: BTreeMap<i32, SkillIdLookup> = BTreeMap::new();
+    for s in db.item_set_skills.row_iter() {
+        skill_ids
+            .entry(s.skill_id())
+            .or_default()
+            .item_sets
+            .push(s.skill_set_id());
+    }
+
+    for r in db.mission_tasks.row_iter() {
+        if r.task_type() == 10 {
+            if let Some(p) = r.task_param1() {
+                for num in p.decode().split(',').map(str::parse).filter_map(Result::ok) {
+                    skill_ids
+                        .entry(num)
                         .or_default()
-                        .activity_rewards
-                        .insert(row.activity_reward_index(), row.object_template());
+                        .mission_tasks
+                        .push(r.uid());
                 }
             }
         }
+    }
 
-        let mut behaviors: BTreeMap<i32, BehaviorKeyIndex> = BTreeMap::new();
-        for bp in db.behavior_parameters.row_iter() {
-            let parameter_id = bp.parameter_id();
-            let behavior_id = bp.behavior_id();
-            if match_action_key(parameter_id) {
-                let value = bp.value() as i32;
-                behaviors.entry(behavior_id).or_default().uses.insert(value);
-                behaviors
-                    .entry(value)
-                    .or_default()
-                    .used_by
-                    .insert(behavior_id);
+    for s in db.object_skills.row_iter() {
+        skill_ids
+            .entry(s.skill_id())
+            .or_default()
+            .objects
+            .push(s.object_template());
+    }
+    skill_ids
+}
+
+fn build_skill_cooldown_groups(db: &TypedDatabase<'_>) -> BTreeMap<i32, SkillCooldownGroup> {
+    let mut skill_cooldown_groups = BTreeMap::<i32, SkillCooldownGroup>::new();
+    let mut cooldowns = BTreeMap::<i32, Vec<f64>>::new();
+    for skill in db.skills.row_iter() {
+        if let Some(cooldowngroup) = skill.cooldowngroup() {
+            skill_cooldown_groups
+                .entry(cooldowngroup)
+                .or_default()
+                .skills
+                .insert(skill.skill_id());
+            if let Some(cooldown) = skill.cooldown() {
+                let values = cooldowns.entry(cooldowngroup).or_default();
+                values.push(f64::from(cooldown));
             }
         }
+    }
+    for (id, values) in cooldowns {
+        if let Some(group) = skill_cooldown_groups.get_mut(&id) {
+            group.cooldown = CooldownStats::new(values);
+        }
+    }
+    skill_cooldown_groups
+}
 
-        for collectible in db.collectible_component.row_iter() {
-            if let Some(mission_id) = collectible.requirement_mission() {
-                missions
-                    .entry(mission_id)
-                    .or_default()
-                    .collectible_components
-                    .requirement_for
-                    .insert(collectible.id());
+/// Get the precondition IDs in a precondition expression such as `"12;15|16"`
+fn precondition_ids(text: &str) -> impl Iterator<Item = i32> + '_ {
+    text.split(|c: char| !c.is_ascii_digit())
+        .filter_map(|id| id.parse().ok())
+}
+
+fn build_emotes(db: &TypedDatabase<'_>) -> BTreeMap<i32, EmoteRev> {
+    let mut emotes = BTreeMap::<i32, EmoteRev>::new();
+    for m in db.missions.row_iter() {
+        let id = m.id();
+        for emote_id in [
+            m.reward_emote(),
+            m.reward_emote2(),
+            m.reward_emote3(),
+            m.reward_emote4(),
+        ] {
+            if emote_id > 0 {
+                let entry = emotes.entry(emote_id).or_default();
+                entry.missions.reward_emote.insert(id);
             }
         }
+    }
+    emotes
+}
 
-        let mut component_use = ComponentRegistryRev::default();
-        for creg in db.comp_reg.row_iter() {
-            let id = creg.id();
-            let ty = creg.component_type();
-            let cid = creg.component_id();
-            let ty_entry = component_use.ty_mut(ty);
-            let co_entry = ty_entry.components.entry(cid).or_default();
-            co_entry.lots.push(id);
+fn build_preconditions(db: &TypedDatabase<'_>) -> BTreeMap<i32, PreconditionRev> {
+    let mut preconditions = BTreeMap::<i32, PreconditionRev>::new();
+    for row in db.item_component.row_iter() {
+        if let Some(text) = row.req_precondition() {
+            for id in precondition_ids(text.decode().as_ref()) {
+                let entry = preconditions.entry(id).or_default();
+                entry.item_components.req_precondition.insert(row.id());
+            }
         }
+    }
+    preconditions
+}
+
+fn build_mission_task_uids(db: &TypedDatabase<'_>) -> BTreeMap<i32, MissionTaskUIDLookup> {
+    let mut mission_task_uids = BTreeMap::new();
+    for r in db.mission_tasks.row_iter() {
+        mission_task_uids.insert(r.uid(), MissionTaskUIDLookup { mission: r.id() });
+    }
+    mission_task_uids
+}
 
-        for row in db.currency_denominations.row_iter() {
-            objects
-                .rev
-                .entry(row.objectid())
+fn build_behaviors(db: &TypedDatabase<'_>) -> BTreeMap<i32, BehaviorKeyIndex> {
+    let mut behaviors: BTreeMap<i32, BehaviorKeyIndex> = BTreeMap::new();
+    let behavior_types: HashMap<i32, i32> = db
+        .behavior_templates
+        .row_iter()
+        .map(|bt| (bt.behavior_id(), bt.template_id()))
+        .collect();
+    for bp in db.behavior_parameters.row_iter() {
+        let parameter_id = bp.parameter_id();
+        let behavior_id = bp.behavior_id();
+        let behavior_type = behavior_types.get(&behavior_id).copied();
+        if is_behavior_edge(behavior_type, parameter_id) {
+            let value = bp.value() as i32;
+            behaviors.entry(behavior_id).or_default().uses.insert(value);
+            behaviors
+                .entry(value)
                 .or_default()
-                .currency_denomination = Some(row.value());
+                .used_by
+                .insert(behavior_id);
         }
+    }
 
-        let deletion_restrictions_has_gate_version = db
-            .deletion_restrictions
-            .get_col(DeletionRestrictionsColumn::GateVersion)
-            .is_some();
-        for row in db.deletion_restrictions.row_iter() {
-            let id = row.id();
-            if row.check_type() == 0 {
-                if let Some(ids) = row.ids() {
-                    let s = ids.decode();
-                    for id_str in s.as_ref().trim().split(',').map(str::trim) {
-                        if let Ok(lot) = id_str.parse() {
-                            objects
-                                .rev
-                                .entry(lot)
-                                .or_default()
-                                .deletion_restrictions
-                                .insert(id);
-                        }
-                    }
-                }
-            }
-            if deletion_restrictions_has_gate_version {
-                if let Some(gate) = row.gate_version() {
-                    gate_versions
-                        .get_or_default(gate)
-                        .deletion_restrictions
-                        .insert(id);
-                }
-            }
-        }
+    for skill in db.skills.row_iter() {
+        let skid = skill.skill_id();
+        let bid = skill.behavior_id();
+        behaviors.entry(bid).or_default().skill.insert(skid);
+    }
+    behaviors
+}
 
-        let mut factions: BTreeMap<i32, FactionRev> = BTreeMap::new();
-        let destructible_component_has_faction_list = db
-            .destructible_component
-            .get_col(DestructibleComponentColumn::FactionList)
-            .is_some();
-        for d in db.destructible_component.row_iter() {
-            let id = d.id();
-            if let Some(faction) = d.faction() {
-                let entry = factions.entry(faction).or_default();
-                entry.destructible.insert(id);
-            }
+fn build_mission_types(db: &TypedDatabase<'_>) -> BTreeMap<String, BTreeMap<String, Vec<i32>>> {
+    let mut mission_types: BTreeMap<String, BTreeMap<String, Vec<i32>>> = BTreeMap::new();
+    for m in db.missions.row_iter() {
+        let d_type = m.defined_type().decode().into_owned();
+        let d_subtype = m
+            .defined_subtype()
+            .map(Latin1Str::decode)
+            .unwrap_or_default()
+            .into_owned();
+        mission_types
+            .entry(d_type)
+            .or_default()
+            .entry(d_subtype)
+            .or_default()
+            .push(m.id());
+    }
+    mission_types
+}
 
-            if let Some(lmi) = d.loot_matrix_index() {
-                loot_matrix_index
-                    .entry(lmi)
-                    .or_default()
-                    .components
-                    .destructible
-                    .insert(id);
-            }
+fn build_missions(db: &TypedDatabase<'_>) -> BTreeMap<i32, MissionRev> {
+    let mut missions = BTreeMap::<i32, MissionRev>::new();
+    for collectible in db.collectible_component.row_iter() {
+        if let Some(mission_id) = collectible.requirement_mission() {
+            missions
+                .entry(mission_id)
+                .or_default()
+                .collectible_components
+                .requirement_for
+                .insert(collectible.id());
+        }
+    }
 
-            if destructible_component_has_faction_list {
-                if let Ok(faction_list) = d.faction_list().decode().parse() {
-                    if faction_list >= 0 {
-                        let entry = factions.entry(faction_list).or_default();
-                        entry.destructible_list.insert(id);
-                    }
-                }
-            }
+    for row in db.item_component.row_iter() {
+        if let Some(req_achievement_id) = row.req_achievement_id() {
+            missions
+                .entry(req_achievement_id)
+                .or_default()
+                .item_components
+                .requirement_for
+                .insert(row.id());
         }
+    }
 
-        for row in db.emotes.row_iter() {
-            let id = row.id();
-            if let Some(gate) = row.gate_version() {
-                gate_versions.get_or_default(gate).emotes.insert(id);
+    for m in db.missions.row_iter() {
+        let id = m.id();
+        if let Some(prereq) = m.prereq_mission_id() {
+            if !prereq.is_empty() {
+                let requires = parse_prereqs(&prereq.decode(), id);
+                for &prereq_id in requires.iter().flatten() {
+                    missions
+                        .entry(prereq_id)
+                        .or_default()
+                        .missions
+                        .prereq_for
+                        .insert(id);
+                }
+                if !requires.is_empty() {
+                    missions.entry(id).or_default().requires = requires;
+                }
             }
         }
+    }
+    missions
+}
 
-        for row in db.loot_matrix.row_iter() {
-            let id = row.id();
-            let loot_matrix = row.loot_matrix_index();
-            let loot_table = row.loot_table_index();
-            loot_table_index
-                .entry(loot_table)
-                .or_default()
-                .loot_matrix
-                .insert(id, loot_matrix);
-            if let Some(gate) = row.gate_version() {
-                gate_versions
-                    .get_or_default(gate)
-                    .loot_matrix
-                    .insert(id, loot_matrix);
+/// Parse a `prereq_mission_id` like `(1|2:3)&4,5` into `[[1, 2], [4], [5]]`
+///
+/// Every inner list is a set of alternatives, all of the lists must be met.
+/// The `:count` suffix is ignored.
+fn parse_prereqs(prereq: &str, id: i32) -> Vec<Vec<i32>> {
+    let mut requires = Vec::new();
+    for all_of in prereq.split(&['&', ',']).map(str::trim) {
+        let all_of = all_of.strip_prefix('(').unwrap_or(all_of);
+        let all_of = all_of.strip_suffix(')').unwrap_or(all_of);
+        let mut alternatives = Vec::new();
+        for any_of in all_of.split('|').map(str::trim) {
+            let prereq_id = any_of.split_once(':').map(|(id, _)| id).unwrap_or(any_of);
+            if let Ok(prereq_id) = prereq_id.parse::<i32>() {
+                alternatives.push(prereq_id);
+            } else {
+                log::warn!("Invalid mission id {}", id);
             }
         }
+        if !alternatives.is_empty() {
+            requires.push(alternatives);
+        }
+    }
+    requires
+}
 
-        for row in db.inventory_component.row_iter() {
-            objects.r(row.itemid()).inventory_component.insert(row.id());
+fn build_factions(db: &TypedDatabase<'_>) -> BTreeMap<i32, FactionRev> {
+    let mut factions: BTreeMap<i32, FactionRev> = BTreeMap::new();
+    let destructible_component_has_faction_list = db
+        .destructible_component
+        .get_col(DestructibleComponentColumn::FactionList)
+        .is_some();
+    for d in db.destructible_component.row_iter() {
+        let id = d.id();
+        if let Some(faction) = d.faction() {
+            let entry = factions.entry(faction).or_default();
+            entry.destructible.insert(id);
         }
 
-        let item_component_has_commendation_lot = db
-            .item_component
-            .get_col(ItemComponentColumn::CommendationLot)
-            .is_some();
-        for row in db.item_component.row_iter() {
-            let id = row.id();
-            if let Some(lot) = row.currency_lot() {
-                objects.r(lot).item_component.currency_lot.insert(id);
-            }
-            if item_component_has_commendation_lot {
-                if let Some(lot) = row.commendation_lot() {
-                    objects.r(lot).item_component.commendation_lot.insert(id);
+        if destructible_component_has_faction_list {
+            if let Ok(faction_list) = d.faction_list().decode().parse() {
+                if faction_list >= 0 {
+                    let entry = factions.entry(faction_list).or_default();
+                    entry.destructible_list.insert(id);
                 }
             }
-            if let Some(text) = row.sub_items() {
-                for lot in text
-                    .decode()
-                    .trim()
-                    .split(',')
-                    .map(str::trim)
-                    .map(FromStr::from_str)
-                    .filter_map(Result::ok)
-                {
-                    objects.r(lot).item_component.subitems.insert(id);
+        }
+    }
+    factions
+}
+
+fn build_objects(db: &TypedDatabase<'_>) -> ObjectsRevData {
+    let mut objects = ObjectsRevData::default();
+    for row in db.currency_denominations.row_iter() {
+        objects
+            .rev
+            .entry(row.objectid())
+            .or_default()
+            .currency_denomination = Some(row.value());
+    }
+
+    for row in db.deletion_restrictions.row_iter() {
+        let id = row.id();
+        if row.check_type() == 0 {
+            if let Some(ids) = row.ids() {
+                let s = ids.decode();
+                for id_str in s.as_ref().trim().split(',').map(str::trim) {
+                    if let Ok(lot) = id_str.parse() {
+                        objects
+                            .rev
+                            .entry(lot)
+                            .or_default()
+                            .deletion_restrictions
+                            .insert(id);
+                    }
                 }
             }
-            if let Some(req_achievement_id) = row.req_achievement_id() {
-                missions
-                    .entry(req_achievement_id)
-                    .or_default()
-                    .item_components
-                    .requirement_for
-                    .insert(id);
-            }
         }
+    }
 
-        for s in db.item_set_skills.row_iter() {
-            skill_ids
-                .entry(s.skill_id())
-                .or_default()
-                .item_sets
-                .push(s.skill_set_id());
-        }
+    for row in db.inventory_component.row_iter() {
+        objects.r(row.itemid()).inventory_component.insert(row.id());
+    }
 
-        for item_set in db.item_sets.row_iter() {
-            let set_id = item_set.set_id();
-            if let Some(gate_version) = item_set.gate_version() {
-                gate_versions
-                    .get_or_default(gate_version)
-                    .item_sets
-                    .insert(set_id);
-            }
+    for row in db.object_skills.row_iter() {
+        objects.r(row.object_template()).skills.insert(
+            row.skill_id(),
+            ObjectSkillUse {
+                cast_on_type: row.cast_on_type(),
+                ai_combat_weight: row.a_i_combat_weight(),
+            },
+        );
+    }
 
-            for lot in item_set
-                .item_i_ds()
+    let item_component_has_commendation_lot = db
+        .item_component
+        .get_col(ItemComponentColumn::CommendationLot)
+        .is_some();
+    for row in db.item_component.row_iter() {
+        let id = row.id();
+        if let Some(lot) = row.currency_lot() {
+            objects.r(lot).item_component.currency_lot.insert(id);
+        }
+        if item_component_has_commendation_lot {
+            if let Some(lot) = row.commendation_lot() {
+                objects.r(lot).item_component.commendation_lot.insert(id);
+            }
+        }
+        if let Some(text) = row.sub_items() {
+            for lot in text
                 .decode()
                 .trim()
                 .split(',')
@@ -529,386 +1033,496 @@ impl ReverseLookup {
                 .map(FromStr::from_str)
                 .filter_map(Result::ok)
             {
-                objects.r(lot).item_sets.insert(set_id);
-            }
-        }
-
-        if let Some(jet_pack_pad_component) = &db.jet_pack_pad_component {
-            for row in jet_pack_pad_component.row_iter() {
-                let id = row.id();
-                if let Some(lot) = row.lot_warning_volume() {
-                    objects
-                        .r(lot)
-                        .jet_pack_pad_component
-                        .lot_warning_volume
-                        .insert(id);
-                }
-                if let Some(lot) = row.lot_blocker() {
-                    objects.r(lot).jet_pack_pad_component.lot_blocker.insert(id);
-                }
+                objects.r(lot).item_component.subitems.insert(id);
             }
         }
+    }
 
-        for l in db.loot_table.row_iter() {
-            let lti = l.loot_table_index();
-            let itemid = l.itemid();
-            let id = l.id();
-            let entry = loot_table_index.entry(lti).or_default();
-            entry.items.insert(id, itemid);
-            objects.r(itemid).loot_table_index.insert(lti);
+    for item_set in db.item_sets.row_iter() {
+        let set_id = item_set.set_id();
+        for lot in item_set
+            .item_i_ds()
+            .decode()
+            .trim()
+            .split(',')
+            .map(str::trim)
+            .map(FromStr::from_str)
+            .filter_map(Result::ok)
+        {
+            objects.r(lot).item_sets.insert(set_id);
         }
+    }
 
-        for m in db.missions.row_iter() {
-            let id = m.id();
-            let d_type = m.defined_type().decode().into_owned();
-            let d_subtype = m
-                .defined_subtype()
-                .map(Latin1Str::decode)
-                .unwrap_or_default()
-                .into_owned();
-            mission_types
-                .entry(d_type)
-                .or_default()
-                .entry(d_subtype)
-                .or_default()
-                .push(id);
-
-            if let Some(gate_version) = m.gate_version() {
-                gate_versions
-                    .get_or_default(gate_version)
-                    .missions
+    if let Some(jet_pack_pad_component) = &db.jet_pack_pad_component {
+        for row in jet_pack_pad_component.row_iter() {
+            let id = row.id();
+            if let Some(lot) = row.lot_warning_volume() {
+                objects
+                    .r(lot)
+                    .jet_pack_pad_component
+                    .lot_warning_volume
                     .insert(id);
             }
-
-            if let Some(prereq) = m.prereq_mission_id() {
-                if !prereq.is_empty() {
-                    let decoded = prereq.decode();
-                    for all_of in decoded.split(&['&', ',']).map(str::trim) {
-                        let all_of = all_of.strip_prefix('(').unwrap_or(all_of);
-                        let all_of = all_of.strip_suffix(')').unwrap_or(all_of);
-                        for any_of in all_of.split('|').map(str::trim) {
-                            let prereq_id =
-                                any_of.split_once(':').map(|(id, _)| id).unwrap_or(any_of);
-                            if let Ok(prereq_id) = prereq_id.parse::<i32>() {
-                                missions
-                                    .entry(prereq_id)
-                                    .or_default()
-                                    .missions
-                                    .prereq_for
-                                    .insert(id);
-                            } else {
-                                log::warn!("Invalid mission id {}", id);
-                            }
-                        }
-                    }
-                }
+            if let Some(lot) = row.lot_blocker() {
+                objects.r(lot).jet_pack_pad_component.lot_blocker.insert(id);
             }
+        }
+    }
 
-            for lot in [
-                m.reward_item1(),
-                m.reward_item2(),
-                m.reward_item3(),
-                m.reward_item4(),
-                m.reward_item1_repeatable(),
-                m.reward_item2_repeatable(),
-                m.reward_item3_repeatable(),
-                m.reward_item4_repeatable(),
-            ] {
-                if lot > 0 {
-                    objects.r(lot).missions.reward_items.insert(id);
-                }
-            }
+    let mut loot_matrix_by_table = BTreeMap::<i32, BTreeSet<i32>>::new();
+    for row in db.loot_matrix.row_iter() {
+        loot_matrix_by_table
+            .entry(row.loot_table_index())
+            .or_default()
+            .insert(row.loot_matrix_index());
+    }
+
+    for l in db.loot_table.row_iter() {
+        let lti = l.loot_table_index();
+        let entry = objects.r(l.itemid());
+        entry.loot_table_index.insert(lti);
+        if let Some(lmis) = loot_matrix_by_table.get(&lti) {
+            entry.dropped_by_loot_matrix.extend(lmis);
         }
+    }
 
-        let mission_tasks_has_gate_version = db
-            .mission_tasks
-            .get_col(MissionTasksColumn::GateVersion)
-            .is_some();
-        for r in db.mission_tasks.row_iter() {
-            let uid = r.uid();
-            let id = r.id();
-            mission_task_uids.insert(uid, MissionTaskUIDLookup { mission: id });
-
-            if r.task_type() == 10 {
-                if let Some(p) = r.task_param1() {
-                    for num in p.decode().split(',').map(str::parse).filter_map(Result::ok) {
-                        skill_ids.entry(num).or_default().mission_tasks.push(uid);
-                    }
-                }
+    for m in db.missions.row_iter() {
+        let id = m.id();
+        for lot in [
+            m.reward_item1(),
+            m.reward_item2(),
+            m.reward_item3(),
+            m.reward_item4(),
+            m.reward_item1_repeatable(),
+            m.reward_item2_repeatable(),
+            m.reward_item3_repeatable(),
+            m.reward_item4_repeatable(),
+        ] {
+            if lot > 0 {
+                objects.r(lot).missions.reward_items.insert(id);
             }
+        }
+    }
 
-            if mission_tasks_has_gate_version {
-                if let Some(gate_version) = r.gate_version() {
-                    gate_versions
-                        .get_or_default(gate_version)
-                        .mission_tasks
-                        .insert(uid);
-                }
+    for row in db.npc_icons.row_iter() {
+        let id = row.id();
+        let lot = row.lot();
+        objects.r(lot).npc_icons_lot.insert(id);
+    }
+
+    let objects_has_internal_notes = db.objects.get_col(ObjectsColumn::InternalNotes).is_some();
+    for o in db.objects.row_iter() {
+        let id = o.id();
+        let name = o.name().decode().into_owned();
+        let description = o.description().map(Latin1Str::decode).map(Cow::into_owned);
+        let display_name = o.display_name().map(Latin1Str::decode).map(Cow::into_owned);
+        let internal_notes = if objects_has_internal_notes {
+            o.internal_notes()
+                .map(Latin1Str::decode)
+                .map(Cow::into_owned)
+        } else {
+            None
+        };
+
+        objects.search_index.insert(
+            id,
+            ObjectStrings {
+                n: name,
+                d: description,
+                i: display_name,
+                t: internal_notes,
+            },
+        );
+    }
+
+    if let Some(rebuild_sections) = &db.rebuild_sections {
+        for row in rebuild_sections.row_iter() {
+            let id = row.id();
+            let lot = row.object_id();
+            objects.r(lot).rebuild_sections.insert(id);
+        }
+    }
+
+    if let Some(reward_codes) = &db.reward_codes {
+        for row in reward_codes.row_iter() {
+            if let Some(lot) = row.attachment_lot() {
+                objects.r(lot).reward_codes.insert(row.id());
             }
-            //skill_ids.entry(r.uid()).or_default().mission_tasks.push(r
         }
+    }
+    objects
+}
 
-        for row in db.npc_icons.row_iter() {
-            let id = row.id();
-            let lot = row.lot();
-            objects.r(lot).npc_icons_lot.insert(id);
+fn build_object_types(db: &TypedDatabase<'_>) -> BTreeMap<String, Vec<i32>> {
+    let mut object_types = BTreeMap::<_, Vec<_>>::new();
+    for o in db.objects.row_iter() {
+        let ty = o.r#type().decode().into_owned();
+        object_types.entry(ty).or_default().push(o.id());
+    }
+    object_types
+}
+
+fn build_component_use(db: &TypedDatabase<'_>) -> ComponentRegistryRev {
+    let mut component_use = ComponentRegistryRev::default();
+    for creg in db.comp_reg.row_iter() {
+        let id = creg.id();
+        let ty = creg.component_type();
+        let cid = creg.component_id();
+        let ty_entry = component_use.ty_mut(ty);
+        let co_entry = ty_entry.components.entry(cid).or_default();
+        co_entry.lots.push(id);
+    }
+    component_use
+}
+
+fn build_object_components(db: &TypedDatabase<'_>) -> BTreeMap<i32, ObjectComponents> {
+    let mut object_components = BTreeMap::<_, ObjectComponents>::new();
+    for creg in db.comp_reg.row_iter() {
+        object_components
+            .entry(creg.id())
+            .or_default()
+            .entry(creg.component_type())
+            .or_default()
+            .push(creg.component_id());
+    }
+    object_components
+}
+
+fn build_activities(db: &TypedDatabase<'_>) -> BTreeMap<i32, ActivityRev> {
+    let mut activities: BTreeMap<i32, ActivityRev> = BTreeMap::new();
+    for r in db.rebuild_component.row_iter() {
+        let id = r.id();
+        if let Some(aid) = r.activity_id() {
+            let entry = activities.entry(aid).or_default();
+            entry.rebuild.push(id);
         }
+    }
 
-        for s in db.object_skills.row_iter() {
-            skill_ids
-                .entry(s.skill_id())
+    if let Some(activity_rewards) = &db.activity_rewards {
+        for row in activity_rewards.row_iter() {
+            let aid = row.object_template();
+            activities
+                .entry(aid)
                 .or_default()
-                .objects
-                .push(s.object_template());
+                .activity_rewards
+                .insert(row.activity_reward_index(), aid);
         }
+    }
+    activities
+}
 
-        let objects_has_internal_notes = db.objects.get_col(ObjectsColumn::InternalNotes).is_some();
-        let objects_has_gate_version = db.objects.get_col(ObjectsColumn::GateVersion).is_some();
-        let mut object_types = BTreeMap::<_, Vec<_>>::new();
-        for o in db.objects.row_iter() {
-            let id = o.id();
-            let ty = o.r#type().decode().into_owned();
-
-            let entry = object_types.entry(ty).or_default();
-            entry.push(id);
-
-            let name = o.name().decode().into_owned();
-            let description = o.description().map(Latin1Str::decode).map(Cow::into_owned);
-            let display_name = o.display_name().map(Latin1Str::decode).map(Cow::into_owned);
-            let internal_notes = if objects_has_internal_notes {
-                o.internal_notes()
-                    .map(Latin1Str::decode)
-                    .map(Cow::into_owned)
-            } else {
-                None
-            };
-
-            objects.search_index.insert(
-                id,
-                ObjectStrings {
-                    n: name,
-                    d: description,
-                    i: display_name,
-                    t: internal_notes,
-                },
-            );
-
-            if objects_has_gate_version {
-                if let Some(gate_version) = o.gate_version() {
-                    gate_versions
-                        .get_or_default(gate_version)
-                        .objects
-                        .insert(id);
-                }
-            }
-        }
+fn build_loot_table_index(db: &TypedDatabase<'_>) -> BTreeMap<i32, LootTableIndexRev> {
+    let mut loot_table_index: BTreeMap<i32, LootTableIndexRev> = BTreeMap::new();
+    for row in db.loot_matrix.row_iter() {
+        loot_table_index
+            .entry(row.loot_table_index())
+            .or_default()
+            .loot_matrix
+            .insert(row.id(), row.loot_matrix_index());
+    }
 
-        if let Some(package_component) = &db.package_component {
-            for row in package_component.row_iter() {
+    for l in db.loot_table.row_iter() {
+        loot_table_index
+            .entry(l.loot_table_index())
+            .or_default()
+            .items
+            .insert(l.id(), l.itemid());
+    }
+    loot_table_index
+}
+
+fn build_loot_matrix_index(db: &TypedDatabase<'_>) -> BTreeMap<i32, LootMatrixIndexRev> {
+    let mut loot_matrix_index: BTreeMap<i32, LootMatrixIndexRev> = BTreeMap::new();
+    if let Some(activity_rewards) = &db.activity_rewards {
+        for row in activity_rewards.row_iter() {
+            if let Some(lmi) = row.loot_matrix_index() {
                 loot_matrix_index
-                    .entry(row.loot_matrix_index())
+                    .entry(lmi)
                     .or_default()
-                    .components
-                    .package
-                    .insert(row.id());
+                    .activity_rewards
+                    .insert(row.activity_reward_index(), row.object_template());
             }
         }
+    }
 
-        if let Some(player_statistics) = &db.player_statistics {
-            for row in player_statistics.row_iter() {
-                let id = row.stat_id();
-                if let Some(gate) = row.gate_version() {
-                    gate_versions
-                        .get_or_default(gate)
-                        .player_statistics
-                        .insert(id);
-                }
-            }
+    for d in db.destructible_component.row_iter() {
+        if let Some(lmi) = d.loot_matrix_index() {
+            loot_matrix_index
+                .entry(lmi)
+                .or_default()
+                .components
+                .destructible
+                .insert(d.id());
         }
+    }
 
-        for row in db.preconditions.row_iter() {
-            let id = row.id();
-            if let Some(gate) = row.gate_version() {
-                gate_versions.get_or_default(gate).preconditions.insert(id);
+    if let Some(package_component) = &db.package_component {
+        for row in package_component.row_iter() {
+            loot_matrix_index
+                .entry(row.loot_matrix_index())
+                .or_default()
+                .components
+                .package
+                .insert(row.id());
+        }
+    }
+
+    if let Some(smashable_component) = &db.smashable_component {
+        for row in smashable_component.row_iter() {
+            loot_matrix_index
+                .entry(row.loot_matrix_index())
+                .or_default()
+                .components
+                .smashable
+                .insert(row.id());
+        }
+    }
+
+    if let Some(vendor_component) = &db.vendor_component {
+        for row in vendor_component.row_iter() {
+            loot_matrix_index
+                .entry(row.loot_matrix_index())
+                .or_default()
+                .components
+                .vendor
+                .insert(row.id());
+        }
+    }
+    loot_matrix_index
+}
+
+fn build_gate_versions(db: &TypedDatabase<'_>) -> GateVersionsUse {
+    let mut gate_versions = GateVersionsUse::default();
+    let activities_has_gate_version = db
+        .activities
+        .get_col(ActivitiesColumn::GateVersion)
+        .is_some();
+    if activities_has_gate_version {
+        for a in db.activities.row_iter() {
+            let id = a.activity_id();
+            if let Some(gate) = a.gate_version() {
+                gate_versions.get_or_default(gate).activities.insert(id);
             }
         }
+    }
 
-        for row in db.property_template.row_iter() {
-            let id = row.id();
+    let deletion_restrictions_has_gate_version = db
+        .deletion_restrictions
+        .get_col(DeletionRestrictionsColumn::GateVersion)
+        .is_some();
+    if deletion_restrictions_has_gate_version {
+        for row in db.deletion_restrictions.row_iter() {
             if let Some(gate) = row.gate_version() {
                 gate_versions
                     .get_or_default(gate)
-                    .property_template
-                    .insert(id);
+                    .deletion_restrictions
+                    .insert(row.id());
             }
         }
+    }
 
-        let mut activities: BTreeMap<i32, ActivityRev> = BTreeMap::new();
-        for r in db.rebuild_component.row_iter() {
-            let id = r.id();
-            if let Some(aid) = r.activity_id() {
-                let entry = activities.entry(aid).or_default();
-                entry.rebuild.push(id);
-            }
+    for row in db.emotes.row_iter() {
+        let id = row.id();
+        if let Some(gate) = row.gate_version() {
+            gate_versions.get_or_default(gate).emotes.insert(id);
         }
+    }
 
-        if let Some(rebuild_sections) = &db.rebuild_sections {
-            for row in rebuild_sections.row_iter() {
-                let id = row.id();
-                let lot = row.object_id();
-                objects.r(lot).rebuild_sections.insert(id);
-            }
+    for row in db.loot_matrix.row_iter() {
+        if let Some(gate) = row.gate_version() {
+            gate_versions
+                .get_or_default(gate)
+                .loot_matrix
+                .insert(row.id(), row.loot_matrix_index());
         }
+    }
 
-        if let Some(reward_codes) = &db.reward_codes {
-            for row in reward_codes.row_iter() {
-                let id = row.id();
-                if let Some(gate) = row.gate_version() {
-                    gate_versions.get_or_default(gate).reward_codes.insert(id);
-                }
-                if let Some(lot) = row.attachment_lot() {
-                    objects.r(lot).reward_codes.insert(id);
-                }
-            }
+    for item_set in db.item_sets.row_iter() {
+        if let Some(gate_version) = item_set.gate_version() {
+            gate_versions
+                .get_or_default(gate_version)
+                .item_sets
+                .insert(item_set.set_id());
         }
+    }
 
-        for skill in db.skills.row_iter() {
-            let skid = skill.skill_id();
-            let bid = skill.behavior_id();
-            behaviors.entry(bid).or_default().skill.insert(skid);
+    for m in db.missions.row_iter() {
+        if let Some(gate_version) = m.gate_version() {
+            gate_versions
+                .get_or_default(gate_version)
+                .missions
+                .insert(m.id());
+        }
+    }
 
-            if let Some(gate_version) = skill.gate_version() {
+    let mission_tasks_has_gate_version = db
+        .mission_tasks
+        .get_col(MissionTasksColumn::GateVersion)
+        .is_some();
+    if mission_tasks_has_gate_version {
+        for r in db.mission_tasks.row_iter() {
+            if let Some(gate_version) = r.gate_version() {
                 gate_versions
                     .get_or_default(gate_version)
-                    .skills
-                    .insert(skid);
-            }
-
-            if let Some(cooldowngroup) = skill.cooldowngroup() {
-                skill_cooldown_groups
-                    .entry(cooldowngroup)
-                    .or_default()
-                    .skills
-                    .insert(skid);
+                    .mission_tasks
+                    .insert(r.uid());
             }
         }
+    }
 
-        if let Some(smashable_component) = &db.smashable_component {
-            for row in smashable_component.row_iter() {
-                loot_matrix_index
-                    .entry(row.loot_matrix_index())
-                    .or_default()
-                    .components
-                    .smashable
-                    .insert(row.id());
+    let objects_has_gate_version = db.objects.get_col(ObjectsColumn::GateVersion).is_some();
+    if objects_has_gate_version {
+        for o in db.objects.row_iter() {
+            if let Some(gate_version) = o.gate_version() {
+                gate_versions
+                    .get_or_default(gate_version)
+                    .objects
+                    .insert(o.id());
             }
         }
+    }
 
-        for row in db.speedchat_menu.row_iter() {
-            let id = row.id();
+    if let Some(player_statistics) = &db.player_statistics {
+        for row in player_statistics.row_iter() {
+            let id = row.stat_id();
             if let Some(gate) = row.gate_version() {
-                gate_versions.get_or_default(gate).speedchat_menu.insert(id);
+                gate_versions
+                    .get_or_default(gate)
+                    .player_statistics
+                    .insert(id);
             }
         }
+    }
+
+    for row in db.preconditions.row_iter() {
+        let id = row.id();
+        if let Some(gate) = row.gate_version() {
+            gate_versions.get_or_default(gate).preconditions.insert(id);
+        }
+    }
 
-        if let Some(ug_behavior_sounds) = &db.ug_behavior_sounds {
-            for row in ug_behavior_sounds.row_iter() {
-                let id = row.id();
-                if let Some(gate) = row.gate_version() {
-                    gate_versions
-                        .get_or_default(gate)
-                        .ug_behavior_sounds
-                        .insert(id);
-                }
-            }
+    for row in db.property_template.row_iter() {
+        let id = row.id();
+        if let Some(gate) = row.gate_version() {
+            gate_versions
+                .get_or_default(gate)
+                .property_template
+                .insert(id);
         }
+    }
 
-        if let Some(vendor_component) = &db.vendor_component {
-            for row in vendor_component.row_iter() {
-                loot_matrix_index
-                    .entry(row.loot_matrix_index())
-                    .or_default()
-                    .components
-                    .vendor
+    if let Some(reward_codes) = &db.reward_codes {
+        for row in reward_codes.row_iter() {
+            if let Some(gate) = row.gate_version() {
+                gate_versions
+                    .get_or_default(gate)
+                    .reward_codes
                     .insert(row.id());
             }
         }
+    }
 
-        if let Some(whats_cool_item_spotlight) = &db.whats_cool_item_spotlight {
-            for row in whats_cool_item_spotlight.row_iter() {
-                let id = row.id();
-                if let Some(gate) = row.gate_version() {
-                    gate_versions
-                        .get_or_default(gate)
-                        .whats_cool_item_spotlight
-                        .insert(id);
-                }
-            }
+    for skill in db.skills.row_iter() {
+        if let Some(gate_version) = skill.gate_version() {
+            gate_versions
+                .get_or_default(gate_version)
+                .skills
+                .insert(skill.skill_id());
         }
+    }
 
-        if let Some(whats_cool_news_and_tips) = &db.whats_cool_news_and_tips {
-            for row in whats_cool_news_and_tips.row_iter() {
-                let id = row.id();
-                if let Some(gate) = row.gate_version() {
-                    gate_versions
-                        .get_or_default(gate)
-                        .whats_cool_news_and_tips
-                        .insert(id);
-                }
-            }
+    for row in db.speedchat_menu.row_iter() {
+        let id = row.id();
+        if let Some(gate) = row.gate_version() {
+            gate_versions.get_or_default(gate).speedchat_menu.insert(id);
         }
+    }
 
-        if let Some(zone_loading_tips) = &db.zone_loading_tips {
-            for row in zone_loading_tips.row_iter() {
-                let id = row.id();
-                let gate = row.gate_version();
+    if let Some(ug_behavior_sounds) = &db.ug_behavior_sounds {
+        for row in ug_behavior_sounds.row_iter() {
+            let id = row.id();
+            if let Some(gate) = row.gate_version() {
                 gate_versions
                     .get_or_default(gate)
-                    .zone_loading_tips
+                    .ug_behavior_sounds
                     .insert(id);
             }
         }
+    }
 
-        for row in db.zone_table.row_iter() {
-            let id = row.zone_id();
+    if let Some(whats_cool_item_spotlight) = &db.whats_cool_item_spotlight {
+        for row in whats_cool_item_spotlight.row_iter() {
+            let id = row.id();
             if let Some(gate) = row.gate_version() {
-                gate_versions.get_or_default(gate).zones.insert(id);
+                gate_versions
+                    .get_or_default(gate)
+                    .whats_cool_item_spotlight
+                    .insert(id);
             }
         }
+    }
 
-        let duration = time.elapsed();
-        info!("Done loading ReverseLookup ({}ms)", duration.as_millis());
-        Self {
-            behaviors,
-            skill_ids,
-            skill_cooldown_groups,
-            mission_task_uids,
-            mission_types,
-            missions,
-            factions,
-            objects,
-            object_types,
-            component_use,
-            activities,
-            loot_matrix_index,
-            loot_table_index,
-            gate_versions,
+    if let Some(whats_cool_news_and_tips) = &db.whats_cool_news_and_tips {
+        for row in whats_cool_news_and_tips.row_iter() {
+            let id = row.id();
+            if let Some(gate) = row.gate_version() {
+                gate_versions
+                    .get_or_default(gate)
+                    .whats_cool_news_and_tips
+                    .insert(id);
+            }
         }
     }
 
-    pub(crate) fn get_behavior_set(&self, root: i32) -> BTreeSet<i32> {
-        let mut todo = Vec::new();
-        let mut all = BTreeSet::new();
-        todo.push(root);
+    if let Some(zone_loading_tips) = &db.zone_loading_tips {
+        for row in zone_loading_tips.row_iter() {
+            let id = row.id();
+            let gate = row.gate_version();
+            gate_versions
+                .get_or_default(gate)
+                .zone_loading_tips
+                .insert(id);
+        }
+    }
 
-        while let Some(next) = todo.pop() {
-            if !all.contains(&next) {
-                all.insert(next);
-                if let Some(data) = self.behaviors.get(&next) {
-                    todo.extend(data.uses.iter().filter(|&&x| x > 0));
-                }
-            }
+    for row in db.zone_table.row_iter() {
+        let id = row.zone_id();
+        if let Some(gate) = row.gate_version() {
+            gate_versions.get_or_default(gate).zones.insert(id);
         }
-        all
+    }
+    gate_versions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_prereqs, precondition_ids, CooldownStats};
+
+    #[test]
+    fn test_parse_prereqs() {
+        assert_eq!(parse_prereqs("1", 0), vec![vec![1]]);
+        assert_eq!(parse_prereqs("1,2", 0), vec![vec![1], vec![2]]);
+        assert_eq!(parse_prereqs("(1|2:3) & 4", 0), vec![vec![1, 2], vec![4]]);
+        assert_eq!(parse_prereqs("x", 0), Vec::<Vec<i32>>::new());
+    }
+
+    #[test]
+    fn test_precondition_ids() {
+        let ids = |text| precondition_ids(text).collect::<Vec<i32>>();
+        assert_eq!(ids("148"), vec![148]);
+        assert_eq!(ids("12;15|16"), vec![12, 15, 16]);
+        assert_eq!(ids(" 7, 8 "), vec![7, 8]);
+        assert_eq!(ids(""), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_cooldown_stats() {
+        assert_eq!(CooldownStats::new(vec![]), None);
+        let stats = CooldownStats::new(vec![4.0, 1.5, 2.0, 4.0, 1.5, 4.0]).unwrap();
+        assert_eq!(stats.min, 1.5);
+        assert_eq!(stats.max, 4.0);
+        assert_eq!(stats.representative, 4.0);
+        let tie = CooldownStats::new(vec![3.0, 2.0]).unwrap();
+        assert_eq!(tie.representative, 2.0);
     }
 }
@@ -0,0 +1,28 @@
+use std::collections::BTreeMap;
+
+use paradox_typed_db::TypedDatabase;
+use serde::Serialize;
+
+use super::{data::EmoteRev, missions::mission_names, Api, ReverseLookup};
+use crate::data::locale::LocaleRoot;
+
+#[derive(Serialize)]
+pub struct EmoteByIdEmbedded {
+    /// Names of the missions that reward this emote
+    #[serde(rename = "Missions")]
+    missions: BTreeMap<i32, String>,
+}
+
+pub(super) fn emote_by_id<'b>(
+    db: &TypedDatabase<'_>,
+    rev: &'b ReverseLookup,
+    loc: &LocaleRoot,
+    id: i32,
+) -> Option<Api<&'b EmoteRev, EmoteByIdEmbedded>> {
+    rev.emotes().get(&id).map(|data| Api {
+        data,
+        embedded: EmoteByIdEmbedded {
+            missions: mission_names(db, loc, &data.missions.reward_emote),
+        },
+    })
+}
@@ -3,7 +3,7 @@ use std::collections::{BTreeMap, BTreeSet};
 use paradox_typed_db::TypedDatabase;
 use serde::Serialize;
 
-use super::ReverseLookup;
+use super::{Api, NoEmbeds, ReverseLookup};
 
 #[derive(Debug, Default, Clone, Serialize)]
 pub struct LootMatrixIndexRev {
@@ -39,6 +39,6 @@ pub(super) fn rev_loop_table_index<'r>(
     _db: &'r TypedDatabase<'_>,
     rev: &'r ReverseLookup,
     index: i32,
-) -> Option<&'r LootMatrixIndexRev> {
-    rev.loot_matrix_index.get(&index)
+) -> Option<Api<&'r LootMatrixIndexRev, NoEmbeds>> {
+    rev.loot_matrix_index().get(&index).map(Api::without_embeds)
 }
@@ -11,7 +11,7 @@ use paradox_typed_db::{
 };
 use serde::Serialize;
 
-use super::ReverseLookup;
+use super::{data::LootTableIndexRev, Api, ReverseLookup};
 
 type LootTableResultInner<'db, 'r> = TypedTableIterAdapter<
     'db,
@@ -35,12 +35,14 @@ pub struct LootTableResult<'db, 'r> {
     loot_matrix: LootMatrixResultInner<'db, 'r>,
 }
 
+type LootTableIndexApiResult<'db, 'r> = Api<&'r LootTableIndexRev, LootTableResult<'db, 'r>>;
+
 pub(super) fn rev_loop_table_index<'db, 'r>(
     db: &'r TypedDatabase<'db>,
     rev: &'r ReverseLookup,
     index: i32,
-) -> Option<LootTableResult<'db, 'r>> {
-    let lti_rev = rev.loot_table_index.get(&index)?;
+) -> Option<LootTableIndexApiResult<'db, 'r>> {
+    let lti_rev = rev.loot_table_index().get(&index)?;
     let loot_table = TypedTableIterAdapter {
         index: &lti_rev.items,
         keys: lti_rev.items.keys().copied(),
@@ -55,8 +57,11 @@ pub(super) fn rev_loop_table_index<'db, 'r>(
         id_col: db.loot_matrix.get_col(LootMatrixColumn::Id).unwrap(),
         layout: AdapterLayout::Seq,
     };
-    Some(LootTableResult {
-        loot_table,
-        loot_matrix,
+    Some(Api {
+        data: lti_rev,
+        embedded: LootTableResult {
+            loot_table,
+            loot_matrix,
+        },
     })
 }
@@ -1,9 +1,10 @@
 use std::{
     borrow::Borrow,
     collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
 };
 
-use paradox_typed_db::{rows::MissionsRow, TypedDatabase};
+use paradox_typed_db::{ext::MissionKind, rows::MissionsRow, TypedDatabase};
 use serde::{ser::SerializeMap, Serialize};
 
 use super::{
@@ -37,7 +38,7 @@ pub(super) struct MissionTypesAdapter<'a>(&'a BTreeMap<String, BTreeMap<String,
 
 impl<'a> MissionTypesAdapter<'a> {
     pub fn new(rev: &'a ReverseLookup) -> Self {
-        Self(&rev.mission_types)
+        Self(rev.mission_types())
     }
 }
 
@@ -143,7 +144,7 @@ pub(super) fn rev_mission_type<'a, 'b: 'a>(
     d_type: PercentDecoded,
 ) -> RevMissionTypeReply<'a, 'b> {
     let key: &String = d_type.borrow();
-    match rev.mission_types.get(key) {
+    match rev.mission_types().get(key) {
         Some(t) => match t.get("") {
             Some(mission_ids) => {
                 RevMissionTypeReply::Missions(missions_reply(db, loc, mission_ids))
@@ -164,33 +165,90 @@ pub(super) fn rev_mission_subtype<'a, 'b>(
     d_subtype: PercentDecoded,
 ) -> Option<MissionsReply<'a, 'b>> {
     let t_key: &String = d_type.borrow();
-    let t = rev.mission_types.get(t_key)?;
+    let t = rev.mission_types().get(t_key)?;
     let s_key: &String = d_subtype.borrow();
     let mission_ids = t.get(s_key)?;
     Some(missions_reply(db, loc, mission_ids))
 }
 
+/// The localized names of the missions `ids`, for an `_embedded.Missions` map
+pub(super) fn mission_names<'i>(
+    db: &TypedDatabase<'_>,
+    loc: &LocaleRoot,
+    ids: impl IntoIterator<Item = &'i i32>,
+) -> BTreeMap<i32, String> {
+    ids.into_iter()
+        .filter_map(|&mission_id| {
+            let kind = match db.get_mission_data(mission_id) {
+                Some(mission) if !mission.is_mission => MissionKind::Achievement,
+                _ => MissionKind::Mission,
+            };
+            loc.get_mission_name(kind, mission_id)
+                .map(|name| (mission_id, name))
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct MissionName {
+    id: i32,
+    name: Option<String>,
+}
+
+/// The IDs of all missions with their localized name, for `/rev/missions?embed=names`
+pub(super) struct MissionNames<'a> {
+    missions: &'a BTreeMap<i32, MissionRev>,
+    loc: &'a LocaleRootInner,
+}
+
+impl<'a> MissionNames<'a> {
+    pub fn new(rev: &'a ReverseLookup, loc: &'a LocaleRoot) -> Self {
+        Self {
+            missions: rev.missions(),
+            loc: &loc.root,
+        }
+    }
+}
+
+impl<'a> Serialize for MissionNames<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let keys = self.loc.keys();
+        let missions = self.loc.node().get_str(keys.missions);
+        serializer.collect_seq(self.missions.keys().map(|&id| {
+            let name = u32::try_from(id)
+                .ok()
+                .and_then(|id| missions.as_ref()?.get_int(id))
+                .and_then(|mission| mission.get_str(keys.name))
+                .and_then(|node| node.value().map(|v| v.to_string()));
+            MissionName { id, name }
+        }))
+    }
+}
+
 #[derive(Serialize)]
-pub struct MissionByIdEmbedded {
+pub struct MissionByIdEmbedded<'b> {
     #[serde(rename = "ItemComponent")]
-    item_components: Filtered<BTreeMap<i32, ComponentUse>, &'static BTreeSet<i32>>,
+    item_components: Filtered<'b, BTreeMap<i32, ComponentUse>, &'b BTreeSet<i32>>,
     #[serde(rename = "CollectibleComponent")]
-    collectible_components: Filtered<BTreeMap<i32, ComponentUse>, &'static BTreeSet<i32>>,
+    collectible_components: Filtered<'b, BTreeMap<i32, ComponentUse>, &'b BTreeSet<i32>>,
 }
 
 pub(crate) fn mission_by_id(
-    rev: &'static ReverseLookup,
+    rev: &ReverseLookup,
     id: i32,
-) -> Option<Api<&'static MissionRev, MissionByIdEmbedded>> {
-    rev.missions.get(&id).map(|data| Api {
+) -> Option<Api<&MissionRev, MissionByIdEmbedded<'_>>> {
+    rev.missions().get(&id).map(|data| Api {
         data,
         embedded: MissionByIdEmbedded {
             item_components: rev
-                .component_use
+                .component_use()
                 .filter(COMPONENT_ID_ITEM, &data.item_components.requirement_for)
                 .unwrap(),
             collectible_components: rev
-                .component_use
+                .component_use()
                 .filter(
                     COMPONENT_ID_COLLECTIBLE,
                     &data.collectible_components.requirement_for,
@@ -3,26 +3,29 @@
 //! This module contains the reverse API of the server. These are, generally speaking,
 //! database lookups by some specific ID such as an "object template id" or a "skill id"
 //! and produce data from multiple tables.
-pub(crate) use self::routes::Route;
-use self::{factions::FactionById, routes::REV_APIS};
+use self::routes::REV_APIS;
+pub(crate) use self::routes::{alias_deprecation, canonical_path, Route, RouteError};
 use super::adapter::Keys;
-use crate::data::locale::LocaleRoot;
+use crate::data::{db::SharedDb, fs::LuRes, locale::LocaleRoot};
 pub use data::ReverseLookup;
-use http::{Method, StatusCode};
-use paradox_typed_db::TypedDatabase;
+use http::{Method, StatusCode, Uri};
 use serde::Serialize;
 use std::task::{Context, Poll};
 use tower::Service;
 
+mod activities;
 mod behaviors;
 mod common;
 mod component_types;
 mod data;
+mod emotes;
 mod factions;
 mod loot_matrix_index;
 mod loot_table_index;
 mod missions;
 mod object_types;
+mod objects;
+mod preconditions;
 mod routes;
 mod skills;
 
@@ -34,24 +37,83 @@ pub struct Api<T, E> {
     embedded: E,
 }
 
+impl<T, E> Api<T, E> {
+    /// Keep the `_embedded` data only if `embed` is set
+    pub fn embed(self, embed: bool) -> Embed<T, E> {
+        if embed {
+            Embed::Full(self)
+        } else {
+            Embed::Bare(self.data)
+        }
+    }
+}
+
+/// The reply of a single-id route, which is the full [`Api`] only with `?embed=1`
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Embed<T, E> {
+    Full(Api<T, E>),
+    Bare(T),
+}
+
+/// The `_embedded` data of routes that have no related rows
+#[derive(Debug, Clone, Serialize)]
+pub struct NoEmbeds {}
+
+impl<T> Api<T, NoEmbeds> {
+    pub fn without_embeds(data: T) -> Self {
+        Self {
+            data,
+            embedded: NoEmbeds {},
+        }
+    }
+}
+
+/// Whether the related rows of a single-id route were requested
+fn embed_param(uri: &Uri) -> bool {
+    matches!(
+        super::query_param(uri, "embed").as_deref(),
+        Some("1" | "true")
+    )
+}
+
 #[derive(Clone)]
 pub struct RevService {
-    db: &'static TypedDatabase<'static>,
+    data: SharedDb,
     loc: LocaleRoot,
-    rev: &'static ReverseLookup,
+    res: LuRes,
+}
+
+impl RevService {
+    pub(crate) fn new(data: SharedDb, loc: LocaleRoot, res: LuRes) -> RevService {
+        Self { data, loc, res }
+    }
 }
 
 impl RevService {
-    pub(crate) fn new(
-        db: &'static TypedDatabase<'static>,
-        loc: LocaleRoot,
-        rev: &'static ReverseLookup,
-    ) -> RevService {
-        Self { db, loc, rev }
+    /// Handle a `QUERY` (or `POST`) request for `/objects/search-index` with a JSON (or YAML) list of IDs
+    pub(crate) fn search_index_query<B>(
+        &self,
+        accept: super::Accept,
+        format: super::BodyFormat,
+        body: B,
+    ) -> super::ApiFuture
+    where
+        B: http_body::Body<Data = hyper::body::Bytes> + Send + Unpin + 'static,
+        B::Error: std::fmt::Display,
+    {
+        let snapshot = self.data.get();
+        let ready = snapshot
+            .rev()
+            .can_answer(Route::ObjectsSearchIndex.indices());
+        if !ready {
+            return super::ApiFuture::ready(super::reply_503_loading(accept));
+        }
+        objects::search_index_query(accept, format, snapshot, body)
     }
 }
 
-impl Service<(super::Accept, Method, Route)> for RevService {
+impl Service<(super::Accept, Method, Route, Uri)> for RevService {
     type Response = http::Response<hyper::Body>;
     type Error = super::ApiError;
     type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
@@ -60,101 +122,217 @@ impl Service<(super::Accept, Method, Route)> for RevService {
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, (a, method, route): (super::Accept, Method, Route)) -> Self::Future {
-        if method != Method::GET && method != Method::HEAD {
-            // For now, only allow GET requests
+    fn call(
+        &mut self,
+        (a, method, route, uri): (super::Accept, Method, Route, Uri),
+    ) -> Self::Future {
+        if method != Method::GET {
+            // For now, only allow GET requests (`HEAD` is handled by the `ApiService`)
             return std::future::ready(Ok(super::reply_405(&super::ALLOW_GET_HEAD)));
         }
-        if method == Method::HEAD {
-            return std::future::ready(Ok(super::reply_200(a)));
+        let snapshot = self.data.get();
+        let (fdb, db, rev) = (snapshot.db(), snapshot.tydb(), snapshot.rev());
+        if !rev.can_answer(route.indices()) {
+            return std::future::ready(super::reply_503_loading(a));
         }
+        let embed = embed_param(&uri);
         let r = match route {
             Route::Base => super::reply_json(&REV_APIS, StatusCode::OK),
-            Route::Activities => super::reply(a, &Keys::new(&self.rev.activities), StatusCode::OK),
-            Route::ActivityById(id) => super::reply_opt(a, self.rev.activities.get(&id)),
-            Route::BehaviorById(id) => {
-                super::reply(a, &behaviors::lookup(self.db, self.rev, id), StatusCode::OK)
-            }
-            Route::ComponentTypes => super::reply(
+            Route::Activities => super::reply(a, &Keys::new(rev.activities()), StatusCode::OK),
+            Route::ActivityById(id) => super::reply_opt(
+                a,
+                activities::rev_activity(db, rev, &self.loc, id)
+                    .map(|api| api.embed(embed))
+                    .as_ref(),
+            ),
+            Route::BehaviorById(id) => super::reply(
                 a,
-                &component_types::Components::new(self.rev),
+                &behaviors::lookup(db, rev, id).embed(embed),
                 StatusCode::OK,
             ),
+            Route::BehaviorTreeById(id) => {
+                super::reply_opt(a, behaviors::tree(db, rev, id).as_ref())
+            }
+            Route::ComponentTypes => {
+                super::reply(a, &component_types::Components::new(rev), StatusCode::OK)
+            }
             Route::ComponentTypeById(id) => super::reply(
                 a,
-                &component_types::rev_component_type(self.db, self.rev, id),
+                &component_types::rev_component_type(db, rev, id).map(|api| api.embed(embed)),
                 StatusCode::OK,
             ),
-            Route::ComponentTypeByIdAndCid(key, cid) => super::reply(
+            Route::ComponentTypeByIdAndCid(key, cid) => {
+                match component_types::rev_single_component(fdb, rev, key, cid) {
+                    Ok(component) => {
+                        super::reply_opt(a, component.map(|api| api.embed(embed)).as_ref())
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            }
+            Route::Emotes => super::reply(a, &Keys::new(rev.emotes()), StatusCode::OK),
+            Route::EmoteById(id) => super::reply_opt(
+                a,
+                emotes::emote_by_id(db, rev, &self.loc, id)
+                    .map(|api| api.embed(embed))
+                    .as_ref(),
+            ),
+            Route::Factions => super::reply(a, &Keys::new(rev.factions()), StatusCode::OK),
+            Route::FactionById(id) => super::reply(
                 a,
-                &component_types::rev_single_component(self.rev, key, cid),
+                &factions::faction_by_id(rev, id).map(|api| api.embed(embed)),
                 StatusCode::OK,
             ),
-            Route::Factions => super::reply(a, &Keys::new(&self.rev.factions), StatusCode::OK),
-            Route::FactionById(id) => {
-                super::reply(a, &FactionById::new(self.rev, id), StatusCode::OK)
-            }
             Route::LootTableIndexById(id) => super::reply(
                 a,
-                &loot_table_index::rev_loop_table_index(self.db, self.rev, id),
+                &loot_table_index::rev_loop_table_index(db, rev, id).map(|api| api.embed(embed)),
                 StatusCode::OK,
             ),
             Route::LootMatrixByIndex(index) => super::reply(
                 a,
-                &loot_matrix_index::rev_loop_table_index(self.db, self.rev, index),
+                &loot_matrix_index::rev_loop_table_index(db, rev, index)
+                    .map(|api| api.embed(embed)),
                 StatusCode::OK,
             ),
-            Route::Missions => super::reply(a, &Keys::new(&self.rev.missions), StatusCode::OK),
-            Route::MissionById(id) => {
-                super::reply_opt(a, missions::mission_by_id(self.rev, id).as_ref())
-            }
-            Route::MissionTypes => super::reply(
+            Route::Missions => match super::query_param(&uri, "embed").as_deref() {
+                Some("names") => super::reply(
+                    a,
+                    &missions::MissionNames::new(rev, &self.loc),
+                    StatusCode::OK,
+                ),
+                _ => super::reply(a, &Keys::new(rev.missions()), StatusCode::OK),
+            },
+            Route::MissionById(id) => super::reply_opt(
                 a,
-                &missions::MissionTypesAdapter::new(self.rev),
-                StatusCode::OK,
+                missions::mission_by_id(rev, id)
+                    .map(|api| api.embed(embed))
+                    .as_ref(),
             ),
-            Route::MissionTypesFull => super::reply(a, &self.rev.mission_types, StatusCode::OK),
+            Route::MissionTypes => {
+                super::reply(a, &missions::MissionTypesAdapter::new(rev), StatusCode::OK)
+            }
+            Route::MissionTypesFull => super::reply(a, rev.mission_types(), StatusCode::OK),
             Route::MissionTypeByTy(ty) => super::reply(
                 a,
-                &missions::rev_mission_type(self.db, self.rev, &self.loc, ty),
+                &missions::rev_mission_type(db, rev, &self.loc, ty),
                 StatusCode::OK,
             ),
             Route::MissionTypeBySubTy(d_type, d_subtype) => super::reply(
                 a,
-                &missions::rev_mission_subtype(self.db, self.rev, &self.loc, d_type, d_subtype),
+                &missions::rev_mission_subtype(db, rev, &self.loc, d_type, d_subtype),
                 StatusCode::OK,
             ),
+            Route::ObjectsSearch => objects::search(a, rev, &uri),
             Route::ObjectsSearchIndex => {
-                super::reply(a, &self.rev.objects.search_index, StatusCode::OK)
+                super::reply(a, &rev.objects().search_index, StatusCode::OK)
             }
-            Route::ObjectTypes => {
-                super::reply(a, &Keys::new(&self.rev.object_types), StatusCode::OK)
+            Route::ObjectTypes => super::reply(a, &Keys::new(rev.object_types()), StatusCode::OK),
+            Route::ObjectTypeCounts => {
+                super::reply(a, &object_types::ObjectTypeCounts::new(rev), StatusCode::OK)
             }
             Route::ObjectTypeByName(ty) => super::reply(
                 a,
-                &object_types::rev_object_type(self.db, self.rev, ty),
+                &object_types::rev_object_type(db, rev, ty),
                 StatusCode::OK,
             ),
+            Route::Skills => {
+                super::reply(a, &skills::SkillNames::new(db, &self.loc), StatusCode::OK)
+            }
             Route::SkillById(skill_id) => super::reply(
                 a,
-                &skills::rev_skill_id(self.db, self.rev, skill_id),
+                &skills::rev_skill_id(db, rev, skill_id).map(|api| api.embed(embed)),
                 StatusCode::OK,
             ),
-            Route::SkillCooldownGroups => super::reply(
+            Route::SkillCooldownGroups => match super::query_param(&uri, "embed").as_deref() {
+                Some("cooldowns") => {
+                    super::reply(a, &skills::SkillCooldownGroups::new(rev), StatusCode::OK)
+                }
+                _ => super::reply(a, &Keys::new(rev.skill_cooldown_groups()), StatusCode::OK),
+            },
+            Route::SkillCooldownGroupById(id) => super::reply_opt(
                 a,
-                &Keys::new(&self.rev.skill_cooldown_groups),
-                StatusCode::OK,
+                skills::rev_skill_cooldown_group(db, rev, &self.loc, id)
+                    .map(|api| api.embed(embed))
+                    .as_ref(),
+            ),
+            Route::GateVersions => super::reply(a, &rev.gate_versions().keys(), StatusCode::OK),
+            Route::GateVersionByName(name) => super::reply_opt(
+                a,
+                rev.gate_versions()
+                    .get(&name.0)
+                    .map(|data| Api::without_embeds(data).embed(embed))
+                    .as_ref(),
             ),
-            Route::SkillCooldownGroupById(id) => {
-                super::reply_opt(a, self.rev.skill_cooldown_groups.get(&id))
+            Route::GateVersionsDiff => {
+                match (
+                    super::query_param(&uri, "from"),
+                    super::query_param(&uri, "to"),
+                ) {
+                    (Some(from), Some(to)) => {
+                        let versions = rev.gate_versions();
+                        match (versions.get(&from), versions.get(&to)) {
+                            (Some(from), Some(to)) => {
+                                super::reply(a, &to.added_since(from), StatusCode::OK)
+                            }
+                            _ => Ok(super::reply_404()),
+                        }
+                    }
+                    _ => super::reply_400(
+                        a,
+                        "missing parameter",
+                        "both `from` and `to` are required",
+                    ),
+                }
             }
-            Route::GateVersions => super::reply(a, &self.rev.gate_versions.keys(), StatusCode::OK),
-            Route::GateVersionByName(name) => {
-                super::reply_opt(a, self.rev.gate_versions.get(&name.0))
+            Route::Objects => super::reply(a, &Keys::new(&rev.objects().rev), StatusCode::OK),
+            Route::Preconditions => {
+                super::reply(a, &Keys::new(rev.preconditions()), StatusCode::OK)
+            }
+            Route::PreconditionById(id) => super::reply_opt(
+                a,
+                preconditions::precondition_by_id(rev, id)
+                    .map(|api| api.embed(embed))
+                    .as_ref(),
+            ),
+            Route::ObjectById(id) => super::reply_opt(
+                a,
+                objects::rev_object(db, rev, &self.res, id)
+                    .map(|api| api.embed(embed))
+                    .as_ref(),
+            ),
+            // `/full` is the embedded view, so it ignores `?embed`
+            Route::ObjectFullById(id) => {
+                super::reply_opt(a, objects::rev_object_full(db, rev, &self.loc, id).as_ref())
+            }
+            Route::ObjectComponentsById(id) => super::reply_opt(
+                a,
+                objects::object_components(db, rev, &self.res, id)
+                    .map(|api| api.embed(embed))
+                    .as_ref(),
+            ),
+            Route::ObjectImageById(id) => {
+                super::reply_opt(a, objects::object_image(db, &self.res, id).as_ref())
             }
-            Route::Objects => super::reply(a, &Keys::new(&self.rev.objects.rev), StatusCode::OK),
-            Route::ObjectById(id) => super::reply_opt(a, self.rev.objects.rev.get(&id)),
         };
         std::future::ready(r)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{Api, NoEmbeds};
+
+    #[test]
+    fn test_embed() {
+        let data: BTreeMap<&str, i32> = [("id", 1)].iter().copied().collect();
+        let api = Api {
+            data: &data,
+            embedded: NoEmbeds {},
+        };
+        let full = serde_json::to_string(&api.clone().embed(true)).unwrap();
+        assert_eq!(full, r#"{"id":1,"_embedded":{}}"#);
+        let bare = serde_json::to_string(&api.embed(false)).unwrap();
+        assert_eq!(bare, r#"{"id":1}"#);
+    }
+}
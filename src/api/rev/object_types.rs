@@ -1,4 +1,4 @@
-use std::borrow::Borrow;
+use std::{borrow::Borrow, collections::BTreeMap};
 
 use paradox_typed_db::TypedDatabase;
 use serde::Serialize;
@@ -9,6 +9,24 @@ use crate::api::{
     PercentDecoded,
 };
 
+/// The number of objects for each object type
+pub(super) struct ObjectTypeCounts<'a>(&'a BTreeMap<String, Vec<i32>>);
+
+impl<'a> ObjectTypeCounts<'a> {
+    pub fn new(rev: &'a ReverseLookup) -> Self {
+        Self(rev.object_types())
+    }
+}
+
+impl<'a> Serialize for ObjectTypeCounts<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_map(self.0.iter().map(|(ty, ids)| (ty, ids.len())))
+    }
+}
+
 #[derive(Serialize)]
 pub(super) struct ObjectIDs<'a, T> {
     object_ids: &'a [T],
@@ -20,7 +38,13 @@ pub(super) fn rev_object_type<'a, 'b, 'r>(
     ty: PercentDecoded,
 ) -> Option<Api<ObjectIDs<'r, i32>, ObjectTypeEmbedded<'a, 'b, &'r [i32]>>> {
     let key: &String = ty.borrow();
-    let object_ids: &[i32] = rev.object_types.get(key)?.as_ref();
+    let types = rev.object_types();
+    // Some clients send `+` for a space, which is only valid in a query string.
+    // No object type contains a `+`, so try that if there is no exact match.
+    let object_ids: &[i32] = match types.get(key) {
+        Some(ids) => ids,
+        None => types.get(&key.replace('+', " "))?,
+    };
     Some(Api {
         data: ObjectIDs { object_ids },
         embedded: ObjectTypeEmbedded {
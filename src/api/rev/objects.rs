@@ -0,0 +1,353 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+use http::{StatusCode, Uri};
+use hyper::body::Bytes;
+use paradox_typed_db::TypedDatabase;
+use serde::Serialize;
+
+use super::{
+    common::ObjectsRefAdapter,
+    data::{
+        ObjectComponents, ObjectStrings, ObjectsUse, COMPONENT_ID_INVENTORY, COMPONENT_ID_ITEM,
+    },
+    missions::mission_names,
+    Api, ReverseLookup,
+};
+use crate::{
+    api::{
+        adapter::Filtered, query_param, read_body, reply, reply_400, Accept, ApiFuture, ApiResult,
+        BodyFormat, BoxError,
+    },
+    data::{db::DbSnapshot, fs::LuRes, locale::LocaleRoot},
+};
+
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+/// In fuzzy mode, one typo is allowed per this many characters of the query
+const FUZZY_CHARS_PER_EDIT: usize = 3;
+
+#[derive(Serialize)]
+pub(super) struct SearchHit<'r> {
+    id: i32,
+    name: &'r str,
+}
+
+fn contains_lowercase(text: Option<&str>, needle: &str) -> bool {
+    match text {
+        Some(text) => text.to_lowercase().contains(needle),
+        None => false,
+    }
+}
+
+/// Rank how well `strings` match the lowercase `needle` (lower is better)
+fn rank(strings: &ObjectStrings, needle: &str) -> Option<u8> {
+    let name = strings.n.to_lowercase();
+    if name == needle {
+        Some(0)
+    } else if name.starts_with(needle) {
+        Some(1)
+    } else if name.contains(needle) {
+        Some(2)
+    } else if contains_lowercase(strings.i.as_deref(), needle) {
+        Some(3)
+    } else if contains_lowercase(strings.d.as_deref(), needle) {
+        Some(4)
+    } else {
+        None
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`, by characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let subst = diag + usize::from(ca != cb);
+            diag = row[j + 1];
+            row[j + 1] = subst.min(row[j] + 1).min(diag + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The edit distance of the lowercase `needle` to `name` or one of its words
+///
+/// Names that are further away than `max` are skipped, as are those whose length
+/// alone is too different, without computing the distance.
+fn fuzzy_rank(name: &str, needle: &str, max: usize) -> Option<usize> {
+    let len = needle.chars().count();
+    let name = name.to_lowercase();
+    std::iter::once(name.as_str())
+        .chain(name.split_whitespace())
+        .filter(|part| part.chars().count().abs_diff(len) <= max)
+        .map(|part| edit_distance(part, needle))
+        .min()
+        .filter(|&d| d <= max)
+}
+
+/// Search the names, display names and descriptions of all objects
+///
+/// With `fuzzy=1`, names that are a few typos away from the query are found
+/// too, after all regular hits.
+pub(super) fn search(accept: Accept, rev: &ReverseLookup, uri: &Uri) -> ApiResult {
+    let needle = match query_param(uri, "q") {
+        Some(q) if !q.trim().is_empty() => q.trim().to_lowercase(),
+        _ => {
+            return reply_400(
+                accept,
+                "missing query",
+                "expected a non-empty `q` parameter",
+            )
+        }
+    };
+    let limit = match query_param(uri, "limit") {
+        Some(limit) => match limit.parse() {
+            Ok(limit) => limit,
+            Err(e) => return reply_400(accept, "invalid limit", e),
+        },
+        None => DEFAULT_SEARCH_LIMIT,
+    };
+    // Shorter queries don't allow a single typo, so they are never fuzzy
+    let max_edits = needle.chars().count() / FUZZY_CHARS_PER_EDIT;
+    let fuzzy = max_edits > 0 && matches!(query_param(uri, "fuzzy").as_deref(), Some("1" | "true"));
+
+    let index = &rev.objects().search_index;
+    let mut hits: Vec<_> = index
+        .iter()
+        .filter_map(|(&id, strings)| Some(((0, usize::from(rank(strings, &needle)?)), id, strings)))
+        .collect();
+    if fuzzy && hits.len() < limit {
+        // A name with the query in it is a regular hit, so the best fuzzy hits have
+        // a distance of 1. The index is sorted by ID, so once there are enough of
+        // those, the remaining objects can't make it into the result.
+        let regular = hits.len();
+        let mut close = 0;
+        for (&id, strings) in index {
+            if regular + close >= limit {
+                break;
+            }
+            let is_regular = hits[..regular]
+                .binary_search_by_key(&id, |&(_, id, _)| id)
+                .is_ok();
+            if is_regular {
+                continue;
+            }
+            if let Some(d) = fuzzy_rank(&strings.n, &needle, max_edits) {
+                close += usize::from(d <= 1);
+                hits.push(((1, d), id, strings));
+            }
+        }
+    }
+    hits.sort_by_key(|&(r, id, _)| (r, id));
+    let hits: Vec<SearchHit> = hits
+        .into_iter()
+        .take(limit)
+        .map(|(_, id, strings)| SearchHit {
+            id,
+            name: &strings.n,
+        })
+        .collect();
+    reply(accept, &hits, StatusCode::OK)
+}
+
+/// Get the entries of the search index for the list of object IDs in the body
+pub(super) fn search_index_query<B>(
+    accept: Accept,
+    format: BodyFormat,
+    snapshot: Arc<DbSnapshot>,
+    body: B,
+) -> ApiFuture
+where
+    B: http_body::Body<Data = Bytes> + Send + Unpin + 'static,
+    B::Error: Into<BoxError>,
+{
+    ApiFuture::boxed(async move {
+        let bytes = match read_body(accept, body).await {
+            Ok(bytes) => bytes,
+            Err(r) => return r,
+        };
+        let keys: BTreeSet<i32> = match format.deserialize(&bytes) {
+            Ok(keys) => keys,
+            Err(e) => return reply_400(accept, format.parse_error(), e),
+        };
+        let filtered = Filtered {
+            inner: &snapshot.rev().objects().search_index,
+            keys,
+        };
+        reply(accept, &filtered, StatusCode::OK)
+    })
+}
+
+#[derive(Serialize)]
+pub(super) struct ObjectEmbedded {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// The URL of the render component icon
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+}
+
+/// The reverse lookup data of an object, with its name, description and icon
+pub(super) fn rev_object<'b>(
+    db: &TypedDatabase<'_>,
+    rev: &'b ReverseLookup,
+    res: &LuRes,
+    id: i32,
+) -> Option<Api<&'b ObjectsUse, ObjectEmbedded>> {
+    let data = rev.objects().rev.get(&id)?;
+    Some(Api {
+        data,
+        embedded: object_embedded(db, res, id),
+    })
+}
+
+/// The components of an object, with its name, description and icon
+pub(super) fn object_components<'b>(
+    db: &TypedDatabase<'_>,
+    rev: &'b ReverseLookup,
+    res: &LuRes,
+    id: i32,
+) -> Option<Api<&'b ObjectComponents, ObjectEmbedded>> {
+    let data = rev.object_components().get(&id)?;
+    Some(Api {
+        data,
+        embedded: object_embedded(db, res, id),
+    })
+}
+
+fn object_embedded(db: &TypedDatabase<'_>, res: &LuRes, id: i32) -> ObjectEmbedded {
+    let (name, description) = match db.get_object_name_desc(id) {
+        Some((name, desc)) => (Some(name), Some(desc).filter(|d| !d.is_empty())),
+        None => (None, None),
+    };
+    ObjectEmbedded {
+        name,
+        description,
+        image: res.object_image(db, id),
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct ObjectImage {
+    /// The URL of the render component icon
+    image: String,
+}
+
+/// The icon of an object, for clients that don't need the rest of [`rev_object`]
+pub(super) fn object_image(db: &TypedDatabase<'_>, res: &LuRes, id: i32) -> Option<ObjectImage> {
+    let image = res.object_image(db, id)?;
+    Some(ObjectImage { image })
+}
+
+#[derive(Serialize)]
+pub(super) struct ObjectFullEmbedded<'a, 'b> {
+    /// Names of the missions that reward this object
+    #[serde(rename = "Missions")]
+    missions: BTreeMap<i32, String>,
+    /// Names of the item sets that contain this object
+    #[serde(rename = "ItemSets")]
+    item_sets: BTreeMap<i32, String>,
+    /// The LOTs that have one of the referencing `ItemComponent`s
+    #[serde(rename = "ItemComponent")]
+    item_component: BTreeMap<i32, &'b [i32]>,
+    /// The LOTs that have one of the referencing `InventoryComponent`s
+    #[serde(rename = "InventoryComponent")]
+    inventory_component: BTreeMap<i32, &'b [i32]>,
+    /// Names of all LOTs in the component maps above
+    #[serde(rename = "Objects")]
+    objects: ObjectsRefAdapter<'a, 'b, Vec<i32>>,
+}
+
+type ObjectFullApiResult<'a, 'b> = Api<&'b ObjectsUse, ObjectFullEmbedded<'a, 'b>>;
+
+/// The LOTs for each of `ids`, as components of type `type_id`
+fn component_lots<'b>(
+    rev: &'b ReverseLookup,
+    type_id: i32,
+    ids: impl Iterator<Item = &'b i32>,
+) -> BTreeMap<i32, &'b [i32]> {
+    let components = match rev.component_use().ty(type_id) {
+        Some(cu) => &cu.components,
+        None => return BTreeMap::new(),
+    };
+    ids.filter_map(|id| components.get(id).map(|c| (*id, &c.lots[..])))
+        .collect()
+}
+
+/// Everything that references the object `id`, with names for the referenced IDs
+pub(super) fn rev_object_full<'a, 'b>(
+    db: &'b TypedDatabase<'a>,
+    rev: &'b ReverseLookup,
+    loc: &LocaleRoot,
+    id: i32,
+) -> Option<ObjectFullApiResult<'a, 'b>> {
+    let data = rev.objects().rev.get(&id)?;
+
+    let missions = mission_names(db, loc, &data.missions.reward_items);
+    let item_sets = data
+        .item_sets
+        .iter()
+        .filter_map(|&set_id| {
+            let rank = db.item_sets.get_data(set_id).map_or(0, |s| s.kit_rank);
+            loc.get_item_set_name(rank, set_id)
+                .map(|name| (set_id, name))
+        })
+        .collect();
+
+    let ic = &data.item_component;
+    let item_component = component_lots(
+        rev,
+        COMPONENT_ID_ITEM,
+        ic.currency_lot
+            .iter()
+            .chain(&ic.commendation_lot)
+            .chain(&ic.subitems),
+    );
+    let inventory_component =
+        component_lots(rev, COMPONENT_ID_INVENTORY, data.inventory_component.iter());
+    let lots: BTreeSet<i32> = item_component
+        .values()
+        .chain(inventory_component.values())
+        .flat_map(|lots| lots.iter().copied())
+        .collect();
+
+    Some(Api {
+        data,
+        embedded: ObjectFullEmbedded {
+            missions,
+            item_sets,
+            item_component,
+            inventory_component,
+            objects: ObjectsRefAdapter::new(&db.objects, lots.into_iter().collect()),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edit_distance, fuzzy_rank};
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("dragn", "dragon"), 1);
+        assert_eq!(edit_distance("dragon", "dragon"), 0);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("äb", "ab"), 1);
+    }
+
+    #[test]
+    fn test_fuzzy_rank() {
+        assert_eq!(fuzzy_rank("Dragon", "dragn", 1), Some(1));
+        assert_eq!(fuzzy_rank("Fire Dragon", "dragn", 1), Some(1));
+        assert_eq!(fuzzy_rank("Wagon", "dragn", 1), None);
+    }
+}
@@ -0,0 +1,29 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use super::{
+    data::{ComponentUse, PreconditionRev, COMPONENT_ID_ITEM},
+    Api, ReverseLookup,
+};
+use crate::api::adapter::Filtered;
+
+#[derive(Serialize)]
+pub struct PreconditionByIdEmbedded<'b> {
+    #[serde(rename = "ItemComponent")]
+    item_components: Option<Filtered<'b, BTreeMap<i32, ComponentUse>, &'b BTreeSet<i32>>>,
+}
+
+pub(crate) fn precondition_by_id(
+    rev: &ReverseLookup,
+    id: i32,
+) -> Option<Api<&PreconditionRev, PreconditionByIdEmbedded<'_>>> {
+    rev.preconditions().get(&id).map(|data| Api {
+        data,
+        embedded: PreconditionByIdEmbedded {
+            item_components: rev
+                .component_use()
+                .filter(COMPONENT_ID_ITEM, &data.item_components.req_precondition),
+        },
+    })
+}
@@ -1,10 +1,12 @@
+use super::data::Index;
 use crate::api::PercentDecoded;
-use std::str;
+use std::{fmt, str::FromStr, str::Split};
 
-pub(super) static REV_APIS: &[&str; 11] = &[
+pub(super) static REV_APIS: &[&str; 13] = &[
     "activity",
     "behaviors",
     "component_types",
+    "emotes",
     "faction",
     "gate_version",
     "loot_table_index",
@@ -12,18 +14,66 @@ pub(super) static REV_APIS: &[&str; 11] = &[
     "missions",
     "objects",
     "object_types",
+    "preconditions",
     "skill_ids",
 ];
 
+/// Other spellings of the [`REV_APIS`] that are still accepted, by canonical name
+///
+/// A request that uses an alias gets a `Deprecation` header with a `Link` to the
+/// canonical path, see [`canonical_path`].
+static ALIASES: &[(&str, &[&str])] = &[
+    ("activity", &["activities"]),
+    ("component_types", &["component-types"]),
+    ("faction", &["factions"]),
+    ("gate_version", &["gate-versions"]),
+    ("mission_types", &["mission-types"]),
+];
+
+/// When the [`ALIASES`] were deprecated, in seconds since the Unix epoch
+///
+/// This is 2026-10-16, when one spelling per API became canonical.
+const ALIASES_DEPRECATED_AT: u64 = 1_792_108_800;
+
+/// The value of the `Deprecation` header for the [`ALIASES`], which is also in the docs
+pub(crate) fn alias_deprecation() -> String {
+    format!("@{}", ALIASES_DEPRECATED_AT)
+}
+
+/// The canonical name of the API that `alias` is another spelling of
+fn canonical_name(alias: &str) -> Option<&'static str> {
+    ALIASES
+        .iter()
+        .find(|(_, aliases)| aliases.contains(&alias))
+        .map(|&(name, _)| name)
+}
+
+/// The path relative to the API root with the canonical name, if `path` uses an alias
+///
+/// `path` is the path of a request below the API, e.g. `/v0/rev/activities/1`,
+/// which results in `v0/rev/activity/1`.
+pub(crate) fn canonical_path(path: &str) -> Option<String> {
+    let rest = path.trim_start_matches('/').strip_prefix("v0/rev/")?;
+    let mut parts = rest.splitn(2, '/');
+    let name = canonical_name(parts.next()?)?;
+    Some(match parts.next() {
+        Some(tail) => format!("v0/rev/{}/{}", name, tail),
+        None => format!("v0/rev/{}", name),
+    })
+}
+
 #[derive(Debug)]
 pub(crate) enum Route {
     Base,
     Activities,
     ActivityById(i32),
     BehaviorById(i32),
+    BehaviorTreeById(i32),
     ComponentTypes,
     ComponentTypeById(i32),
     ComponentTypeByIdAndCid(i32, i32),
+    Emotes,
+    EmoteById(i32),
     Factions,
     FactionById(i32),
     LootTableIndexById(i32),
@@ -36,286 +86,509 @@ pub(crate) enum Route {
     MissionTypeBySubTy(PercentDecoded, PercentDecoded),
     Objects,
     ObjectById(i32),
+    ObjectFullById(i32),
+    ObjectComponentsById(i32),
+    ObjectImageById(i32),
+    ObjectsSearch,
     ObjectsSearchIndex,
     ObjectTypes,
+    ObjectTypeCounts,
     ObjectTypeByName(PercentDecoded),
+    Preconditions,
+    PreconditionById(i32),
+    Skills,
     SkillById(i32),
     SkillCooldownGroups,
     SkillCooldownGroupById(i32),
     GateVersions,
     GateVersionByName(PercentDecoded),
+    GateVersionsDiff,
+}
+
+/// Why a path below `/rev` is not a [`Route`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RouteError {
+    /// The first segment is not one of the [`REV_APIS`]
+    UnknownApi(String),
+    /// A segment is missing
+    Missing {
+        after: &'static str,
+        expected: &'static str,
+    },
+    /// A segment could not be parsed
+    Invalid {
+        after: &'static str,
+        expected: &'static str,
+        found: String,
+    },
+    /// There are more segments than the route allows
+    Unexpected { after: &'static str, found: String },
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownApi(found) => write!(
+                f,
+                "unknown API `{}`, expected one of {}",
+                found,
+                REV_APIS.join(", ")
+            ),
+            Self::Missing { after, expected } => {
+                write!(f, "expected {} after /rev/{}", expected, after)
+            }
+            Self::Invalid {
+                after,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected {} after /rev/{}, found `{}`",
+                expected, after, found
+            ),
+            Self::Unexpected { after, found } => {
+                write!(f, "unexpected `{}` after /rev/{}", found, after)
+            }
+        }
+    }
+}
+
+const ID: &str = "an integer id";
+
+/// Parse a path segment, e.g. an ID
+fn parse<T: FromStr>(
+    key: &str,
+    after: &'static str,
+    expected: &'static str,
+) -> Result<T, RouteError> {
+    if key.is_empty() {
+        return Err(RouteError::Missing { after, expected });
+    }
+    key.parse().map_err(|_| RouteError::Invalid {
+        after,
+        expected,
+        found: key.to_owned(),
+    })
+}
+
+fn unexpected(after: &'static str, found: &str) -> RouteError {
+    RouteError::Unexpected {
+        after,
+        found: found.to_owned(),
+    }
+}
+
+/// There must be no further segments
+fn no_more(parts: &mut Split<'_, char>, after: &'static str) -> Result<(), RouteError> {
+    match parts.next() {
+        None => Ok(()),
+        Some(found) => Err(unexpected(after, found)),
+    }
 }
 
 impl Route {
-    fn lti_from_parts(mut parts: str::Split<'_, char>) -> Result<Self, ()> {
-        match parts.next() {
-            Some(key) => match key.parse() {
-                Ok(id) => match parts.next() {
-                    None => Ok(Self::LootTableIndexById(id)),
-                    Some("") => match parts.next() {
-                        None => Ok(Self::LootTableIndexById(id)),
-                        Some(_) => Err(()),
-                    },
-                    _ => Err(()),
-                },
-                Err(_) => Err(()),
-            },
-            _ => Err(()),
+    /// The indices of the [`ReverseLookup`](super::ReverseLookup) that this route reads
+    pub(crate) fn indices(&self) -> &'static [Index] {
+        match self {
+            Self::Base | Self::Skills | Self::ObjectImageById(_) => &[],
+            Self::Activities | Self::ActivityById(_) => &[Index::Activities],
+            Self::BehaviorById(_) | Self::BehaviorTreeById(_) => &[Index::Behaviors],
+            Self::ComponentTypes
+            | Self::ComponentTypeById(_)
+            | Self::ComponentTypeByIdAndCid(_, _) => &[Index::ComponentUse],
+            Self::Emotes | Self::EmoteById(_) => &[Index::Emotes],
+            Self::Factions | Self::FactionById(_) => &[Index::Factions],
+            Self::LootTableIndexById(_) => &[Index::LootTableIndex],
+            Self::LootMatrixByIndex(_) => &[Index::LootMatrixIndex],
+            Self::Missions | Self::MissionById(_) => &[Index::Missions],
+            Self::MissionTypes
+            | Self::MissionTypesFull
+            | Self::MissionTypeByTy(_)
+            | Self::MissionTypeBySubTy(_, _) => &[Index::MissionTypes],
+            Self::Objects
+            | Self::ObjectById(_)
+            | Self::ObjectsSearch
+            | Self::ObjectsSearchIndex => &[Index::Objects],
+            Self::ObjectFullById(_) => &[Index::Objects, Index::ComponentUse],
+            Self::ObjectComponentsById(_) => &[Index::ObjectComponents],
+            Self::ObjectTypes | Self::ObjectTypeCounts | Self::ObjectTypeByName(_) => {
+                &[Index::ObjectTypes]
+            }
+            Self::Preconditions | Self::PreconditionById(_) => &[Index::Preconditions],
+            Self::SkillById(_) => &[Index::SkillIds, Index::MissionTaskUids],
+            Self::SkillCooldownGroups | Self::SkillCooldownGroupById(_) => {
+                &[Index::SkillCooldownGroups]
+            }
+            Self::GateVersions | Self::GateVersionByName(_) | Self::GateVersionsDiff => {
+                &[Index::GateVersions]
+            }
         }
     }
 
-    fn lmi_from_parts(mut parts: str::Split<'_, char>) -> Result<Self, ()> {
+    fn lti_from_parts(mut parts: Split<'_, char>, after: &'static str) -> Result<Self, RouteError> {
         match parts.next() {
-            Some(key) => match key.parse() {
-                Ok(id) => match parts.next() {
-                    None => Ok(Self::LootMatrixByIndex(id)),
-                    Some("") => match parts.next() {
-                        None => Ok(Self::LootMatrixByIndex(id)),
-                        Some(_) => Err(()),
-                    },
-                    _ => Err(()),
-                },
-                Err(_) => Err(()),
-            },
-            _ => Err(()),
+            Some(key) => {
+                let id = parse(key, after, ID)?;
+                no_more(&mut parts, "loot_table_index/:id")?;
+                Ok(Self::LootTableIndexById(id))
+            }
+            None => Err(RouteError::Missing {
+                after,
+                expected: ID,
+            }),
         }
     }
 
-    pub(crate) fn from_parts(mut parts: str::Split<'_, char>) -> Result<Self, ()> {
+    fn lmi_from_parts(mut parts: Split<'_, char>) -> Result<Self, RouteError> {
         match parts.next() {
-            Some("activity" | "activities") => match parts.next() {
-                Some("") => match parts.next() {
-                    None => Ok(Self::Activities),
-                    _ => Err(()),
-                },
-                Some(key) => match parts.next() {
-                    None => match key.parse() {
-                        Ok(id) => Ok(Self::ActivityById(id)),
-                        Err(_) => Err(()),
-                    },
-                    _ => Err(()),
-                },
+            Some(key) => {
+                let index = parse(key, "loot_matrix_index", "an integer index")?;
+                no_more(&mut parts, "loot_matrix_index/:index")?;
+                Ok(Self::LootMatrixByIndex(index))
+            }
+            None => Err(RouteError::Missing {
+                after: "loot_matrix_index",
+                expected: "an integer index",
+            }),
+        }
+    }
+
+    pub(crate) fn from_parts(mut parts: Split<'_, char>) -> Result<Self, RouteError> {
+        let api = parts
+            .next()
+            .map(|name| canonical_name(name).unwrap_or(name));
+        match api {
+            Some("activity") => match parts.next() {
+                Some(key) => {
+                    no_more(&mut parts, "activity/:id")?;
+                    parse(key, "activity", ID).map(Self::ActivityById)
+                }
                 None => Ok(Self::Activities),
             },
             Some("behaviors") => match parts.next() {
-                Some(key) => match key.parse() {
-                    Ok(id) => Ok(Self::BehaviorById(id)),
-                    Err(_) => Err(()),
-                },
-                _ => Err(()),
+                Some(key) => {
+                    let id = parse(key, "behaviors", ID)?;
+                    match parts.next() {
+                        None => Ok(Self::BehaviorById(id)),
+                        Some("tree") => Ok(Self::BehaviorTreeById(id)),
+                        Some(found) => Err(unexpected("behaviors/:id", found)),
+                    }
+                }
+                None => Err(RouteError::Missing {
+                    after: "behaviors",
+                    expected: ID,
+                }),
             },
-            Some("component_types" | "component-types") => match parts.next() {
-                Some("") => match parts.next() {
-                    None => Ok(Self::ComponentTypes),
-                    _ => Err(()),
-                },
-                Some(key) => match key.parse() {
-                    Ok(id) => match parts.next() {
+            Some("component_types") => match parts.next() {
+                Some(key) => {
+                    let id = parse(key, "component_types", ID)?;
+                    match parts.next() {
                         None => Ok(Self::ComponentTypeById(id)),
-                        Some("") => match parts.next() {
-                            Some(_) => Err(()),
-                            None => Ok(Self::ComponentTypeById(id)),
-                        },
-                        Some(key2) => match key2.parse() {
-                            Ok(cid) => match parts.next() {
-                                None => Ok(Self::ComponentTypeByIdAndCid(id, cid)),
-                                Some("") => match parts.next() {
-                                    Some(_) => Err(()),
-                                    None => Ok(Self::ComponentTypeByIdAndCid(id, cid)),
-                                },
-                                Some(_) => Err(()),
-                            },
-                            Err(_) => Err(()),
-                        },
-                    },
-                    Err(_) => Err(()),
-                },
+                        Some(key2) => {
+                            let cid = parse(key2, "component_types/:id", ID)?;
+                            no_more(&mut parts, "component_types/:id/:cid")?;
+                            Ok(Self::ComponentTypeByIdAndCid(id, cid))
+                        }
+                    }
+                }
                 None => Ok(Self::ComponentTypes),
             },
-            Some("faction" | "factions") => match parts.next() {
+            Some("emotes") => match parts.next() {
+                None => Ok(Self::Emotes),
+                Some(key) => {
+                    let id = parse(key, "emotes", ID)?;
+                    no_more(&mut parts, "emotes/:id")?;
+                    Ok(Self::EmoteById(id))
+                }
+            },
+            Some("faction") => match parts.next() {
                 None => Ok(Self::Factions),
-                Some("") => match parts.next() {
-                    None => Ok(Self::Factions),
-                    Some(_) => Err(()),
-                },
-                Some(key) => match key.parse() {
-                    Ok(id) => match parts.next() {
-                        None => Ok(Self::FactionById(id)),
-                        Some("") => match parts.next() {
-                            Some(_) => Err(()),
-                            None => Ok(Self::FactionById(id)),
-                        },
-                        Some(_) => Err(()),
-                    },
-                    Err(_) => Err(()),
-                },
+                Some(key) => {
+                    let id = parse(key, "faction", ID)?;
+                    no_more(&mut parts, "faction/:id")?;
+                    Ok(Self::FactionById(id))
+                }
             },
-            Some("gate_version" | "gate-versions") => match parts.next() {
+            Some("gate_version") => match parts.next() {
                 None => Ok(Self::GateVersions),
-                Some("") => match parts.next() {
-                    None => Ok(Self::GateVersions),
-                    Some(_) => Err(()),
-                },
-                Some(key) => match key.parse() {
-                    Ok(name) => match parts.next() {
-                        None => Ok(Self::GateVersionByName(name)),
-                        Some("") => match parts.next() {
-                            None => Ok(Self::GateVersionByName(name)),
-                            Some(_) => Err(()),
-                        },
-                        Some(_) => Err(()),
-                    },
-                    Err(_) => Err(()),
+                Some("diff") => match parts.next() {
+                    None => Ok(Self::GateVersionsDiff),
+                    Some(found) => Err(unexpected("gate_version/diff", found)),
                 },
+                Some(key) => {
+                    let name = parse(key, "gate_version", "a gate version")?;
+                    no_more(&mut parts, "gate_version/:name")?;
+                    Ok(Self::GateVersionByName(name))
+                }
             },
             Some("loot_matrix_index") => Self::lmi_from_parts(parts),
-            Some("loot_table_index") => Self::lti_from_parts(parts),
+            Some("loot_table_index") => Self::lti_from_parts(parts, "loot_table_index"),
             Some("loot-tables") => match parts.next() {
-                Some("indices") => Self::lti_from_parts(parts),
-                Some(_) => Err(()),
-                None => Err(()),
+                Some("indices") => Self::lti_from_parts(parts, "loot-tables/indices"),
+                Some(found) => Err(unexpected("loot-tables", found)),
+                None => Err(RouteError::Missing {
+                    after: "loot-tables",
+                    expected: "`indices`",
+                }),
             },
-            Some("mission_types" | "mission-types") => Self::mission_types_from_parts(parts),
+            Some("mission_types") => Self::mission_types_from_parts(parts),
             Some("missions") => match parts.next() {
                 None => Ok(Self::Missions),
-                Some("") => match parts.next() {
-                    None => Ok(Self::Missions),
-                    _ => Err(()),
-                },
                 Some("types") => Self::mission_types_from_parts(parts),
-                Some(key) => match key.parse() {
-                    Ok(id) => match parts.next() {
-                        None => Ok(Self::MissionById(id)),
-                        Some("") => match parts.next() {
-                            None => Ok(Self::MissionById(id)),
-                            _ => Err(()),
-                        },
-                        _ => Err(()),
-                    },
-                    Err(_) => Err(()),
-                },
+                Some(key) => {
+                    let id = parse(key, "missions", ID)?;
+                    no_more(&mut parts, "missions/:id")?;
+                    Ok(Self::MissionById(id))
+                }
             },
             Some("objects") => match parts.next() {
                 None => Ok(Self::Objects),
-                Some("") => match parts.next() {
-                    None => Ok(Self::Objects),
-                    Some(_) => Err(()),
-                },
-                Some("search_index" | "search-index") => match parts.next() {
-                    None => Ok(Self::ObjectsSearchIndex),
-                    Some("") => match parts.next() {
-                        None => Ok(Self::ObjectsSearchIndex),
-                        _ => Err(()),
-                    },
-                    Some(_) => Err(()),
-                },
-                Some(key) => match key.parse() {
-                    Ok(lot) => match parts.next() {
+                Some("search") => {
+                    no_more(&mut parts, "objects/search").map(|()| Self::ObjectsSearch)
+                }
+                Some("search_index" | "search-index") => {
+                    no_more(&mut parts, "objects/search_index").map(|()| Self::ObjectsSearchIndex)
+                }
+                Some(key) => {
+                    let lot = parse(key, "objects", ID)?;
+                    match parts.next() {
                         None => Ok(Self::ObjectById(lot)),
-                        Some("") => match parts.next() {
-                            None => Ok(Self::ObjectById(lot)),
-                            Some(_) => Err(()),
+                        Some("full") => match parts.next() {
+                            None => Ok(Self::ObjectFullById(lot)),
+                            Some(found) => Err(unexpected("objects/:id/full", found)),
                         },
-                        Some(_) => Err(()),
-                    },
-                    Err(_) => Err(()),
-                },
+                        Some("components") => no_more(&mut parts, "objects/:id/components")
+                            .map(|()| Self::ObjectComponentsById(lot)),
+                        Some("image") => no_more(&mut parts, "objects/:id/image")
+                            .map(|()| Self::ObjectImageById(lot)),
+                        Some(found) => Err(unexpected("objects/:id", found)),
+                    }
+                }
             },
             Some("object_types") => match parts.next() {
                 None => Ok(Self::ObjectTypes),
-                Some("") => match parts.next() {
-                    None => Ok(Self::ObjectTypes),
-                    _ => Err(()),
-                },
-                Some(key) => match key.parse() {
-                    Ok(ty) => match parts.next() {
-                        None => Ok(Self::ObjectTypeByName(ty)),
-                        Some("") => match parts.next() {
-                            None => Ok(Self::ObjectTypeByName(ty)),
-                            _ => Err(()),
-                        },
-                        Some(_) => Err(()),
-                    },
-                    Err(_) => Err(()),
-                },
+                Some("counts") => {
+                    no_more(&mut parts, "object_types/counts").map(|()| Self::ObjectTypeCounts)
+                }
+                Some(key) => {
+                    let ty = parse(key, "object_types", "an object type")?;
+                    no_more(&mut parts, "object_types/:type")?;
+                    Ok(Self::ObjectTypeByName(ty))
+                }
             },
+            Some("preconditions") => match parts.next() {
+                None => Ok(Self::Preconditions),
+                Some(key) => {
+                    let id = parse(key, "preconditions", ID)?;
+                    no_more(&mut parts, "preconditions/:id")?;
+                    Ok(Self::PreconditionById(id))
+                }
+            },
+            // `/skills` is the documented path of the listing, not a deprecated alias
             Some("skill_ids" | "skills") => match parts.next() {
                 Some("cooldowngroups") => match parts.next() {
                     None => Ok(Self::SkillCooldownGroups),
-                    Some("") => match parts.next() {
-                        None => Ok(Self::SkillCooldownGroups),
-                        Some(_) => Err(()),
-                    },
-                    Some(key) => match key.parse() {
-                        Ok(id) => match parts.next() {
-                            None => Ok(Self::SkillCooldownGroupById(id)),
-                            Some("") => match parts.next() {
-                                None => Ok(Self::SkillCooldownGroupById(id)),
-                                Some(_) => Err(()),
-                            },
-                            Some(_) => Err(()),
-                        },
-                        Err(_) => Err(()),
-                    },
+                    Some(key) => {
+                        let id = parse(key, "skill_ids/cooldowngroups", ID)?;
+                        no_more(&mut parts, "skill_ids/cooldowngroups/:id")?;
+                        Ok(Self::SkillCooldownGroupById(id))
+                    }
                 },
-                Some(key) => match key.parse() {
-                    Ok(id) => match parts.next() {
-                        None => Ok(Self::SkillById(id)),
-                        Some("") => match parts.next() {
-                            None => Ok(Self::SkillById(id)),
-                            Some(_) => Err(()),
-                        },
-                        Some(_) => Err(()),
-                    },
-                    Err(_) => Err(()),
-                },
-                None => Err(()),
-            },
-            Some("") => match parts.next() {
-                None => Ok(Self::Base),
-                _ => Err(()),
+                Some(key) => {
+                    let id = parse(key, "skill_ids", ID)?;
+                    no_more(&mut parts, "skill_ids/:id")?;
+                    Ok(Self::SkillById(id))
+                }
+                None => Ok(Self::Skills),
             },
             None => Ok(Self::Base),
-            _ => Err(()),
+            Some(found) => Err(RouteError::UnknownApi(found.to_owned())),
         }
     }
 
-    fn mission_types_from_parts(mut parts: str::Split<char>) -> Result<Route, ()> {
+    fn mission_types_from_parts(mut parts: Split<char>) -> Result<Route, RouteError> {
         match parts.next() {
             None => Ok(Self::MissionTypes),
-            Some("") => match parts.next() {
-                None => Ok(Self::MissionTypes),
-                Some(_) => Err(()),
-            },
-            Some("full") => match parts.next() {
-                None => Ok(Self::MissionTypesFull),
-                Some("") => match parts.next() {
-                    None => Ok(Self::MissionTypesFull),
-                    Some(_) => Err(()),
-                },
-                Some(_) => Err(()),
-            },
-            Some(key) => match key.parse() {
-                Ok(d_type) => match parts.next() {
+            Some("full") => {
+                no_more(&mut parts, "mission_types/full").map(|()| Self::MissionTypesFull)
+            }
+            Some(key) => {
+                let d_type = parse(key, "mission_types", "a mission type")?;
+                match parts.next() {
                     None => Ok(Self::MissionTypeByTy(d_type)),
-                    Some("") => match parts.next() {
-                        None => Ok(Self::MissionTypeByTy(d_type)),
-                        Some(_) => Err(()),
-                    },
-                    Some(key2) => match key2.parse() {
-                        Ok(d_subtype) => match parts.next() {
-                            None => Ok(Self::MissionTypeBySubTy(d_type, d_subtype)),
-                            Some("") => match parts.next() {
-                                None => Ok(Self::MissionTypeBySubTy(d_type, d_subtype)),
-                                Some(_) => Err(()),
-                            },
-                            Some(_) => Err(()),
-                        },
-                        Err(_) => Err(()),
-                    },
-                },
-                Err(_) => Err(()),
-            },
+                    Some(key2) => {
+                        let d_subtype = parse(key2, "mission_types/:type", "a mission subtype")?;
+                        no_more(&mut parts, "mission_types/:type/:subtype")?;
+                        Ok(Self::MissionTypeBySubTy(d_type, d_subtype))
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{canonical_path, Route, RouteError, ID};
+
+    fn parse(path: &str) -> Result<Route, RouteError> {
+        Route::from_parts(path.split('/'))
+    }
+
+    #[test]
+    fn test_routes() {
+        let cases = [
+            ("activity", "Activities"),
+            ("activities/1", "ActivityById(1)"),
+            ("behaviors/1", "BehaviorById(1)"),
+            ("behaviors/1/tree", "BehaviorTreeById(1)"),
+            ("component_types", "ComponentTypes"),
+            ("component-types/2", "ComponentTypeById(2)"),
+            ("component_types/2/7", "ComponentTypeByIdAndCid(2, 7)"),
+            ("emotes", "Emotes"),
+            ("emotes/11", "EmoteById(11)"),
+            ("factions", "Factions"),
+            ("faction/3", "FactionById(3)"),
+            ("gate_version", "GateVersions"),
+            ("gate-versions/diff", "GateVersionsDiff"),
+            (
+                "gate_version/live",
+                r#"GateVersionByName(PercentDecoded("live"))"#,
+            ),
+            ("loot_matrix_index/4", "LootMatrixByIndex(4)"),
+            ("loot_table_index/5", "LootTableIndexById(5)"),
+            ("loot-tables/indices/5", "LootTableIndexById(5)"),
+            ("missions", "Missions"),
+            ("missions/-6", "MissionById(-6)"),
+            ("missions/types", "MissionTypes"),
+            ("mission_types/full", "MissionTypesFull"),
+            (
+                "mission-types/Achievement",
+                r#"MissionTypeByTy(PercentDecoded("Achievement"))"#,
+            ),
+            (
+                "mission_types/Achievement/Collection%20Items",
+                r#"MissionTypeBySubTy(PercentDecoded("Achievement"), PercentDecoded("Collection Items"))"#,
+            ),
+            ("objects", "Objects"),
+            ("objects/7", "ObjectById(7)"),
+            ("objects/7/full", "ObjectFullById(7)"),
+            ("objects/7/components", "ObjectComponentsById(7)"),
+            ("objects/7/image", "ObjectImageById(7)"),
+            ("objects/search", "ObjectsSearch"),
+            ("objects/search-index", "ObjectsSearchIndex"),
+            ("object_types", "ObjectTypes"),
+            ("object_types/counts", "ObjectTypeCounts"),
+            (
+                "object_types/Enemies",
+                r#"ObjectTypeByName(PercentDecoded("Enemies"))"#,
+            ),
+            ("preconditions", "Preconditions"),
+            ("preconditions/8", "PreconditionById(8)"),
+            ("skills", "Skills"),
+            ("skills/9", "SkillById(9)"),
+            ("skill_ids/cooldowngroups", "SkillCooldownGroups"),
+            ("skill_ids/cooldowngroups/10", "SkillCooldownGroupById(10)"),
+        ];
+        for (path, expected) in cases {
+            let route = parse(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            assert_eq!(format!("{:?}", route), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_route_edge_cases() {
+        let missing = |after, expected| RouteError::Missing { after, expected };
+        let invalid = |after, expected, found: &str| RouteError::Invalid {
+            after,
+            expected,
+            found: found.to_owned(),
+        };
+        let unexpected = |after, found: &str| RouteError::Unexpected {
+            after,
+            found: found.to_owned(),
+        };
+        let cases = [
+            // an empty segment, i.e. a double or trailing slash
+            ("objects/", missing("objects", ID)),
+            ("objects//full", missing("objects", ID)),
+            ("component_types/2/", missing("component_types/:id", ID)),
+            ("loot-tables", missing("loot-tables", "`indices`")),
+            ("loot-tables/indices", missing("loot-tables/indices", ID)),
+            ("objects/x", invalid("objects", ID, "x")),
+            ("objects/99999999999", invalid("objects", ID, "99999999999")),
+            (
+                "component_types/2/x",
+                invalid("component_types/:id", ID, "x"),
+            ),
+            (
+                "loot_matrix_index/1.5",
+                invalid("loot_matrix_index", "an integer index", "1.5"),
+            ),
+            ("activities/1/x", unexpected("activity/:id", "x")),
+            (
+                "component_types/2/7/1",
+                unexpected("component_types/:id/:cid", "1"),
+            ),
+            ("gate_version/diff/x", unexpected("gate_version/diff", "x")),
+            ("loot-tables/x", unexpected("loot-tables", "x")),
+            (
+                "loot_table_index/5/x",
+                unexpected("loot_table_index/:id", "x"),
+            ),
+            ("objects/search/1", unexpected("objects/search", "1")),
+            (
+                "mission_types/A/B/C",
+                unexpected("mission_types/:type/:subtype", "C"),
+            ),
+            ("", RouteError::UnknownApi(String::new())),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(parse(path).unwrap_err(), expected, "{}", path);
         }
     }
+
+    #[test]
+    fn test_route_errors() {
+        assert!(matches!(
+            parse("behaviors/4/tree"),
+            Ok(Route::BehaviorTreeById(4))
+        ));
+        assert_eq!(
+            parse("behaviors/abc").unwrap_err().to_string(),
+            "expected an integer id after /rev/behaviors, found `abc`"
+        );
+        assert_eq!(
+            parse("behaviors").unwrap_err().to_string(),
+            "expected an integer id after /rev/behaviors"
+        );
+        assert_eq!(
+            parse("objects/1/fulll").unwrap_err().to_string(),
+            "unexpected `fulll` after /rev/objects/:id"
+        );
+        assert_eq!(
+            parse("missions/1/x").unwrap_err().to_string(),
+            "unexpected `x` after /rev/missions/:id"
+        );
+        assert!(matches!(
+            parse("mission"),
+            Err(RouteError::UnknownApi(api)) if api == "mission"
+        ));
+    }
+
+    #[test]
+    fn test_canonical_path() {
+        assert_eq!(
+            canonical_path("/v0/rev/activities/1").as_deref(),
+            Some("v0/rev/activity/1")
+        );
+        assert_eq!(
+            canonical_path("/v0/rev/component-types/2/7").as_deref(),
+            Some("v0/rev/component_types/2/7")
+        );
+        assert_eq!(canonical_path("/v0/rev/activity/1"), None);
+        assert_eq!(canonical_path("/v0/rev/skills"), None);
+        assert_eq!(canonical_path("/v0/rev/loot-tables/indices/5"), None);
+        assert_eq!(canonical_path("/v0/tables/activities"), None);
+    }
 }
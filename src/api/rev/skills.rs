@@ -1,5 +1,15 @@
-use super::{common::MissionTasks, data::SkillIdLookup, Api, ReverseLookup};
-use crate::api::adapter::{AdapterLayout, I32Slice};
+use std::{collections::BTreeMap, convert::TryFrom};
+
+use super::{
+    common::MissionTasks,
+    data::{CooldownStats, SkillCooldownGroup, SkillIdLookup},
+    Api, ReverseLookup,
+};
+use crate::{
+    api::adapter::{AdapterLayout, I32Slice},
+    data::locale::{LocaleRoot, LocaleRootInner},
+};
+use latin1str::Latin1Str;
 use paradox_typed_db::{columns::MissionTasksColumn, TypedDatabase};
 use serde::Serialize;
 
@@ -17,9 +27,9 @@ pub(super) fn rev_skill_id<'a, 'b>(
     rev: &'b ReverseLookup,
     skill_id: i32,
 ) -> Option<SkillApiResult<'a, 'b>> {
-    let data = rev.skill_ids.get(&skill_id)?;
+    let data = rev.skill_ids().get(&skill_id)?;
     let mission_tasks = MissionTasks {
-        index: &rev.mission_task_uids,
+        index: rev.mission_task_uids(),
         keys: I32Slice(&data.mission_tasks[..]),
         table: &db.mission_tasks,
         id_col: db.mission_tasks.get_col(MissionTasksColumn::Uid).unwrap(),
@@ -30,3 +40,125 @@ pub(super) fn rev_skill_id<'a, 'b>(
         embedded: SkillIDEmbedded { mission_tasks },
     })
 }
+
+#[derive(Clone, Serialize)]
+pub(super) struct SkillRefData<'b> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<&'b Latin1Str>,
+}
+
+#[derive(Serialize)]
+struct SkillName<'b> {
+    id: i32,
+    name: Option<String>,
+    icon: Option<&'b Latin1Str>,
+}
+
+/// The IDs of all skills with their localized name and icon, for `/rev/skills`
+pub(super) struct SkillNames<'a, 'b> {
+    db: &'b TypedDatabase<'a>,
+    loc: &'b LocaleRootInner,
+}
+
+impl<'a, 'b> SkillNames<'a, 'b> {
+    pub fn new(db: &'b TypedDatabase<'a>, loc: &'b LocaleRoot) -> Self {
+        Self { db, loc: &loc.root }
+    }
+}
+
+impl<'a, 'b> Serialize for SkillNames<'a, 'b> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let keys = self.loc.keys();
+        let skills = self.loc.node().get_str(keys.skill_behavior);
+        let mut rows: Vec<_> = self
+            .db
+            .skills
+            .row_iter()
+            .map(|skill| (skill.skill_id(), skill.skill_icon()))
+            .collect();
+        rows.sort_unstable_by_key(|&(id, _)| id);
+        serializer.collect_seq(rows.into_iter().map(|(id, icon_id)| {
+            let name = u32::try_from(id)
+                .ok()
+                .and_then(|id| skills.as_ref()?.get_int(id))
+                .and_then(|skill| skill.get_str(keys.name))
+                .and_then(|node| node.value().map(|v| v.to_string()));
+            let icon = icon_id.and_then(|icon_id| self.db.get_icon_path(icon_id));
+            SkillName { id, name, icon }
+        }))
+    }
+}
+
+#[derive(Serialize)]
+struct SkillCooldownGroupRef<'b> {
+    id: i32,
+    #[serde(flatten)]
+    cooldown: Option<&'b CooldownStats>,
+}
+
+/// The IDs of all cooldown groups with their cooldowns, for `?embed=cooldowns`
+pub(super) struct SkillCooldownGroups<'b>(&'b BTreeMap<i32, SkillCooldownGroup>);
+
+impl<'b> SkillCooldownGroups<'b> {
+    pub fn new(rev: &'b ReverseLookup) -> Self {
+        Self(rev.skill_cooldown_groups())
+    }
+}
+
+impl<'b> Serialize for SkillCooldownGroups<'b> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_seq(self.0.iter().map(|(&id, group)| SkillCooldownGroupRef {
+            id,
+            cooldown: group.cooldown.as_ref(),
+        }))
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub(super) struct SkillCooldownGroupEmbedded<'b> {
+    #[serde(rename = "SkillBehavior")]
+    skills: BTreeMap<i32, SkillRefData<'b>>,
+}
+
+type SkillCooldownGroupApiResult<'b> = Api<&'b SkillCooldownGroup, SkillCooldownGroupEmbedded<'b>>;
+
+pub(super) fn rev_skill_cooldown_group<'a, 'b>(
+    db: &'b TypedDatabase<'a>,
+    rev: &'b ReverseLookup,
+    loc: &LocaleRoot,
+    id: i32,
+) -> Option<SkillCooldownGroupApiResult<'b>> {
+    let data = rev.skill_cooldown_groups().get(&id)?;
+    let skills = data
+        .skills
+        .iter()
+        .map(|&skill_id| {
+            let (name, description) = loc.get_skill_name_desc(skill_id);
+            let icon = db
+                .skills
+                .get_data(skill_id)
+                .and_then(|skill| skill.skill_icon)
+                .and_then(|icon_id| db.get_icon_path(icon_id));
+            let embedded = SkillRefData {
+                name,
+                description,
+                icon,
+            };
+            (skill_id, embedded)
+        })
+        .collect();
+    Some(Api {
+        data,
+        embedded: SkillCooldownGroupEmbedded { skills },
+    })
+}
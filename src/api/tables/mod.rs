@@ -15,21 +15,57 @@ use hyper::body::Bytes;
 use latin1str::Latin1String;
 use serde::Serialize;
 
-use super::{Accept, ApiResult};
+use super::{Accept, ApiResult, BodyFormat};
 
 mod query;
 mod util;
 
+use util::AsRowIter;
+
+/// A list of rows that can also be written as CSV
+pub(super) trait Tabular: Serialize {
+    /// Write a header line with the column names, then one line per row
+    fn write_csv(&self, out: &mut dyn fmt::Write) -> fmt::Result;
+}
+
 #[derive(Serialize)]
 pub(super) struct TableDef<'a> {
     name: Cow<'a, str>,
     columns: Vec<TableCol<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bucket_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    row_count: Option<usize>,
 }
 
 #[derive(Serialize)]
 struct TableCol<'a> {
     name: Cow<'a, str>,
     data_type: ValueType,
+    /// The first column is the one that rows are hashed by
+    is_primary_key: bool,
+    /// Whether any row has a `NULL` in this column.
+    ///
+    /// The FDB has no schema for this, so it's only set when all rows were scanned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nullable: Option<bool>,
+}
+
+/// How the rows of a table are spread over its hash buckets
+#[derive(Serialize)]
+pub(super) struct TableStats<'a> {
+    name: Cow<'a, str>,
+    bucket_count: usize,
+    row_count: usize,
+    empty_buckets: usize,
+    min_rows_per_bucket: usize,
+    max_rows_per_bucket: usize,
+    avg_rows_per_bucket: f64,
+}
+
+#[derive(Serialize)]
+struct RowCount {
+    count: usize,
 }
 
 pub(super) fn tables_json(db: Database) -> Result<Vec<Cow<'_, str>>, CastError> {
@@ -44,28 +80,114 @@ pub(super) fn tables_json(db: Database) -> Result<Vec<Cow<'_, str>>, CastError>
 pub(super) fn table_def_json<'a>(
     db: Database<'a>,
     name: &str,
+) -> Result<Option<TableDef<'a>>, CastError> {
+    table_def(db, name, false)
+}
+
+/// Like [`table_def_json`], but scans all rows for nullability and counts
+pub(super) fn table_columns_json<'a>(
+    db: Database<'a>,
+    name: &str,
+) -> Result<Option<TableDef<'a>>, CastError> {
+    table_def(db, name, true)
+}
+
+fn table_def<'a>(
+    db: Database<'a>,
+    name: &str,
+    scan: bool,
 ) -> Result<Option<TableDef<'a>>, CastError> {
     let tables = db.tables()?;
     if let Some(table) = tables.by_name(name) {
         let table = table?;
         let name = table.name();
-        let columns: Vec<_> = table
+        let mut columns: Vec<_> = table
             .column_iter()
-            .map(|col| TableCol {
+            .enumerate()
+            .map(|(index, col)| TableCol {
                 name: col.name(),
                 data_type: col.value_type(),
+                is_primary_key: index == 0,
+                nullable: None,
             })
             .collect();
-        Ok(Some(TableDef { name, columns }))
+        if !scan {
+            return Ok(Some(TableDef {
+                name,
+                columns,
+                bucket_count: None,
+                row_count: None,
+            }));
+        }
+
+        let bucket_count = table.bucket_count();
+        let mut row_count = 0;
+        let mut nullable = vec![false; columns.len()];
+        for bucket in (0..bucket_count).filter_map(|index| table.bucket_at(index)) {
+            for row in bucket.row_iter() {
+                row_count += 1;
+                for (index, field) in row.field_iter().enumerate() {
+                    if matches!(field, Value::Nothing) {
+                        nullable[index] = true;
+                    }
+                }
+            }
+        }
+        for (col, nullable) in columns.iter_mut().zip(nullable) {
+            col.nullable = Some(nullable);
+        }
+        Ok(Some(TableDef {
+            name,
+            columns,
+            bucket_count: Some(bucket_count),
+            row_count: Some(row_count),
+        }))
     } else {
         Ok(None)
     }
 }
 
+/// Count the rows in every bucket of a table
+pub(super) fn table_stats_json<'a>(
+    db: Database<'a>,
+    name: &str,
+) -> Result<Option<TableStats<'a>>, CastError> {
+    let tables = db.tables()?;
+    let table = match tables.by_name(name) {
+        Some(table) => table?,
+        None => return Ok(None),
+    };
+    let bucket_count = table.bucket_count();
+    let mut stats = TableStats {
+        name: table.name(),
+        bucket_count,
+        row_count: 0,
+        empty_buckets: 0,
+        min_rows_per_bucket: usize::MAX,
+        max_rows_per_bucket: 0,
+        avg_rows_per_bucket: 0.0,
+    };
+    for index in 0..bucket_count {
+        let rows = table.bucket_at(index).map_or(0, |b| b.row_iter().count());
+        stats.row_count += rows;
+        if rows == 0 {
+            stats.empty_buckets += 1;
+        }
+        stats.min_rows_per_bucket = stats.min_rows_per_bucket.min(rows);
+        stats.max_rows_per_bucket = stats.max_rows_per_bucket.max(rows);
+    }
+    if bucket_count == 0 {
+        stats.min_rows_per_bucket = 0;
+    } else {
+        stats.avg_rows_per_bucket = stats.row_count as f64 / bucket_count as f64;
+    }
+    Ok(Some(stats))
+}
+
 pub(super) fn table_all_get<'a>(
     db: Database<'a>,
     name: &str,
-) -> Result<Option<impl Serialize + 'a>, CastError> {
+) -> Result<Option<impl Tabular + 'a>, CastError> {
     let tables = db.tables()?;
     let table = tables.by_name(name).transpose()?;
 
@@ -78,12 +200,13 @@ pub(super) fn table_all_get<'a>(
 pub(super) async fn table_all_query<'a, B>(
     db: Database<'a>,
     accept: Accept,
+    format: BodyFormat,
     name: &str,
     body: B,
 ) -> ApiResult
 where
     B: http_body::Body<Data = Bytes> + Unpin,
-    B::Error: fmt::Display,
+    B::Error: Into<super::BoxError>,
 {
     let tables = db.tables()?;
     let Some(table) = tables.by_name(name).transpose()? else {
@@ -93,13 +216,13 @@ where
     let pk_col = table
         .column_at(0)
         .expect("Tables must have at least 1 column");
-    let bytes = match hyper::body::to_bytes(body).await {
+    let bytes = match super::read_body(accept, body).await {
         Ok(b) => b,
-        Err(e) => return super::reply_400(accept, "Failed to aggregate query body", e),
+        Err(r) => return r,
     };
 
     let ty = pk_col.value_type();
-    let _req = match query::TableQuery::new(ty, &bytes) {
+    let _req = match query::TableQuery::new(format, ty, &bytes) {
         Ok(v) => v,
         Err(e) => return super::reply_400(accept, "Failed to parse query body", e),
     };
@@ -107,13 +230,48 @@ where
     let names = table.column_iter().map(|c| c.name()).collect::<Vec<_>>();
     let to_cols = util::PartialColValIterSpec::new(names, &_req.columns);
 
-    super::reply(
+    super::reply_table(
         accept,
         &util::RowIter::<'a, _, _>::new(util::MultiPKFilterSpec::new(table, _req.pks), to_cols),
         StatusCode::OK,
     )
 }
 
+/// Count the rows of a table, optionally restricted to a comma separated list of primary keys
+pub(super) fn table_count(
+    db: Database,
+    accept: Accept,
+    name: &str,
+    keys: Option<&str>,
+) -> ApiResult {
+    let tables = db.tables()?;
+    let Some(table) = tables.by_name(name).transpose()? else {
+        return Ok(super::reply_404());
+    };
+
+    let count: usize = match keys {
+        None => (0..table.bucket_count())
+            .filter_map(|index| table.bucket_at(index))
+            .map(|bucket| bucket.row_iter().count())
+            .sum(),
+        Some(keys) => {
+            let pk_col = table
+                .column_at(0)
+                .expect("Tables must have at least 1 column");
+            let pks = match query::ValueSet::from_list(pk_col.value_type(), keys) {
+                Ok(v) => v,
+                Err(ParseError) => {
+                    return super::reply_400(accept, "Failed to parse keys", keys);
+                }
+            };
+            util::MultiPKFilterSpec::new(table, pks)
+                .as_row_iter()
+                .count()
+        }
+    };
+    super::reply(accept, &RowCount { count }, StatusCode::OK)
+}
+
 struct FastContext;
 
 impl Context for FastContext {
@@ -144,12 +302,15 @@ impl FastContext {
             ValueType::Float => Value::Float(v.parse()?),
             ValueType::Text => Value::Text(Latin1String::encode(v).into_owned()),
             ValueType::Boolean => match v {
-                "true" | "1" | "TRUE" => Value::Boolean(true),
-                "false" | "0" | "FALSE" => Value::Boolean(false),
+                "1" => Value::Boolean(true),
+                "0" => Value::Boolean(false),
+                _ if v.eq_ignore_ascii_case("true") => Value::Boolean(true),
+                _ if v.eq_ignore_ascii_case("false") => Value::Boolean(false),
                 _ => return Err(ParseError),
             },
             ValueType::BigInt => Value::BigInt(v.parse()?),
-            ValueType::VarChar => return Err(ParseError),
+            // Parsed like `Text`, but kept as `VarChar` so it compares equal to the row
+            ValueType::VarChar => Value::VarChar(Latin1String::encode(v).into_owned()),
         })
     }
 }
@@ -158,7 +319,7 @@ pub(super) fn table_key_json<'a>(
     db: Database<'a>,
     name: &str,
     key: &str,
-) -> Result<Option<impl Serialize + 'a>, CastError> {
+) -> Result<Option<impl Tabular + 'a>, CastError> {
     let tables = db.tables()?;
     let table = match tables.by_name(name) {
         Some(t) => t?,
@@ -181,3 +342,52 @@ pub(super) fn table_key_json<'a>(
         table.column_iter().map(|c| c.name()).collect::<Vec<_>>(),
     )))
 }
+
+#[cfg(test)]
+mod tests {
+    use assembly_fdb::value::{Value, ValueType};
+
+    use super::FastContext;
+
+    #[test]
+    fn test_parse_as() {
+        let parse = FastContext::parse_as;
+        assert!(matches!(parse("", ValueType::Nothing), Ok(Value::Nothing)));
+        assert!(matches!(
+            parse("-12", ValueType::Integer),
+            Ok(Value::Integer(-12))
+        ));
+        assert!(parse("1.5", ValueType::Integer).is_err());
+        assert!(matches!(parse("1.5", ValueType::Float), Ok(Value::Float(f)) if f == 1.5));
+        assert!(matches!(parse("3", ValueType::Float), Ok(Value::Float(f)) if f == 3.0));
+        assert!(parse("x", ValueType::Float).is_err());
+        assert!(matches!(parse("abc", ValueType::Text), Ok(Value::Text(t)) if t.decode() == "abc"));
+        assert!(
+            matches!(parse("<a/>", ValueType::VarChar), Ok(Value::VarChar(t)) if t.decode() == "<a/>")
+        );
+        assert!(matches!(
+            parse("9000000000", ValueType::BigInt),
+            Ok(Value::BigInt(9000000000))
+        ));
+        assert!(parse("x", ValueType::BigInt).is_err());
+    }
+
+    #[test]
+    fn test_parse_as_boolean() {
+        for v in ["true", "1", "TRUE", "True"] {
+            assert!(matches!(
+                FastContext::parse_as(v, ValueType::Boolean),
+                Ok(Value::Boolean(true))
+            ));
+        }
+        for v in ["false", "0", "FALSE", "False"] {
+            assert!(matches!(
+                FastContext::parse_as(v, ValueType::Boolean),
+                Ok(Value::Boolean(false))
+            ));
+        }
+        for v in ["", "2", "yes", "truee"] {
+            assert!(FastContext::parse_as(v, ValueType::Boolean).is_err());
+        }
+    }
+}
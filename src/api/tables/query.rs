@@ -8,6 +8,11 @@ use assembly_fdb::{
 use latin1str::Latin1String;
 use serde::Deserialize;
 
+use super::{
+    super::{BodyError, BodyFormat},
+    FastContext, ParseError,
+};
+
 pub(super) enum ValueSet {
     /// The NULL value
     Nothing,
@@ -46,6 +51,35 @@ impl ValueSet {
         }
     }
 
+    /// Parse a comma separated list of primary keys of type `ty`
+    pub(super) fn from_list(ty: ValueType, list: &str) -> Result<Self, ParseError> {
+        let keys = list.split(',').map(str::trim).filter(|k| !k.is_empty());
+        Ok(match ty {
+            ValueType::Nothing => ValueSet::Nothing,
+            ValueType::Integer => {
+                ValueSet::Integer(keys.map(str::parse).collect::<Result<_, _>>()?)
+            }
+            ValueType::Float => ValueSet::Float,
+            ValueType::Text => ValueSet::Text(
+                keys.map(Latin1String::encode)
+                    .map(Cow::into_owned)
+                    .collect(),
+            ),
+            ValueType::Boolean => {
+                let (mut _true, mut _false) = (false, false);
+                for key in keys {
+                    match FastContext::parse_as(key, ValueType::Boolean)? {
+                        Value::Boolean(true) => _true = true,
+                        _ => _false = true,
+                    }
+                }
+                ValueSet::Boolean { _true, _false }
+            }
+            ValueType::BigInt => ValueSet::BigInt(keys.map(str::parse).collect::<Result<_, _>>()?),
+            ValueType::VarChar => ValueSet::VarChar,
+        })
+    }
+
     pub fn bucket_set(&self, bucket_count: usize) -> BTreeSet<usize> {
         match self {
             ValueSet::Integer(s) => s
@@ -88,21 +122,24 @@ pub(super) struct TableQuery<'req_body, PKSet> {
 
 impl<'req> TableQuery<'req, ValueSet> {
     fn de<T: Default + Deserialize<'req>>(
+        format: BodyFormat,
         body: &'req [u8],
         f: impl FnOnce(T) -> ValueSet,
-    ) -> Result<Self, serde_json::Error> {
-        serde_json::from_slice::<TableQuery<'req, T>>(body).map(|tq| TableQuery {
-            pks: f(tq.pks),
-            columns: tq.columns,
-        })
+    ) -> Result<Self, BodyError> {
+        format
+            .deserialize::<TableQuery<'req, T>>(body)
+            .map(|tq| TableQuery {
+                pks: f(tq.pks),
+                columns: tq.columns,
+            })
     }
 
-    pub fn new(ty: ValueType, body: &'req [u8]) -> Result<Self, serde_json::Error> {
+    pub fn new(format: BodyFormat, ty: ValueType, body: &'req [u8]) -> Result<Self, BodyError> {
         match ty {
-            ValueType::Nothing => Self::de::<()>(body, |()| ValueSet::Nothing),
-            ValueType::Integer => Self::de::<BTreeSet<i32>>(body, ValueSet::Integer),
-            ValueType::Float => Self::de::<()>(body, |()| ValueSet::Float),
-            ValueType::Text => Self::de::<BTreeSet<&'req str>>(body, |s| {
+            ValueType::Nothing => Self::de::<()>(format, body, |()| ValueSet::Nothing),
+            ValueType::Integer => Self::de::<BTreeSet<i32>>(format, body, ValueSet::Integer),
+            ValueType::Float => Self::de::<()>(format, body, |()| ValueSet::Float),
+            ValueType::Text => Self::de::<BTreeSet<&'req str>>(format, body, |s| {
                 ValueSet::Text(
                     s.into_iter()
                         .map(Latin1String::encode)
@@ -110,12 +147,12 @@ impl<'req> TableQuery<'req, ValueSet> {
                         .collect(),
                 )
             }),
-            ValueType::Boolean => Self::de::<BTreeSet<bool>>(body, |s| ValueSet::Boolean {
+            ValueType::Boolean => Self::de::<BTreeSet<bool>>(format, body, |s| ValueSet::Boolean {
                 _true: s.contains(&true),
                 _false: s.contains(&false),
             }),
-            ValueType::BigInt => Self::de::<BTreeSet<i64>>(body, ValueSet::BigInt),
-            ValueType::VarChar => Self::de::<()>(body, |()| ValueSet::VarChar),
+            ValueType::BigInt => Self::de::<BTreeSet<i64>>(format, body, ValueSet::BigInt),
+            ValueType::VarChar => Self::de::<()>(format, body, |()| ValueSet::VarChar),
         }
     }
 }
@@ -1,6 +1,7 @@
 use std::{
     borrow::Cow,
     collections::{btree_set, BTreeSet},
+    fmt::{self, Write},
     iter,
     marker::PhantomData,
     slice,
@@ -12,7 +13,7 @@ use assembly_fdb::{
 };
 use serde::Serialize;
 
-use super::{query::ValueSet, FastContext};
+use super::{query::ValueSet, FastContext, Tabular};
 
 pub(super) trait AsRowIter<'a> {
     type AsIter<'b>: Iterator<Item = Row<'a>> + 'b
@@ -36,6 +37,7 @@ pub(super) trait AsColValIter<'a> {
     where
         Self: 'b;
     fn as_cv_iter<'b>(&'b self, row: Row<'a>) -> Self::AsIter<'b>;
+    fn names(&self) -> Vec<Cow<'a, str>>;
 }
 
 impl<'a> AsColValIter<'a> for Vec<Cow<'a, str>> {
@@ -44,6 +46,10 @@ impl<'a> AsColValIter<'a> for Vec<Cow<'a, str>> {
     fn as_cv_iter<'b>(&'b self, row: Row<'a>) -> Self::AsIter<'b> {
         self.iter().cloned().zip(row.field_iter())
     }
+
+    fn names(&self) -> Vec<Cow<'a, str>> {
+        self.clone()
+    }
 }
 
 type ColValIter<'a, 'b> = iter::Zip<iter::Cloned<slice::Iter<'b, Cow<'a, str>>>, FieldIter<'a>>;
@@ -82,6 +88,10 @@ impl<'a> AsColValIter<'a> for PartialColValIterSpec<'a> {
             row,
         }
     }
+
+    fn names(&self) -> Vec<Cow<'a, str>> {
+        self.indices.iter().map(|&i| self.names[i].clone()).collect()
+    }
 }
 
 ///
@@ -158,6 +168,41 @@ where
     }
 }
 
+impl<'a, FR, FC> Tabular for RowIter<'a, FR, FC>
+where
+    FR: AsRowIter<'a>,
+    FC: AsColValIter<'a>,
+{
+    fn write_csv(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str(&self.to_cols.names().join(","))?;
+        out.write_char('\n')?;
+        for row in self.to_rows.as_row_iter() {
+            for (i, (_, value)) in self.to_cols.as_cv_iter(row).enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                write_csv_value(out, &value)?;
+            }
+            out.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+/// Write a single field, quoting all strings
+fn write_csv_value(out: &mut dyn fmt::Write, value: &Value<MemContext>) -> fmt::Result {
+    match value {
+        Value::Nothing => Ok(()),
+        Value::Integer(v) => write!(out, "{}", v),
+        Value::Float(v) => write!(out, "{}", v),
+        Value::Boolean(v) => write!(out, "{}", v),
+        Value::BigInt(v) => write!(out, "{}", v),
+        Value::Text(v) | Value::VarChar(v) => {
+            write!(out, "\"{}\"", v.decode().replace('"', "\"\""))
+        }
+    }
+}
+
 pub(super) struct MultiPKFilterSpec<'a> {
     table: Table<'a>,
     buckets: BTreeSet<usize>,
@@ -39,7 +39,38 @@ pub enum AuthImpl {
     Basic(Arc<BasicCfg>),
 }
 
+impl BasicCfg {
+    /// The allowed credentials or API key of `request`, if it has any
+    fn credentials<B>(&self, request: &Request<B>) -> Option<String> {
+        if let Some(Ok(authorization)) = request
+            .headers()
+            .get(AUTHORIZATION)
+            .map(HeaderValue::to_str)
+        {
+            if let Some(credentials) = authorization.strip_prefix("Basic ") {
+                if self.allowed_credentials.contains(credentials) {
+                    return Some(authorization.to_string());
+                }
+            }
+        }
+        let query = request.uri().query()?;
+        form_urlencoded::parse(query.as_bytes())
+            .find(|(key, value)| key == "apiKey" && self.allowed_api_keys.contains(value.as_ref()))
+            .map(|(_, value)| format!("apiKey {}", value))
+    }
+}
+
 impl AuthImpl {
+    /// The credentials or API key of `request` that are allowed by the config
+    ///
+    /// Without `[auth.basic]`, there are no credentials to check, so this is always `None`.
+    pub fn credentials<B>(&self, request: &Request<B>) -> Option<String> {
+        match self {
+            Self::None => None,
+            Self::Basic(cfg) => cfg.credentials(request),
+        }
+    }
+
     pub fn new(cfg: Option<&AuthConfig>) -> Self {
         let mut auth_impl = AuthImpl::None;
         if let Some(auth_cfg) = cfg {
@@ -92,24 +123,8 @@ impl<B: http_body::Body, R: http_body::Body + Default> AuthorizeRequest<B> for A
         match &self.kind {
             AuthImpl::None => Ok(()),
             AuthImpl::Basic(cfg) => {
-                if let Some(Ok(authorization)) = request
-                    .headers()
-                    .get(AUTHORIZATION)
-                    .map(HeaderValue::to_str)
-                {
-                    if let Some(credentials) = authorization.strip_prefix("Basic ") {
-                        if cfg.allowed_credentials.contains(credentials) {
-                            return Ok(());
-                        }
-                    }
-                }
-                if let Some(query) = request.uri().query() {
-                    let parse = form_urlencoded::parse(query.as_bytes());
-                    for (key, value) in parse {
-                        if key == "apiKey" && cfg.allowed_api_keys.contains(value.as_ref()) {
-                            return Ok(());
-                        }
-                    }
+                if cfg.credentials(request).is_some() {
+                    return Ok(());
                 }
                 if let Some(Ok(user_agent)) =
                     request.headers().get(USER_AGENT).map(HeaderValue::to_str)
@@ -1,48 +1,33 @@
-use assembly_fdb::mem::Database;
 use assembly_xml::localization::load_locale;
 use clap::Parser;
 use color_eyre::eyre::{eyre, WrapErr};
-use hyper::server::Server;
-use mapr::Mmap;
+use futures_util::future::try_join_all;
+use hyper::{
+    server::{conn::AddrStream, Server},
+    service::make_service_fn,
+    Body, Request,
+};
 use paradox_server::{
-    api::{self, rev::ReverseLookup},
+    api,
     auth::{AuthKind, Authorize},
-    config::{Config, Options},
-    data::locale::LocaleRoot,
-    middleware::{CorsLayerExt, PublicOrLayer, RedirectLayer},
-    services::{self, BaseRouter, FallbackService},
-};
-use paradox_typed_db::TypedDatabase;
-use std::{
-    fs::{self, File},
-    path::Path,
-};
-use tower::{make::Shared, ServiceBuilder};
-use tower_http::{
-    auth::RequireAuthorizationLayer, cors::CorsLayer, services::ServeDir, trace::TraceLayer,
+    config::{Config, Maintenance, Options},
+    data::{
+        check,
+        db::{self, DbSnapshot, SharedDb},
+        events::ReloadEvents,
+        locale::{LocaleRoot, Locales},
+    },
+    middleware::{
+        request_id, PeerAddr, PublicOrLayer, RateLimitLayer, RedirectLayer, RequestIdSpan,
+        ScopedCorsLayer, SecurityHeadersLayer,
+    },
+    services::{self, BaseRouter, FallbackService, RouterPaths},
 };
+use std::{collections::BTreeMap, convert::Infallible, fs};
+use tower::{Layer, ServiceBuilder, ServiceExt};
+use tower_http::{auth::RequireAuthorizationLayer, services::ServeDir, trace::TraceLayer};
 use tracing::log::{self, LevelFilter};
 
-fn load_db(path: &Path) -> color_eyre::Result<Database<'static>> {
-    // Load the database file
-    let file = File::open(path)
-        .wrap_err_with(|| format!("Failed to open input file '{}'", path.display()))?;
-
-    // Load the database
-    let mmap = unsafe { Mmap::map(&file)? };
-    // We want to keep this mapped until the end of the program!
-    let mref: &'static Mmap = Box::leak(Box::new(mmap));
-    let buf: &'static [u8] = mref.as_ref();
-    Ok(Database::new(buf))
-}
-
-fn load_table_rels(path: &Path) -> color_eyre::Result<&'static api::graphql::TableRels> {
-    let table_rels = api::graphql::read_out_table_rels(path)?;
-    // We want to keep this mapped until the end of the program!
-    let mref: &'static api::graphql::TableRels = Box::leak(Box::new(table_rels));
-    Ok(mref)
-}
-
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
     pretty_env_logger::formatted_builder()
@@ -57,43 +42,85 @@ async fn main() -> color_eyre::Result<()> {
     let cfg_file = fs::read_to_string(&cfg_path)
         .wrap_err_with(|| format!("Failed to open config file '{}'", cfg_path.display()))?;
     let cfg: Config = toml::from_str(&cfg_file)?;
+    cfg.validate()?;
+
+    if opts.check {
+        let errors = check::run(&cfg.data);
+        for e in &errors {
+            log::error!("{}", e);
+        }
+        return match errors.len() {
+            0 => {
+                log::info!("All checks passed");
+                Ok(())
+            }
+            n => Err(eyre!("{} check(s) failed", n)),
+        };
+    }
+
+    let maintenance = Maintenance::new(cfg.general.maintenance);
+    maintenance.spawn_watcher(&cfg_path)?;
 
-    // Load the database
-    let db = load_db(&cfg.data.cdclient)?;
-    // Load SQL table relations
-    let table_rels = load_table_rels(&cfg.data.sqlite)?;
+    // Load the database, and build the reverse lookup while the server starts up
+    let data = SharedDb::new(DbSnapshot::load(&cfg.data.cdclient, false)?);
+    if cfg.data.warm_rev {
+        data.get().warm_in_background();
+    }
+    let events = ReloadEvents::new();
+    db::spawn_watcher(
+        &cfg.data.cdclient,
+        data.clone(),
+        cfg.data.warm_rev,
+        events.clone(),
+    )?;
     let sqlite_path = Box::leak(Box::new(cfg.data.sqlite.clone()));
 
     // Load the locale
     let locale_root = load_locale(&cfg.data.locale)
         .context("Failed to load locale.xml")
         .map(LocaleRoot::new)?;
-
-    // Load the typed database
-    let tables = db.tables().unwrap();
-    let tydb = TypedDatabase::new(tables)?;
-    let tydb = Box::leak(Box::new(tydb));
-    let rev = Box::leak(Box::new(ReverseLookup::new(tydb)));
+    let mut by_lang = BTreeMap::new();
+    for (lang, path) in &cfg.data.locales {
+        let root = load_locale(path)
+            .with_context(|| format!("Failed to load {} locale '{}'", lang, path.display()))
+            .map(LocaleRoot::new)?;
+        by_lang.insert(lang.clone(), root);
+    }
+    let locales = Locales::new(locale_root, by_lang, &cfg.data.default_locale);
 
     // Set up res connection
     let base_url = cfg.general.base_url();
+    let paths = RouterPaths::new(&cfg.general);
 
     // Initialize the Application
-    let app = services::app(&cfg.data, tydb, locale_root.clone(), &base_url)?;
+    let app = services::app(
+        &cfg.data,
+        data.clone(),
+        locales.clone(),
+        &base_url,
+        &paths,
+        &cfg.meta,
+    )?;
 
     // Initialize the Api
     let auth_kind = AuthKind::of(&cfg.auth);
     let api = api::service(
         &cfg.data,
-        locale_root,
+        locales,
         auth_kind,
-        base_url,
-        db,
-        tydb,
-        rev,
-        table_rels,
+        &cfg.base_urls(),
+        &paths,
+        &cfg.docs,
+        data,
         sqlite_path,
+        &cfg.query,
+        cfg.general.max_response_bytes,
+        cfg.general.max_request_bytes,
+        maintenance,
+        events,
     )?;
+    // Only the API counts towards the rate limit, not the app and lu-res files
+    let api = RateLimitLayer::new(&cfg.auth).layer(api);
     // Unfortunately still need the API fallback
     let api_fallback = FallbackService::new(cfg.data.lu_json_cache.as_path());
 
@@ -101,12 +128,15 @@ async fn main() -> color_eyre::Result<()> {
     let res = ServeDir::new(&cfg.data.lu_res_cache);
 
     let service = ServiceBuilder::new()
-        .layer(TraceLayer::new_for_http())
-        .layer(CorsLayer::configure(&cfg.general.cors))
+        .layer(request_id::set_request_id_layer())
+        .layer(TraceLayer::new_for_http().make_span_with(RequestIdSpan))
+        .layer(request_id::propagate_request_id_layer())
+        .layer(SecurityHeadersLayer::new(&cfg.general.security))
+        .layer(ScopedCorsLayer::new(&cfg.general.cors, &paths))
         .layer(RedirectLayer::new(&cfg))
         .layer(PublicOrLayer::new(&cfg.data.public))
         .layer(RequireAuthorizationLayer::custom(Authorize::new(&cfg.auth)))
-        .service(BaseRouter::new(api, app, res, api_fallback));
+        .service(BaseRouter::new(api, app, res, api_fallback, paths));
 
     // FIXME: TLS
     if let Some(tls_cfg) = cfg.tls {
@@ -123,14 +153,23 @@ async fn main() -> color_eyre::Result<()> {
         }
     }
 
-    let addr = cfg.general.addr();
-    log::info!("Starting server on http://{}", addr);
-
-    // Finally, run the server
-    Server::bind(&addr)
-        .serve(Shared::new(service))
-        .await
-        .expect("server error");
+    // Finally, run the server on all addresses, telling each request where it came from
+    let make_service = make_service_fn(move |conn: &AddrStream| {
+        let peer = PeerAddr(conn.remote_addr());
+        let service = service.clone().map_request(move |mut req: Request<Body>| {
+            req.extensions_mut().insert(peer);
+            req
+        });
+        async move { Ok::<_, Infallible>(service) }
+    });
+    let mut servers = Vec::new();
+    for addr in cfg.general.addrs() {
+        let builder =
+            Server::try_bind(&addr).wrap_err_with(|| format!("Failed to bind to {}", addr))?;
+        log::info!("Starting server on http://{}", addr);
+        servers.push(builder.serve(make_service.clone()));
+    }
+    try_join_all(servers).await.wrap_err("server error")?;
 
     Ok(())
 }
@@ -1,4 +1,15 @@
-use std::{borrow::Cow, collections::BTreeMap, net::SocketAddr, path::PathBuf};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use clap::Parser;
 use http::{header::InvalidHeaderValue, HeaderValue};
@@ -6,15 +17,30 @@ use serde::{
     de::{SeqAccess, Unexpected, Visitor},
     Deserialize, Deserializer,
 };
+use tracing::{error, info};
+
+use crate::data::{fs::spawn_file_watcher, locale::Locales};
 
 fn default_port() -> u16 {
     3030
 }
 
+fn default_locale() -> String {
+    Locales::BASE_LANG.to_string()
+}
+
 fn default_lu_res_cache() -> PathBuf {
     PathBuf::from("lu-res")
 }
 
+fn default_lu_res_image_base() -> PathBuf {
+    PathBuf::from("/textures/ui")
+}
+
+fn default_lu_res_image_extension() -> String {
+    String::from("png")
+}
+
 fn default_lu_json_cache() -> PathBuf {
     PathBuf::from("lu-json")
 }
@@ -23,6 +49,65 @@ fn default_public() -> PathBuf {
     PathBuf::from("public")
 }
 
+fn default_api_prefix() -> String {
+    String::from("/api")
+}
+
+fn default_res_prefix() -> String {
+    String::from("/lu-res")
+}
+
+fn default_max_request_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_query_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_graphql_cache_size() -> usize {
+    256
+}
+
+fn default_docs_title() -> String {
+    String::from("LU-Explorer API")
+}
+
+fn default_meta_site() -> String {
+    String::from("@lu_explorer")
+}
+
+fn default_meta_image() -> PathBuf {
+    PathBuf::from("/ui/ingame/freetrialcongratulations_id.png")
+}
+
+fn default_referrer_policy() -> Option<HeaderValue> {
+    Some(HeaderValue::from_static("strict-origin-when-cross-origin"))
+}
+
+fn default_frame_options() -> Option<HeaderValue> {
+    Some(HeaderValue::from_static("SAMEORIGIN"))
+}
+
+/// Deserialize a single header value, where an empty string is `None`
+fn deserialize_header_value_opt<'de, D>(deserializer: D) -> Result<Option<HeaderValue>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let src = Cow::<'de, str>::deserialize(deserializer)?;
+    if src.is_empty() {
+        return Ok(None);
+    }
+    HeaderValue::from_str(src.as_ref())
+        .map(Some)
+        .map_err(|_: InvalidHeaderValue| {
+            <D::Error as serde::de::Error>::invalid_value(
+                Unexpected::Str(src.as_ref()),
+                &"only visible ASCII characters (32-127)",
+            )
+        })
+}
+
 fn deserialize_header_value_vec<'de, D>(deserializer: D) -> Result<Vec<HeaderValue>, D::Error>
 where
     D: Deserializer<'de>,
@@ -63,6 +148,10 @@ pub struct CorsOptions {
     pub all: bool,
     #[serde(default, deserialize_with = "deserialize_header_value_vec")]
     pub domains: Vec<HeaderValue>,
+    /// The paths below `general.base` that CORS applies to, `general.api_prefix` if unset
+    ///
+    /// The app and the other files don't need `Access-Control-*` headers.
+    pub paths: Option<Vec<String>>,
 }
 
 impl Default for CorsOptions {
@@ -70,6 +159,39 @@ impl Default for CorsOptions {
         Self {
             all: true,
             domains: vec![],
+            paths: None,
+        }
+    }
+}
+
+/// Security headers, see [`crate::middleware::SecurityHeadersLayer`]
+///
+/// An empty string disables a header.
+#[derive(Deserialize)]
+pub struct SecurityOptions {
+    /// The `Content-Security-Policy` of HTML pages, not sent by default
+    #[serde(default, deserialize_with = "deserialize_header_value_opt")]
+    pub csp: Option<HeaderValue>,
+    /// The `Referrer-Policy` of HTML pages
+    #[serde(
+        default = "default_referrer_policy",
+        deserialize_with = "deserialize_header_value_opt"
+    )]
+    pub referrer_policy: Option<HeaderValue>,
+    /// The `X-Frame-Options` of HTML pages
+    #[serde(
+        default = "default_frame_options",
+        deserialize_with = "deserialize_header_value_opt"
+    )]
+    pub frame_options: Option<HeaderValue>,
+}
+
+impl Default for SecurityOptions {
+    fn default() -> Self {
+        Self {
+            csp: None,
+            referrer_policy: default_referrer_policy(),
+            frame_options: default_frame_options(),
         }
     }
 }
@@ -81,16 +203,42 @@ pub struct GeneralOptions {
     pub port: u16,
     /// Bind to `0.0.0.0` instead of `127.0.0.1`
     pub public: bool,
+    /// The addresses to bind to, overrides `port` and `public` if not empty
+    #[serde(default)]
+    pub bind: Vec<SocketAddr>,
     /// The allowed cross-origin domains
     #[serde(default)]
     pub cors: CorsOptions,
+    /// Security headers of the responses
+    #[serde(default)]
+    pub security: SecurityOptions,
     /// The base of the path
+    ///
+    /// Requests that start with it are routed as if it wasn't there, so the app
+    /// works both behind a proxy that removes it and one that doesn't.
     pub base: Option<String>,
+    /// The path of the API below `base`
+    #[serde(default = "default_api_prefix")]
+    pub api_prefix: String,
+    /// The path of the `lu_res_cache` files below `base`
+    #[serde(default = "default_res_prefix")]
+    pub res_prefix: String,
     /// The canonical domain
     pub domain: String,
     /// Whether this is served via https
     #[serde(default = "no")]
     pub secure: bool,
+    /// The largest API response in bytes, larger ones are replaced with a `413`
+    #[serde(default)]
+    pub max_response_bytes: Option<usize>,
+    /// The largest request body in bytes, e.g. of a GraphQL `POST`, larger ones get a `413`
+    #[serde(default = "default_max_request_bytes")]
+    pub max_request_bytes: usize,
+    /// Reply `503` to all API data routes, e.g. while swapping databases
+    ///
+    /// This is the only setting that is picked up when the config file changes.
+    #[serde(default)]
+    pub maintenance: bool,
 }
 
 impl GeneralOptions {
@@ -112,6 +260,14 @@ impl GeneralOptions {
         SocketAddr::from((self.ip(), self.port))
     }
 
+    /// All addresses the server should listen on
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        match self.bind.is_empty() {
+            true => vec![self.addr()],
+            false => self.bind.clone(),
+        }
+    }
+
     /// Get the canonical base URL (without a trailing slash)
     pub fn base_url(&self) -> String {
         let mut start = self.scheme().to_string() + "://" + &self.domain;
@@ -123,6 +279,52 @@ impl GeneralOptions {
     }
 }
 
+/// The current value of `general.maintenance`, shared by all services
+#[derive(Clone, Default)]
+pub struct Maintenance {
+    inner: Arc<AtomicBool>,
+}
+
+impl Maintenance {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            inner: Arc::new(AtomicBool::new(enabled)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.inner.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Re-read `general.maintenance` whenever the config file at `cfg_path` changes
+    pub fn spawn_watcher(&self, cfg_path: &Path) -> Result<(), notify::Error> {
+        let this = self.clone();
+        spawn_file_watcher(cfg_path, move |path| {
+            let cfg = match fs::read_to_string(path) {
+                Ok(text) => toml::from_str::<Config>(&text).map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            match cfg {
+                Ok(cfg) => {
+                    let enabled = cfg.general.maintenance;
+                    if enabled != this.is_enabled() {
+                        info!(
+                            "Maintenance mode is now {}",
+                            if enabled { "on" } else { "off" }
+                        );
+                    }
+                    this.set(enabled);
+                }
+                Err(e) => error!("Failed to re-load '{}': {}", path.display(), e),
+            }
+        })
+    }
+}
+
 fn no() -> bool {
     false
 }
@@ -144,6 +346,11 @@ pub struct DataOptions {
     pub public: PathBuf,
     /// The `client/res` directory
     pub res: Option<PathBuf>,
+    /// More `client/res` directories, e.g. with patched files, loaded after `res`
+    ///
+    /// A file replaces the one with the same path in `res` or an earlier overlay.
+    #[serde(default)]
+    pub res_overlays: Vec<PathBuf>,
     /// The `versions` directory
     pub versions: Option<PathBuf>,
     /// The CDClient database FDB file
@@ -158,10 +365,120 @@ pub struct DataOptions {
     pub lu_json_cache: PathBuf,
     /// The LU-Res prefix
     pub lu_res_prefix: Option<String>,
+    /// The directory on LU-Res that client image paths are relative to
+    #[serde(default = "default_lu_res_image_base")]
+    pub lu_res_image_base: PathBuf,
+    /// The extension of images on LU-Res, empty to keep the one from the client path
+    #[serde(default = "default_lu_res_image_extension")]
+    pub lu_res_image_extension: String,
     /// The locale.xml file
     pub locale: PathBuf,
+    /// Additional locale.xml files by language (e.g. `de_DE`), used for page
+    /// metadata when the `Accept-Language` header asks for that language
+    #[serde(default)]
+    pub locales: BTreeMap<String, PathBuf>,
+    /// The language used when a request doesn't ask for one, either `en_US` for
+    /// `locale` or one of `locales`
+    ///
+    /// This applies to page metadata without an `Accept-Language` header, to
+    /// `_loc` columns in GraphQL queries and to the locale API.
+    #[serde(default = "default_locale")]
+    pub default_locale: String,
     /// The sqlite file to serve SQL queries from
     pub sqlite: PathBuf,
+    /// Build all reverse lookup indices at startup instead of on first use
+    ///
+    /// At startup, this happens in the background and `/rev` routes reply `503`
+    /// until the indices they use are done. On a reload, the old database is used
+    /// in the meantime.
+    #[serde(default)]
+    pub warm_rev: bool,
+    /// The tables that are exposed by the `tables` API
+    #[serde(default)]
+    pub tables: TableFilter,
+}
+
+/// An allow and deny list of FDB table names
+///
+/// By default, all tables are exposed.
+#[derive(Deserialize, Default, Clone)]
+pub struct TableFilter {
+    /// If set, only these tables are exposed
+    #[serde(default)]
+    pub allow: Option<BTreeSet<String>>,
+    /// These tables are never exposed
+    #[serde(default)]
+    pub deny: BTreeSet<String>,
+}
+
+impl TableFilter {
+    /// Whether the table with that name may be served
+    pub fn is_exposed(&self, name: &str) -> bool {
+        !self.deny.contains(name) && self.allow.as_ref().map_or(true, |a| a.contains(name))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct QueryOptions {
+    /// The time (in milliseconds) after which an SQL or GraphQL query is cancelled
+    #[serde(default = "default_query_timeout_ms")]
+    pub timeout_ms: u64,
+    /// The number of GraphQL queries whose translation to SQL is cached
+    #[serde(default = "default_graphql_cache_size")]
+    pub graphql_cache_size: usize,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            timeout_ms: default_query_timeout_ms(),
+            graphql_cache_size: default_graphql_cache_size(),
+        }
+    }
+}
+
+impl QueryOptions {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DocsOptions {
+    /// The title of the Swagger UI page and the OpenAPI document
+    #[serde(default = "default_docs_title")]
+    pub title: String,
+    /// API URLs of other servers to list in the OpenAPI document, e.g. mirrors
+    #[serde(default)]
+    pub servers: Vec<String>,
+}
+
+impl Default for DocsOptions {
+    fn default() -> Self {
+        Self {
+            title: default_docs_title(),
+            servers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MetaConfig {
+    /// The twitter handle for the `twitter:site` tag
+    #[serde(default = "default_meta_site")]
+    pub site: String,
+    /// The image for pages that don't have one, relative to the LU-Res prefix
+    #[serde(default = "default_meta_image")]
+    pub default_image: PathBuf,
+}
+
+impl Default for MetaConfig {
+    fn default() -> Self {
+        Self {
+            site: default_meta_site(),
+            default_image: default_meta_image(),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -170,10 +487,123 @@ pub struct Config {
     pub tls: Option<TlsOptions>,
     pub data: DataOptions,
     #[serde(default)]
+    pub query: QueryOptions,
+    #[serde(default)]
+    pub docs: DocsOptions,
+    #[serde(default)]
+    pub meta: MetaConfig,
+    #[serde(default)]
     pub host: Vec<HostConfig>,
     pub auth: Option<AuthConfig>,
 }
 
+impl Config {
+    /// Get the base URLs (without a trailing slash) this server can be reached at,
+    /// starting with the canonical one and followed by all hosts that don't redirect
+    pub fn base_urls(&self) -> Vec<String> {
+        let mut urls = vec![self.general.base_url()];
+        for host in self.host.iter().filter(|host| !host.redirect) {
+            let mut url = self.general.scheme().to_string() + "://" + &host.name;
+            if let Some(b) = host.base.as_deref() {
+                if !b.starts_with('/') {
+                    url.push('/');
+                }
+                url.push_str(b.trim_end_matches('/'));
+            }
+            urls.push(url);
+        }
+        urls
+    }
+
+    /// Check that all files and directories in the config can be read
+    ///
+    /// The `public` directory is optional, so it is not checked.
+    pub fn validate(&self) -> color_eyre::Result<()> {
+        let mut problems = Vec::new();
+        let data = &self.data;
+        check_file(&mut problems, "data.cdclient", &data.cdclient);
+        check_file(&mut problems, "data.locale", &data.locale);
+        check_file(&mut problems, "data.sqlite", &data.sqlite);
+        for (lang, path) in &data.locales {
+            check_file(&mut problems, &format!("data.locales.{}", lang), path);
+        }
+        if data.default_locale != Locales::BASE_LANG
+            && !data.locales.contains_key(&data.default_locale)
+        {
+            problems.push(format!(
+                "data.default_locale: '{}' is neither {} nor one of data.locales",
+                data.default_locale,
+                Locales::BASE_LANG
+            ));
+        }
+        check_dir(&mut problems, "data.explorer_spa", &data.explorer_spa);
+        check_dir(&mut problems, "data.lu_res_cache", &data.lu_res_cache);
+        check_dir(&mut problems, "data.lu_json_cache", &data.lu_json_cache);
+        if let Some(res) = &data.res {
+            check_dir(&mut problems, "data.res", res);
+        }
+        for (i, overlay) in data.res_overlays.iter().enumerate() {
+            check_dir(&mut problems, &format!("data.res_overlays[{}]", i), overlay);
+        }
+        if let Some(versions) = &data.versions {
+            check_dir(&mut problems, "data.versions", versions);
+        }
+        check_prefix(
+            &mut problems,
+            "general.api_prefix",
+            &self.general.api_prefix,
+        );
+        check_prefix(
+            &mut problems,
+            "general.res_prefix",
+            &self.general.res_prefix,
+        );
+        for (i, path) in self.general.cors.paths.iter().flatten().enumerate() {
+            check_prefix(&mut problems, &format!("general.cors.paths[{}]", i), path);
+        }
+        if let Some(tls) = self.tls.as_ref().filter(|tls| tls.enabled) {
+            check_file(&mut problems, "tls.key", &tls.key);
+            check_file(&mut problems, "tls.cert", &tls.cert);
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let mut report = String::from("Invalid configuration:");
+        for problem in problems {
+            report.push_str("\n  - ");
+            report.push_str(&problem);
+        }
+        Err(color_eyre::eyre::eyre!(report))
+    }
+}
+
+fn check_prefix(problems: &mut Vec<String>, key: &str, prefix: &str) {
+    if !prefix.starts_with('/') || prefix.len() < 2 || prefix.ends_with('/') {
+        problems.push(format!(
+            "{}: '{}' must start with a '/' and not end with one",
+            key, prefix
+        ));
+    }
+}
+
+fn check_file(problems: &mut Vec<String>, key: &str, path: &Path) {
+    if let Err(e) = fs::File::open(path) {
+        problems.push(format!("{}: can't open '{}': {}", key, path.display(), e));
+    }
+}
+
+fn check_dir(problems: &mut Vec<String>, key: &str, path: &Path) {
+    if let Err(e) = fs::read_dir(path) {
+        problems.push(format!(
+            "{}: can't read directory '{}': {}",
+            key,
+            path.display(),
+            e
+        ));
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct HostConfig {
     pub name: String,
@@ -189,6 +619,16 @@ pub struct AuthConfig {
     pub user_agents: Vec<String>,
     #[serde(default)]
     pub api_keys: Vec<String>,
+    /// Per-key request quotas
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RateLimitConfig {
+    /// Requests per minute for each set of credentials or API key
+    pub per_minute: u32,
+    /// Requests per minute for each client IP address without credentials
+    pub anonymous_per_minute: u32,
 }
 
 #[derive(Parser)]
@@ -196,4 +636,7 @@ pub struct AuthConfig {
 pub struct Options {
     #[clap(long, default_value = "paradox.toml")]
     pub cfg: PathBuf,
+    /// Load and validate all data files, then exit instead of starting the server
+    #[clap(long)]
+    pub check: bool,
 }
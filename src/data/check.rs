@@ -0,0 +1,96 @@
+//! # Data integrity check
+//!
+//! This is what `--check` runs: it loads every data file from the config the same
+//! way the server does (including all reverse lookup indices) and collects the
+//! failures instead of stopping at the first one. This makes it possible to
+//! validate e.g. a new `cdclient.fdb` before deploying it.
+//!
+//! The typed database panics on missing columns, so panics are caught and reported
+//! with their message and location.
+
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    sync::Mutex,
+};
+
+use assembly_xml::localization::load_locale;
+
+use super::db::DbSnapshot;
+#[cfg(feature = "graphql")]
+use crate::api::graphql::SharedTableRels;
+use crate::config::DataOptions;
+
+/// A single failed check
+#[derive(Debug)]
+pub struct CheckError {
+    /// The file that failed to load
+    pub file: String,
+    /// What went wrong
+    pub message: String,
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+static LAST_PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+/// Run `f`, turning both errors and panics into a message
+fn catch<T, E, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, E>,
+    E: fmt::Display,
+{
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(v)) => Ok(v),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => {
+            let message = LAST_PANIC.lock().unwrap().take();
+            Err(message.unwrap_or_else(|| String::from("panicked")))
+        }
+    }
+}
+
+fn check_file<T, E, F>(errors: &mut Vec<CheckError>, path: &Path, f: F)
+where
+    F: FnOnce() -> Result<T, E>,
+    E: fmt::Display,
+{
+    if let Err(message) = catch(f) {
+        errors.push(CheckError {
+            file: path.display().to_string(),
+            message,
+        });
+    }
+}
+
+/// Load all data files, returning every failure
+pub fn run(data: &DataOptions) -> Vec<CheckError> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| {
+        *LAST_PANIC.lock().unwrap() = Some(info.to_string());
+    }));
+
+    let mut errors = Vec::new();
+    check_file(&mut errors, &data.cdclient, || {
+        DbSnapshot::load(&data.cdclient, true).map_err(|e| {
+            let chain: Vec<String> = e.chain().map(ToString::to_string).collect();
+            chain.join(": ")
+        })
+    });
+    check_file(&mut errors, &data.locale, || load_locale(&data.locale));
+    for path in data.locales.values() {
+        check_file(&mut errors, path, || load_locale(path));
+    }
+    #[cfg(feature = "graphql")]
+    check_file(&mut errors, &data.sqlite, || {
+        SharedTableRels::load(&data.sqlite, 0, &data.default_locale)
+    });
+
+    panic::set_hook(hook);
+    errors
+}
@@ -0,0 +1,220 @@
+//! # The CDClient database
+//!
+//! The FDB file is memory-mapped, and the typed database and the reverse lookup
+//! borrow from that map inside of a [`DbSnapshot`]. When the file changes on disk,
+//! a fresh snapshot is loaded and swapped into the [`SharedDb`], so that new
+//! requests see the new data. Requests that are still running hold an `Arc` of the
+//! snapshot they started with, which is freed when the last of them is done.
+//!
+//! Updates should replace the file (e.g. by renaming a new file over it) instead
+//! of writing to it in place, because the old snapshots still map the old file.
+
+use std::{
+    fs::File,
+    mem::ManuallyDrop,
+    path::Path,
+    ptr::NonNull,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+use arc_swap::ArcSwap;
+use assembly_fdb::mem::Database;
+use color_eyre::eyre::WrapErr;
+use mapr::Mmap;
+use paradox_typed_db::TypedDatabase;
+use serde::Serialize;
+use tracing::{error, info};
+
+use super::{
+    events::{Reload, ReloadEvents},
+    fs::spawn_file_watcher,
+};
+use crate::api::rev::ReverseLookup;
+
+/// One loaded version of the CDClient database and everything derived from it
+///
+/// The borrowed parts are stored with a `'static` lifetime, but the accessors only
+/// hand them out for the lifetime of `&self`, and [`Drop`] frees them before the
+/// memory map they borrow from.
+pub struct DbSnapshot {
+    /// Borrows from `tydb`, so it is dropped first
+    rev: ManuallyDrop<ReverseLookup>,
+    /// Borrows from `_mmap`, allocated in [`DbSnapshot::load`] and freed in [`Drop`]
+    tydb: NonNull<TypedDatabase<'static>>,
+    /// Borrows from `_mmap`
+    db: Database<'static>,
+    /// The modification time of the FDB file, used as `Last-Modified`
+    pub modified: Option<SystemTime>,
+    pub meta: DbMeta,
+    /// The mapped file, which is unmapped after all other fields are dropped
+    _mmap: Mmap,
+}
+
+// SAFETY: `tydb` is owned by the snapshot and only ever accessed through `&self`
+unsafe impl Send for DbSnapshot {}
+unsafe impl Sync for DbSnapshot {}
+
+/// Which FDB file a [`DbSnapshot`] was loaded from, for `/v0/meta`
+#[derive(Debug, Serialize)]
+pub struct DbMeta {
+    /// The file name, without the directory
+    pub file: String,
+    /// The size of the file in bytes
+    pub size: usize,
+    /// The number of tables
+    pub tables: usize,
+    /// The modification time of the file, as an HTTP date
+    pub modified: Option<String>,
+    /// The FNV-1a hash of the file contents, as 16 hex digits
+    pub hash: String,
+}
+
+/// 64-bit FNV-1a, which is cheap and stable across builds
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl DbSnapshot {
+    /// Load the FDB file at `path`
+    ///
+    /// If `warm_rev` is set, all reverse lookup indices are built immediately.
+    pub fn load(path: &Path, warm_rev: bool) -> color_eyre::Result<Self> {
+        let file = File::open(path)
+            .wrap_err_with(|| format!("Failed to open input file '{}'", path.display()))?;
+        let modified = file.metadata().and_then(|m| m.modified()).ok();
+
+        // Load the database
+        let mmap = unsafe { Mmap::map(&file)? };
+        // SAFETY: The mapping doesn't move with the `Mmap`, which is kept in the
+        // snapshot until everything that borrows from it was dropped
+        let buf: &'static [u8] = unsafe { std::slice::from_raw_parts(mmap.as_ptr(), mmap.len()) };
+        let db = Database::new(buf);
+
+        // Load the typed database
+        let tables = db.tables().wrap_err("Failed to load the table list")?;
+        let meta = DbMeta {
+            file: path
+                .file_name()
+                .map(|f| f.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            size: buf.len(),
+            tables: tables.len(),
+            modified: modified.map(httpdate::fmt_http_date),
+            hash: format!("{:016x}", fnv1a(buf)),
+        };
+        let tydb = NonNull::from(Box::leak(Box::new(TypedDatabase::new(tables)?)));
+        // SAFETY: `tydb` is freed only after `rev` was dropped
+        let tydb_ref: &'static TypedDatabase = unsafe { tydb.as_ref() };
+        let rev = match warm_rev {
+            true => ReverseLookup::new(tydb_ref),
+            false => ReverseLookup::lazy(tydb_ref),
+        };
+        Ok(Self {
+            rev: ManuallyDrop::new(rev),
+            tydb,
+            db,
+            modified,
+            meta,
+            _mmap: mmap,
+        })
+    }
+
+    /// The raw database
+    pub fn db(&self) -> Database<'_> {
+        self.db
+    }
+
+    /// The typed database
+    pub fn tydb(&self) -> &TypedDatabase<'_> {
+        // SAFETY: `tydb` is valid until `self` is dropped
+        unsafe { self.tydb.as_ref() }
+    }
+
+    /// The reverse lookup indices
+    pub fn rev(&self) -> &ReverseLookup {
+        &self.rev
+    }
+
+    /// Build all reverse lookup indices on a new thread, which keeps the snapshot alive
+    pub fn warm_in_background(self: Arc<Self>) {
+        ReverseLookup::warm_in_background(self);
+    }
+}
+
+impl AsRef<ReverseLookup> for DbSnapshot {
+    fn as_ref(&self) -> &ReverseLookup {
+        &self.rev
+    }
+}
+
+impl Drop for DbSnapshot {
+    fn drop(&mut self) {
+        // SAFETY: Nothing borrows from `rev` or `tydb` anymore, because the accessors
+        // are bounded by `&self`. `rev` borrows from `tydb`, so it is dropped first,
+        // and `tydb` was allocated with `Box` in `load`.
+        unsafe {
+            ManuallyDrop::drop(&mut self.rev);
+            drop(Box::from_raw(self.tydb.as_ptr()));
+        }
+    }
+}
+
+/// Handle to the current [`DbSnapshot`], shared by all services
+#[derive(Clone)]
+pub struct SharedDb {
+    inner: Arc<ArcSwap<DbSnapshot>>,
+}
+
+impl SharedDb {
+    pub fn new(snapshot: DbSnapshot) -> Self {
+        Self {
+            inner: Arc::new(ArcSwap::from_pointee(snapshot)),
+        }
+    }
+
+    /// Get the current snapshot, which stays alive while the `Arc` is held
+    pub fn get(&self) -> Arc<DbSnapshot> {
+        self.inner.load_full()
+    }
+
+    /// Replace the current snapshot for all subsequent calls to [`SharedDb::get`]
+    pub fn set(&self, snapshot: DbSnapshot) {
+        self.inner.store(Arc::new(snapshot));
+    }
+}
+
+/// Reload the database at `path` into `shared` whenever the file changes
+pub fn spawn_watcher(
+    path: &Path,
+    shared: SharedDb,
+    warm_rev: bool,
+    events: ReloadEvents,
+) -> Result<(), notify::Error> {
+    spawn_file_watcher(path, move |path| {
+        info!("(re-)loading '{}'", path.display());
+        let time = Instant::now();
+        match DbSnapshot::load(path, warm_rev) {
+            Ok(snapshot) => {
+                shared.set(snapshot);
+                events.send(Reload::Db);
+                let duration = time.elapsed();
+                info!("Done re-loading database ({}ms)", duration.as_millis());
+            }
+            Err(e) => error!("Failed to re-load database: {:?}", e),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fnv1a;
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
@@ -0,0 +1,50 @@
+//! # Reload notifications
+//!
+//! The file watchers announce every successful reload of a data file here, so that
+//! `/api/v0/events` can forward them to clients as Server-Sent Events.
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// The number of events a slow subscriber may fall behind before it misses some
+const CAPACITY: usize = 16;
+
+/// The data that was reloaded
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reload {
+    /// The `cdclient.fdb` database
+    Db,
+    /// The SQLite mirror of the database, used for GraphQL
+    Sqlite,
+}
+
+/// Handle to send and subscribe to [`Reload`] events
+#[derive(Clone)]
+pub struct ReloadEvents {
+    tx: broadcast::Sender<Reload>,
+}
+
+impl Default for ReloadEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReloadEvents {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CAPACITY);
+        Self { tx }
+    }
+
+    /// Notify all current subscribers
+    pub fn send(&self, reload: Reload) {
+        // No subscribers is not an error
+        let _ = self.tx.send(reload);
+    }
+
+    /// Receive all events from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Reload> {
+        self.tx.subscribe()
+    }
+}
@@ -1,10 +1,11 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     convert::TryFrom,
     ffi::OsStr,
     fs::{DirEntry, File},
     io::{self, ErrorKind},
     path::{Component, Path, PathBuf},
+    sync::Arc,
 };
 
 use assembly_pack::{
@@ -12,17 +13,27 @@ use assembly_pack::{
     pki::core::{PackFileRef, PackIndexFile},
 };
 use latin1str::Latin1Str;
+use paradox_typed_db::TypedDatabase;
 
 use hyper::body::Bytes;
+use notify::{
+    event::{AccessKind, AccessMode, CreateKind, EventKind, ModifyKind},
+    recommended_watcher, RecursiveMode, Watcher,
+};
 use serde::Serialize;
 use tokio::sync::oneshot::Sender;
-use tracing::error;
+use tracing::{debug, error};
+
+use crate::{config::DataOptions, services::router::RouterPaths};
 
-pub fn cleanup_path(url: &Latin1Str) -> Option<PathBuf> {
+/// Turn a client path (e.g. of an icon) into a path on the res server
+///
+/// The path is resolved relative to `base` and gets `extension`, if one is set.
+pub fn cleanup_path(url: &Latin1Str, base: &Path, extension: Option<&str>) -> Option<PathBuf> {
     let url = url.decode().replace('\\', "/").to_ascii_lowercase();
     let p = Path::new(&url);
 
-    let mut path = Path::new("/textures/ui").to_owned();
+    let mut path = base.to_owned();
     for comp in p.components() {
         match comp {
             Component::ParentDir => {
@@ -34,7 +45,9 @@ pub fn cleanup_path(url: &Latin1Str) -> Option<PathBuf> {
             Component::Prefix(_) => return None,
         }
     }
-    path.set_extension("png");
+    if let Some(extension) = extension {
+        path.set_extension(extension);
+    }
     Some(path)
 }
 
@@ -42,16 +55,46 @@ pub fn cleanup_path(url: &Latin1Str) -> Option<PathBuf> {
 /// Helper to get an usable http(s) URI from a client-relative path
 pub struct LuRes {
     prefix: String,
+    image_base: PathBuf,
+    image_extension: Option<String>,
 }
 
 impl LuRes {
-    pub fn new(prefix: String) -> Self {
-        Self { prefix }
+    pub fn new(prefix: String, image_base: PathBuf, image_extension: Option<String>) -> Self {
+        Self {
+            prefix,
+            image_base,
+            image_extension,
+        }
+    }
+
+    /// Create the helper for the `lu_res_*` options, with `base_url` as the default host
+    pub fn from_config(cfg: &DataOptions, base_url: &str, paths: &RouterPaths) -> Self {
+        Self::new(
+            cfg.lu_res_prefix
+                .clone()
+                .unwrap_or_else(|| base_url.to_string() + paths.res()),
+            cfg.lu_res_image_base.clone(),
+            Some(cfg.lu_res_image_extension.clone()).filter(|ext| !ext.is_empty()),
+        )
+    }
+
+    /// Get the URI of a client image path, see [`cleanup_path`]
+    pub fn image_href(&self, url: &Latin1Str) -> Option<String> {
+        cleanup_path(url, &self.image_base, self.image_extension.as_deref())
+            .map(|p| self.to_res_href(&p))
     }
 
     pub fn to_res_href(&self, path: &Path) -> String {
         format!("{}{}", self.prefix, path.display())
     }
+
+    /// Get the URI of the render component icon of an object
+    pub fn object_image(&self, data: &TypedDatabase<'_>, id: i32) -> Option<String> {
+        let comp = data.get_components(id);
+        let image = comp.render.and_then(|id| data.get_render_image(id));
+        image.and_then(|path| self.image_href(path))
+    }
 }
 
 pub enum Event {
@@ -108,6 +151,69 @@ pub fn spawn_handler(_path: &Path) -> EventSender {
     EventSender(tx)
 }
 
+fn is_update(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Access(AccessKind::Close(AccessMode::Write))
+            | EventKind::Create(CreateKind::File)
+            | EventKind::Modify(ModifyKind::Name(_))
+    )
+}
+
+/// Call `on_change` on the blocking thread pool whenever the file at `path` is written or replaced
+pub fn spawn_file_watcher<F>(path: &Path, on_change: F) -> Result<(), notify::Error>
+where
+    F: Fn(&Path) + Send + Sync + 'static,
+{
+    // Watch the directory, so that we also see the file being replaced
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().map(OsStr::to_owned);
+    let path: Arc<Path> = Arc::from(path);
+    let on_change = Arc::new(on_change);
+
+    // Setup the watcher
+    let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+    let mut watcher = recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let _ = tx.blocking_send(event);
+    })?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+    let rt = tokio::runtime::Handle::current();
+    rt.spawn(async move {
+        // The watcher stops when it is dropped
+        let _watcher = watcher;
+        while let Some(r) = rx.recv().await {
+            let e = match r {
+                Ok(e) => e,
+                Err(e) => {
+                    error!("filesystem watch failure: {}", e);
+                    continue;
+                }
+            };
+
+            debug!("filesystem watch event: {:?}", e);
+            if !is_update(&e.kind)
+                || !e
+                    .paths
+                    .iter()
+                    .any(|p| p.file_name() == file_name.as_deref())
+            {
+                continue;
+            }
+
+            let path = path.clone();
+            let on_change = on_change.clone();
+            if let Err(e) = tokio::task::spawn_blocking(move || on_change(&path)).await {
+                error!("file watch handler failed: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
 /// A single file
 #[derive(Debug, Copy, Clone, Serialize)]
 pub enum NodeKind {
@@ -117,6 +223,17 @@ pub enum NodeKind {
     Script,
 }
 
+impl NodeKind {
+    /// The MIME type to serve a file of this kind with
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::DirectDrawSurface => "image/vnd.ms-dds",
+            Self::Script => "text/x-lua",
+            Self::ZoneFile | Self::LevelFile => "application/octet-stream",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Node {
     /// Server side path: DO NOT SERIALIZE
@@ -132,6 +249,20 @@ pub struct ServerNode {
     pub abs_path: PathBuf,
 }
 
+/// A file in a [`DirListing`]
+#[derive(Debug, Clone, Serialize)]
+pub struct FileEntry {
+    pub crc: u32,
+    pub kind: NodeKind,
+}
+
+/// The direct children of a directory in the res tree
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DirListing {
+    pub dirs: BTreeSet<String>,
+    pub files: BTreeMap<String, FileEntry>,
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct Loader {
     /// Maps path CRCs to a node
@@ -149,6 +280,36 @@ impl Loader {
         self.entries.get(&crc)
     }
 
+    /// List the files and directories directly within `dir`
+    ///
+    /// `dir` is a path relative to the server side root, e.g. `client/res/maps`
+    pub fn list_dir(&self, dir: &Path) -> Option<DirListing> {
+        let mut listing = DirListing::default();
+        let mut found = false;
+        for (&crc, node) in &self.entries {
+            let Ok(rest) = node.public.rel_path.strip_prefix(dir) else {
+                continue;
+            };
+            found = true;
+            let mut components = rest.components();
+            let name = match components.next() {
+                Some(Component::Normal(name)) => name.to_string_lossy().into_owned(),
+                _ => continue,
+            };
+            if components.next().is_some() {
+                listing.dirs.insert(name);
+            } else {
+                let kind = node.public.kind;
+                listing.files.insert(name, FileEntry { crc, kind });
+            }
+        }
+        if found {
+            Some(listing)
+        } else {
+            None
+        }
+    }
+
     pub fn get_pki(&self, crc: u32) -> Option<&PackFileRef> {
         self.pki
             .files
@@ -1,10 +1,13 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use assembly_xml::localization::{Interner, Key, LocaleNodeRef, LocaleRoot as LocaleRootNode};
 use paradox_typed_db::ext::MissionKind;
 
 pub(crate) struct Keys {
+    pub activities: Key,
+    pub activity_name: Key,
     pub description: Key,
+    pub factions: Key,
     pub missions: Key,
     pub mission_text: Key,
     pub mission_tasks: Key,
@@ -18,7 +21,10 @@ pub(crate) struct Keys {
 impl Keys {
     fn new(strs: &mut Interner) -> Self {
         Keys {
+            activities: strs.intern("Activities"),
+            activity_name: strs.intern("ActivityName"),
             description: strs.intern("description"),
+            factions: strs.intern("Factions"),
             missions: strs.intern("Missions"),
             mission_text: strs.intern("MissionText"),
             mission_tasks: strs.intern("MissionTasks"),
@@ -94,6 +100,34 @@ impl LocaleRoot {
         None
     }
 
+    pub fn get_activity_name(&self, id: i32) -> Option<String> {
+        let keys = &self.root.keys;
+        let activities = self.root.root.as_ref().get_str(keys.activities)?;
+        if id > 0 {
+            if let Some(activity) = activities.get_int(id as u32) {
+                if let Some(name_node) = activity.get_str(keys.activity_name) {
+                    let name = name_node.value().unwrap();
+                    return Some(format!("{} | Activity #{}", name, id));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn get_faction_name(&self, id: i32) -> Option<String> {
+        let keys = &self.root.keys;
+        let factions = self.root.root.as_ref().get_str(keys.factions)?;
+        if id > 0 {
+            if let Some(faction) = factions.get_int(id as u32) {
+                if let Some(name_node) = faction.get_str(keys.name) {
+                    let name = name_node.value().unwrap();
+                    return Some(format!("{} | Faction #{}", name, id));
+                }
+            }
+        }
+        None
+    }
+
     pub fn get_skill_name_desc(&self, id: i32) -> (Option<String>, Option<String>) {
         let keys = &self.root.keys;
         let root = self.root.root.as_ref();
@@ -115,3 +149,200 @@ impl LocaleRoot {
         (the_name, the_desc)
     }
 }
+
+/// The base locale and additional ones by language (e.g. `de_DE`)
+#[derive(Clone)]
+pub struct Locales {
+    base: LocaleRoot,
+    by_lang: Arc<BTreeMap<String, LocaleRoot>>,
+    default_lang: String,
+}
+
+impl Locales {
+    /// The language of the base locale, i.e. `data.locale`
+    pub const BASE_LANG: &'static str = "en_US";
+
+    /// Create the locales, using the one for `default_lang` when no other is requested
+    ///
+    /// If there is no locale for `default_lang`, the base locale is the default.
+    pub fn new(
+        base: LocaleRoot,
+        by_lang: BTreeMap<String, LocaleRoot>,
+        default_lang: &str,
+    ) -> Self {
+        let default_lang = if default_lang == Self::BASE_LANG || by_lang.contains_key(default_lang)
+        {
+            default_lang
+        } else {
+            Self::BASE_LANG
+        };
+        Self {
+            base,
+            by_lang: Arc::new(by_lang),
+            default_lang: default_lang.to_string(),
+        }
+    }
+
+    /// The language of the default locale
+    pub fn default_lang(&self) -> &str {
+        &self.default_lang
+    }
+
+    pub fn default_locale(&self) -> &LocaleRoot {
+        self.get(&self.default_lang).unwrap_or(&self.base)
+    }
+
+    /// The locale for exactly `lang`
+    pub fn get(&self, lang: &str) -> Option<&LocaleRoot> {
+        if lang == Self::BASE_LANG {
+            Some(&self.base)
+        } else {
+            self.by_lang.get(lang)
+        }
+    }
+
+    /// The languages of all loaded locales, starting with the default one
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        let all = std::iter::once(Self::BASE_LANG).chain(self.by_lang.keys().map(String::as_str));
+        let default_lang = self.default_lang.as_str();
+        std::iter::once(default_lang).chain(all.filter(move |lang| *lang != default_lang))
+    }
+
+    /// Pick the locale that best matches an `Accept-Language` header value
+    ///
+    /// Languages are tried by descending quality, first by the full tag, then by
+    /// the primary subtag alone. If nothing matches, this is the default locale.
+    pub fn negotiate(&self, accept_language: &str) -> &LocaleRoot {
+        for tag in parse_accept_language(accept_language) {
+            if let Some(loc) = self.find(|lang| lang_eq(lang, tag)) {
+                return loc;
+            }
+            let primary = tag.split(['-', '_']).next().unwrap_or(tag);
+            let by_primary = |lang: &str| lang_eq(lang.split('_').next().unwrap_or(lang), primary);
+            if let Some(loc) = self.find(by_primary) {
+                return loc;
+            }
+        }
+        self.default_locale()
+    }
+
+    fn find(&self, pred: impl Fn(&str) -> bool) -> Option<&LocaleRoot> {
+        if pred(Self::BASE_LANG) {
+            return Some(&self.base);
+        }
+        self.by_lang
+            .iter()
+            .find(|(lang, _)| pred(lang))
+            .map(|(_, loc)| loc)
+    }
+}
+
+/// Compare a language of the locale file (`de_DE`) with a language tag (`de-de`)
+fn lang_eq(lang: &str, tag: &str) -> bool {
+    lang.len() == tag.len()
+        && lang.bytes().zip(tag.bytes()).all(|(a, b)| {
+            let b = if b == b'-' { b'_' } else { b };
+            a.eq_ignore_ascii_case(&b)
+        })
+}
+
+/// Get the language tags of an `Accept-Language` header, best first
+fn parse_accept_language(value: &str) -> Vec<&str> {
+    let mut tags: Vec<(&str, f32)> = value
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let tag = parts.next()?.trim();
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map(|q| q.parse().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            (!tag.is_empty() && tag != "*" && q > 0.0).then_some((tag, q))
+        })
+        .collect();
+    // stable, so equal weights keep the order of the header
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+    tags.into_iter().map(|(tag, _)| tag).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fs};
+
+    use assembly_xml::localization::load_locale;
+
+    use super::{lang_eq, parse_accept_language, LocaleRoot, Locales};
+
+    const SKILL_LOCALE_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<localization version="1.7">
+<locales count="1"><locale>en_US</locale></locales>
+<phrases count="2">
+<phrase id="SkillBehavior_42_name"><translation locale="en_US">Bubble Blast</translation></phrase>
+<phrase id="SkillBehavior_42_descriptionUI"><translation locale="en_US">Blows bubbles</translation></phrase>
+</phrases>
+</localization>
+"#;
+
+    fn load_test_locale(file_name: &str, xml: &str) -> LocaleRoot {
+        let path = std::env::temp_dir().join(file_name);
+        fs::write(&path, xml).unwrap();
+        let root = load_locale(&path).map(LocaleRoot::new);
+        fs::remove_file(&path).unwrap();
+        root.unwrap()
+    }
+
+    #[test]
+    fn test_skill_name_desc() {
+        let loc = load_test_locale("paradox-test-skill-locale.xml", SKILL_LOCALE_XML);
+        let (name, desc) = loc.get_skill_name_desc(42);
+        assert_eq!(name.as_deref(), Some("Bubble Blast | Skill #42"));
+        assert_eq!(desc.as_deref(), Some("Blows bubbles"));
+    }
+
+    #[test]
+    fn test_skill_name_desc_missing() {
+        let loc = load_test_locale("paradox-test-skill-missing.xml", SKILL_LOCALE_XML);
+        assert_eq!(loc.get_skill_name_desc(43), (None, None));
+        assert_eq!(loc.get_skill_name_desc(-42), (None, None));
+    }
+
+    #[test]
+    fn test_default_lang() {
+        let de_xml = SKILL_LOCALE_XML.replace("Bubble Blast", "Blasenblaster");
+        let de = load_test_locale("paradox-test-default-lang-de.xml", &de_xml);
+        let base = load_test_locale("paradox-test-default-lang.xml", SKILL_LOCALE_XML);
+        let mut by_lang = BTreeMap::new();
+        by_lang.insert("de_DE".to_string(), de);
+        let name = |loc: &LocaleRoot| loc.get_skill_name_desc(42).0.unwrap();
+
+        let locales = Locales::new(base.clone(), by_lang.clone(), "de_DE");
+        assert_eq!(locales.default_lang(), "de_DE");
+        assert_eq!(name(locales.default_locale()), "Blasenblaster | Skill #42");
+        assert_eq!(
+            name(locales.negotiate("fr-FR")),
+            "Blasenblaster | Skill #42"
+        );
+        assert_eq!(name(locales.negotiate("en")), "Bubble Blast | Skill #42");
+        let languages: Vec<&str> = locales.languages().collect();
+        assert_eq!(languages, ["de_DE", "en_US"]);
+
+        let locales = Locales::new(base, by_lang, "fr_FR");
+        assert_eq!(locales.default_lang(), "en_US");
+        assert_eq!(name(locales.negotiate("fr-FR")), "Bubble Blast | Skill #42");
+    }
+
+    #[test]
+    fn test_parse_accept_language() {
+        let tags = parse_accept_language("en;q=0.8, de-DE, fr;q=0.9, *;q=0.1, es;q=0");
+        assert_eq!(tags, vec!["de-DE", "fr", "en"]);
+        assert!(parse_accept_language("").is_empty());
+    }
+
+    #[test]
+    fn test_lang_eq() {
+        assert!(lang_eq("de_DE", "de-de"));
+        assert!(lang_eq("en_US", "en_US"));
+        assert!(!lang_eq("en_US", "en-GB"));
+        assert!(!lang_eq("en_US", "en"));
+    }
+}
@@ -3,6 +3,9 @@ use std::{convert::TryFrom, fmt};
 use latin1str::Latin1Str;
 use serde::{Deserialize, Serialize};
 
+pub mod check;
+pub mod db;
+pub mod events;
 pub mod fs;
 pub mod locale;
 pub mod maps;
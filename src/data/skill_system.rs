@@ -1,5 +1,18 @@
 use latin1str::Latin1Str;
 
+/// The `templateID` of the OverTime behavior
+pub const BEHAVIOR_TYPE_OVER_TIME: i32 = 12;
+
+/// Whether the parameter `key` of a behavior with the template `behavior_type` refers to another behavior
+///
+/// The `action` of an OverTime behavior is a skill ID, not a behavior ID.
+pub fn is_behavior_edge(behavior_type: Option<i32>, key: &Latin1Str) -> bool {
+    match behavior_type {
+        Some(BEHAVIOR_TYPE_OVER_TIME) if key.as_bytes() == b"action" => false,
+        _ => match_action_key(key),
+    }
+}
+
 pub fn match_action_key(key: &Latin1Str) -> bool {
     matches!(
         key.as_bytes(),
@@ -44,3 +57,23 @@ pub fn match_action_key(key: &Latin1Str) -> bool {
             | b"behavior 9"
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use latin1str::Latin1String;
+
+    use super::{is_behavior_edge, BEHAVIOR_TYPE_OVER_TIME};
+
+    #[test]
+    fn test_over_time_action_is_not_an_edge() {
+        let action = Latin1String::encode("action");
+        assert!(!is_behavior_edge(Some(BEHAVIOR_TYPE_OVER_TIME), &action));
+        assert!(is_behavior_edge(Some(1), &action));
+        assert!(is_behavior_edge(None, &action));
+
+        let behavior = Latin1String::encode("behavior 1");
+        assert!(is_behavior_edge(Some(BEHAVIOR_TYPE_OVER_TIME), &behavior));
+        let delay = Latin1String::encode("delay");
+        assert!(!is_behavior_edge(Some(1), &delay));
+    }
+}
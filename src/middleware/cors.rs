@@ -1,8 +1,16 @@
-use http::{header::AUTHORIZATION, Method};
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_util::future::Either;
+use http::{header::AUTHORIZATION, HeaderName, Method, Request, Response};
 use once_cell::sync::Lazy;
-use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower::{Layer, Service};
+use tower_http::cors::{AllowOrigin, Cors, CorsLayer};
 
-use crate::config::CorsOptions;
+use super::request_id::X_REQUEST_ID;
+use crate::{config::CorsOptions, services::RouterPaths};
 
 pub trait CorsLayerExt<C> {
     fn configure(config: &C) -> Self;
@@ -14,6 +22,7 @@ impl CorsLayerExt<CorsOptions> for CorsLayer {
     fn configure(cfg: &CorsOptions) -> Self {
         Self::new()
             .allow_headers([AUTHORIZATION])
+            .expose_headers([HeaderName::from_static(X_REQUEST_ID)])
             .allow_methods([
                 Method::OPTIONS,
                 Method::GET,
@@ -26,3 +35,111 @@ impl CorsLayerExt<CorsOptions> for CorsLayer {
             })
     }
 }
+
+/// The paths that CORS applies to
+struct CorsScope {
+    router: RouterPaths,
+    prefixes: Vec<String>,
+}
+
+impl CorsScope {
+    /// Whether `path` is one of the prefixes or below one, after the base
+    fn contains(&self, path: &str) -> bool {
+        let path = self.router.strip_base(path);
+        self.prefixes.iter().any(|prefix| {
+            path.strip_prefix(prefix.as_str())
+                .map_or(false, |rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
+/// A [`CorsLayer`] for only some paths, e.g. the API
+///
+/// This is applied outside of the authorization, so that preflight requests and
+/// errors still get the `Access-Control-*` headers.
+pub struct ScopedCorsLayer {
+    cors: CorsLayer,
+    scope: Arc<CorsScope>,
+}
+
+impl ScopedCorsLayer {
+    pub fn new(cfg: &CorsOptions, router: &RouterPaths) -> Self {
+        let prefixes = match &cfg.paths {
+            Some(paths) => paths.clone(),
+            None => vec![router.api().to_string()],
+        };
+        Self {
+            cors: CorsLayer::configure(cfg),
+            scope: Arc::new(CorsScope {
+                router: router.clone(),
+                prefixes,
+            }),
+        }
+    }
+}
+
+impl<S: Clone> Layer<S> for ScopedCorsLayer {
+    type Service = ScopedCors<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ScopedCors {
+            cors: self.cors.layer(inner.clone()),
+            inner,
+            scope: self.scope.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ScopedCors<S> {
+    inner: S,
+    cors: Cors<S>,
+    scope: Arc<CorsScope>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for ScopedCors<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    ResBody: Default,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Either<S::Future, <Cors<S> as Service<Request<ReqBody>>>::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.inner.poll_ready(cx) {
+            Poll::Ready(Ok(())) => self.cors.poll_ready(cx),
+            other => other,
+        }
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        if self.scope.contains(req.uri().path()) {
+            Either::Right(self.cors.call(req))
+        } else {
+            Either::Left(self.inner.call(req))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::GeneralOptions, services::RouterPaths};
+
+    use super::CorsScope;
+
+    #[test]
+    fn test_cors_scope() {
+        let json = r#"{"public": false, "domain": "localhost", "base": "paradox"}"#;
+        let general: GeneralOptions = serde_json::from_str(json).unwrap();
+        let scope = CorsScope {
+            router: RouterPaths::new(&general),
+            prefixes: vec![String::from("/api")],
+        };
+        assert!(scope.contains("/paradox/api/v0/tables"));
+        assert!(scope.contains("/api"));
+        assert!(!scope.contains("/paradox/apis"));
+        assert!(!scope.contains("/paradox/lu-res/textures"));
+        assert!(!scope.contains("/paradox/objects/1"));
+    }
+}
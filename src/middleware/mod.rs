@@ -2,7 +2,13 @@
 
 pub mod cors;
 pub mod redirect;
-pub use cors::CorsLayerExt;
+pub use cors::{CorsLayerExt, ScopedCors, ScopedCorsLayer};
 pub use redirect::{Redirect, RedirectLayer};
 mod public;
+pub mod rate_limit;
 pub use public::{PublicOr, PublicOrLayer};
+pub use rate_limit::{PeerAddr, RateLimitLayer};
+pub mod request_id;
+pub use request_id::RequestIdSpan;
+mod security_headers;
+pub use security_headers::{SecurityHeaders, SecurityHeadersLayer};
@@ -0,0 +1,286 @@
+//! # Per-key request quotas
+//!
+//! Requests are assigned to a token bucket by their `Authorization` header or
+//! `apiKey` query parameter, if the auth config allows them. All other requests,
+//! including those with unknown credentials, get an anonymous bucket for the IP
+//! address of the client, see [`PeerAddr`].
+
+use std::{
+    collections::HashMap,
+    future::{Future, Ready},
+    hash::Hash,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{self, Poll},
+    time::Instant,
+};
+
+use http::{header::RETRY_AFTER, HeaderValue, Request, Response, StatusCode};
+use pin_project::pin_project;
+use tower::{Layer, Service};
+
+use crate::{
+    auth::AuthImpl,
+    config::{AuthConfig, RateLimitConfig},
+};
+
+/// Drop full buckets once there are more than this many keys
+const MAX_IDLE_BUCKETS: usize = 1024;
+
+/// The address of the client, which the server adds to the extensions of each request
+#[derive(Debug, Clone, Copy)]
+pub struct PeerAddr(pub SocketAddr);
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn full(per_minute: u32, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(per_minute),
+            last: now,
+        }
+    }
+
+    fn refill(&mut self, per_minute: u32, now: Instant) {
+        let capacity = f64::from(per_minute);
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * capacity / 60.0).min(capacity);
+        self.last = now;
+    }
+
+    /// Take a token, or return the number of seconds until one is available
+    fn take(&mut self, per_minute: u32, now: Instant) -> Result<(), u64> {
+        self.refill(per_minute, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else if per_minute == 0 {
+            Err(60)
+        } else {
+            let missing = 1.0 - self.tokens;
+            Err((missing * 60.0 / f64::from(per_minute)).ceil() as u64)
+        }
+    }
+}
+
+/// Take a token from the bucket of `key`, dropping full buckets if there are too many
+fn take_from<K: Hash + Eq>(
+    buckets: &Mutex<HashMap<K, Bucket>>,
+    key: K,
+    per_minute: u32,
+    now: Instant,
+) -> Result<(), u64> {
+    let mut buckets = buckets.lock().unwrap();
+    if buckets.len() >= MAX_IDLE_BUCKETS && !buckets.contains_key(&key) {
+        buckets.retain(|_, bucket| {
+            bucket.refill(per_minute, now);
+            bucket.tokens < f64::from(per_minute)
+        });
+    }
+    buckets
+        .entry(key)
+        .or_insert_with(|| Bucket::full(per_minute, now))
+        .take(per_minute, now)
+}
+
+struct RateLimitCore {
+    auth: AuthImpl,
+    per_minute: u32,
+    anonymous_per_minute: u32,
+    keyed: Mutex<HashMap<String, Bucket>>,
+    anonymous: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimitCore {
+    fn new(auth: AuthImpl, cfg: &RateLimitConfig) -> Self {
+        Self {
+            auth,
+            per_minute: cfg.per_minute,
+            anonymous_per_minute: cfg.anonymous_per_minute,
+            keyed: Mutex::new(HashMap::new()),
+            anonymous: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn check(&self, key: Option<String>, peer: IpAddr, now: Instant) -> Result<(), u64> {
+        match key {
+            Some(key) => take_from(&self.keyed, key, self.per_minute, now),
+            None => take_from(&self.anonymous, peer, self.anonymous_per_minute, now),
+        }
+    }
+
+    fn check_request<B>(&self, request: &Request<B>, now: Instant) -> Result<(), u64> {
+        self.check(self.auth.credentials(request), peer_ip(request), now)
+    }
+}
+
+/// The IP address of the client, or the unspecified address if the server didn't set it
+fn peer_ip<B>(request: &Request<B>) -> IpAddr {
+    request
+        .extensions()
+        .get::<PeerAddr>()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |peer| peer.0.ip())
+}
+
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    core: Option<Arc<RateLimitCore>>,
+}
+
+impl RateLimitLayer {
+    /// Create a new layer, which is a no-op if there is no `[auth.rate_limit]` section
+    pub fn new(cfg: &Option<AuthConfig>) -> Self {
+        let core = cfg.as_ref().and_then(|auth| {
+            let rate_limit = auth.rate_limit.as_ref()?;
+            Some(Arc::new(RateLimitCore::new(
+                AuthImpl::new(Some(auth)),
+                rate_limit,
+            )))
+        });
+        Self { core }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            core: self.core.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    core: Option<Arc<RateLimitCore>>,
+}
+
+#[pin_project(project = RateLimitFutureProj)]
+pub enum RateLimitFuture<F, B> {
+    Inner(#[pin] F),
+    Ready(#[pin] Ready<Response<B>>),
+}
+
+impl<F, B, E> Future for RateLimitFuture<F, B>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+{
+    type Output = Result<Response<B>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            RateLimitFutureProj::Inner(f) => f.poll(cx),
+            RateLimitFutureProj::Ready(f) => f.poll(cx).map(Ok),
+        }
+    }
+}
+
+impl<B, S, ResBody> Service<Request<B>> for RateLimit<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    ResBody: Default,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = RateLimitFuture<S::Future, ResBody>;
+
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        if let Some(core) = &self.core {
+            if let Err(retry_after) = core.check_request(&req, Instant::now()) {
+                let mut r = Response::new(ResBody::default());
+                *r.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+                r.headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+                return RateLimitFuture::Ready(std::future::ready(r));
+            }
+        }
+        RateLimitFuture::Inner(self.inner.call(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+        time::{Duration, Instant},
+    };
+
+    use http::Request;
+
+    use crate::{
+        auth::AuthImpl,
+        config::{AuthConfig, RateLimitConfig},
+    };
+
+    use super::{Bucket, PeerAddr, RateLimitCore};
+
+    const RATE_LIMIT: RateLimitConfig = RateLimitConfig {
+        per_minute: 60,
+        anonymous_per_minute: 1,
+    };
+
+    #[test]
+    fn test_bucket() {
+        let start = Instant::now();
+        let mut bucket = Bucket::full(60, start);
+        for _ in 0..60 {
+            assert_eq!(bucket.take(60, start), Ok(()));
+        }
+        assert_eq!(bucket.take(60, start), Err(1));
+        let later = start + Duration::from_millis(500);
+        assert_eq!(bucket.take(60, later), Err(1));
+        let later = start + Duration::from_secs(1);
+        assert_eq!(bucket.take(60, later), Ok(()));
+        assert_eq!(Bucket::full(0, start).take(0, start), Err(60));
+    }
+
+    #[test]
+    fn test_anonymous_by_peer() {
+        let core = RateLimitCore::new(AuthImpl::None, &RATE_LIMIT);
+        let now = Instant::now();
+        let a = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let b = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(core.check(None, a, now), Ok(()));
+        assert_eq!(core.check(None, a, now), Err(60));
+        assert_eq!(core.check(None, b, now), Ok(()));
+        assert_eq!(core.check(Some(String::from("key")), a, now), Ok(()));
+    }
+
+    #[test]
+    fn test_unknown_key_is_anonymous() {
+        let auth = AuthConfig {
+            basic: Some([(String::from("user"), String::from("pass"))].into()),
+            user_agents: vec![],
+            api_keys: vec![String::from("secret")],
+            rate_limit: None,
+        };
+        let core = RateLimitCore::new(AuthImpl::new(Some(&auth)), &RATE_LIMIT);
+        let now = Instant::now();
+        let peer = PeerAddr(SocketAddr::from(([10, 0, 0, 1], 4000)));
+        let request = |uri: &str| {
+            let mut request = Request::get(uri).body(()).unwrap();
+            request.extensions_mut().insert(peer);
+            request
+        };
+        assert_eq!(core.check_request(&request("/?apiKey=bogus1"), now), Ok(()));
+        // A different unknown key still uses the (now empty) bucket of the IP
+        assert_eq!(
+            core.check_request(&request("/?apiKey=bogus2"), now),
+            Err(60)
+        );
+        assert_eq!(core.check_request(&request("/?apiKey=secret"), now), Ok(()));
+        assert_eq!(core.keyed.lock().unwrap().len(), 1);
+    }
+}
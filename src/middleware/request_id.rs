@@ -0,0 +1,40 @@
+use http::{HeaderName, Request};
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::MakeSpan,
+};
+use tracing::Span;
+
+/// The header that holds the ID of a request
+pub const X_REQUEST_ID: &str = "x-request-id";
+
+/// Layer that adds an `X-Request-Id` to every request that doesn't have one
+pub fn set_request_id_layer() -> SetRequestIdLayer<MakeRequestUuid> {
+    SetRequestIdLayer::new(HeaderName::from_static(X_REQUEST_ID), MakeRequestUuid)
+}
+
+/// Layer that copies the `X-Request-Id` of the request to the response
+pub fn propagate_request_id_layer() -> PropagateRequestIdLayer {
+    PropagateRequestIdLayer::new(HeaderName::from_static(X_REQUEST_ID))
+}
+
+/// Creates the tracing span for a request, including its ID
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RequestIdSpan;
+
+impl<B> MakeSpan<B> for RequestIdSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let id = request
+            .headers()
+            .get(X_REQUEST_ID)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("-");
+        tracing::debug_span!(
+            "request",
+            id = %id,
+            method = %request.method(),
+            uri = %request.uri(),
+            version = ?request.version(),
+        )
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{self, Poll},
+};
+
+use futures_util::ready;
+use http::{
+    header::{
+        CONTENT_SECURITY_POLICY, CONTENT_TYPE, REFERRER_POLICY, X_CONTENT_TYPE_OPTIONS,
+        X_FRAME_OPTIONS,
+    },
+    HeaderMap, HeaderValue, Response,
+};
+use pin_project::pin_project;
+use tower::{Layer, Service};
+
+use crate::config::SecurityOptions;
+
+static NOSNIFF: HeaderValue = HeaderValue::from_static("nosniff");
+
+/// Whether the `Content-Type` of a response is HTML
+fn is_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map_or(false, |v| v.starts_with("text/html"))
+}
+
+struct SecurityHeadersCore {
+    csp: Option<HeaderValue>,
+    referrer_policy: Option<HeaderValue>,
+    frame_options: Option<HeaderValue>,
+}
+
+impl SecurityHeadersCore {
+    /// Add the headers that the response doesn't set itself
+    ///
+    /// `X-Content-Type-Options` is set on every response, the others only on HTML.
+    fn apply(&self, headers: &mut HeaderMap) {
+        headers
+            .entry(X_CONTENT_TYPE_OPTIONS)
+            .or_insert_with(|| NOSNIFF.clone());
+        if !is_html(headers) {
+            return;
+        }
+        let html_headers = [
+            (CONTENT_SECURITY_POLICY, &self.csp),
+            (REFERRER_POLICY, &self.referrer_policy),
+            (X_FRAME_OPTIONS, &self.frame_options),
+        ];
+        for (name, value) in html_headers.iter() {
+            if let Some(value) = value {
+                headers.entry(name).or_insert_with(|| value.clone());
+            }
+        }
+    }
+}
+
+pub struct SecurityHeadersLayer {
+    core: Arc<SecurityHeadersCore>,
+}
+
+impl SecurityHeadersLayer {
+    pub fn new(cfg: &SecurityOptions) -> Self {
+        Self {
+            core: Arc::new(SecurityHeadersCore {
+                csp: cfg.csp.clone(),
+                referrer_policy: cfg.referrer_policy.clone(),
+                frame_options: cfg.frame_options.clone(),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for SecurityHeadersLayer {
+    type Service = SecurityHeaders<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SecurityHeaders {
+            inner,
+            core: self.core.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SecurityHeaders<S> {
+    inner: S,
+    core: Arc<SecurityHeadersCore>,
+}
+
+#[pin_project]
+pub struct SecurityHeadersFuture<F> {
+    #[pin]
+    inner: F,
+    core: Arc<SecurityHeadersCore>,
+}
+
+impl<F, B, E> Future for SecurityHeadersFuture<F>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+{
+    type Output = Result<Response<B>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut r = ready!(this.inner.poll(cx))?;
+        this.core.apply(r.headers_mut());
+        Poll::Ready(Ok(r))
+    }
+}
+
+impl<R, S, B> Service<R> for SecurityHeaders<S>
+where
+    S: Service<R, Response = Response<B>>,
+{
+    type Response = Response<B>;
+    type Error = S::Error;
+    type Future = SecurityHeadersFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        SecurityHeadersFuture {
+            inner: self.inner.call(req),
+            core: self.core.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{
+        header::{CONTENT_SECURITY_POLICY, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS},
+        HeaderMap, HeaderValue,
+    };
+
+    use super::SecurityHeadersCore;
+
+    #[test]
+    fn test_apply() {
+        let core = SecurityHeadersCore {
+            csp: Some(HeaderValue::from_static("default-src 'self'")),
+            referrer_policy: None,
+            frame_options: Some(HeaderValue::from_static("SAMEORIGIN")),
+        };
+
+        let mut json = HeaderMap::new();
+        json.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        core.apply(&mut json);
+        assert_eq!(json[X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert!(!json.contains_key(CONTENT_SECURITY_POLICY));
+
+        let mut html = HeaderMap::new();
+        html.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        html.insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+        core.apply(&mut html);
+        assert_eq!(html[CONTENT_SECURITY_POLICY], "default-src 'self'");
+        // headers of the response itself are kept
+        assert_eq!(html[X_FRAME_OPTIONS], "DENY");
+        assert_eq!(html.len(), 4);
+    }
+}
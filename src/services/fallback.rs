@@ -30,13 +30,13 @@ impl FallbackService {
         }
     }
 
-    /// The routes that require the fallback
+    /// The routes that require the fallback, relative to the API prefix
     pub(super) fn requires_fallback(path: &str) -> bool {
-        path.starts_with("/api/v0/maps/") || path.starts_with("/api/v0/scripts/")
+        path.starts_with("/v0/maps/") || path.starts_with("/v0/scripts/")
     }
 
     /// The prefix path to remove from the request before passing to [ServeDir]
-    const PREFIX: &'static str = "/api/v0";
+    const PREFIX: &'static str = "/v0";
 }
 
 impl<B> Service<HttpRequest<B>> for FallbackService
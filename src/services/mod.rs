@@ -4,8 +4,7 @@ use std::{
 };
 
 pub mod router;
-use paradox_typed_db::TypedDatabase;
-pub use router::BaseRouter;
+pub use router::{BaseRouter, RouterPaths};
 mod fallback;
 pub use fallback::FallbackService;
 use tower_http::services::ServeDir;
@@ -13,8 +12,8 @@ mod template;
 pub use template::SpaDynamic;
 
 use crate::{
-    config::DataOptions,
-    data::{fs::LuRes, locale::LocaleRoot},
+    config::{DataOptions, MetaConfig},
+    data::{db::SharedDb, fs::LuRes, locale::Locales},
 };
 
 #[derive(Debug)]
@@ -55,9 +54,11 @@ impl fmt::Display for Error {
 
 pub fn app(
     cfg: &DataOptions,
-    tydb: &'static TypedDatabase<'static>,
-    locale_root: LocaleRoot,
+    data: SharedDb,
+    locales: Locales,
     base_url: &str,
+    paths: &RouterPaths,
+    meta: &MetaConfig,
 ) -> Result<ServeDir<SpaDynamic>, color_eyre::Report> {
     let spa_path = &cfg.explorer_spa;
     let spa_index = spa_path.join("index.html");
@@ -68,13 +69,13 @@ pub fn app(
     template::spawn_watcher(&spa_index, hb.clone())?;
 
     // Set up the application
-    let res = LuRes::new(
-        cfg.lu_res_prefix
-            .clone()
-            .unwrap_or_else(|| base_url.to_string() + router::RES_PREFIX),
-    );
-    let spa_dynamic = template::SpaDynamic::new(tydb, locale_root, res, hb, base_url);
+    let res = LuRes::from_config(cfg, base_url, paths);
+    let spa_dynamic = template::SpaDynamic::new(data, locales, res, hb, base_url, meta);
+    // Serve e.g. `index.js.br` or `index.js.gz` in place of `index.js` when
+    // they exist and the client accepts that encoding
     Ok(ServeDir::new(spa_path)
+        .precompressed_br()
+        .precompressed_gzip()
         .append_index_html_on_directories(false)
         .fallback(spa_dynamic))
 }
@@ -1,6 +1,8 @@
 use std::{
+    borrow::Cow,
     io,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
 };
 
@@ -16,6 +18,7 @@ use tower::Service;
 use tower_http::services::fs::ServeFileSystemResponseBody;
 
 use super::{Error, FallbackService};
+use crate::config::GeneralOptions;
 
 #[pin_project(project = BaseRouterResponseBodyProj)]
 pub enum BaseRouterResponseBody<A, P, S> {
@@ -68,24 +71,86 @@ where
     }
 }
 
+/// The paths that the [`BaseRouter`] dispatches on
+#[derive(Debug, Clone)]
+pub struct RouterPaths {
+    /// The mount path of the whole app from `general.base`, e.g. `/paradox`, or empty
+    base: String,
+    api: String,
+    res: String,
+}
+
+impl RouterPaths {
+    pub fn new(cfg: &GeneralOptions) -> Self {
+        let base = cfg
+            .base
+            .as_deref()
+            .map(|b| b.trim_matches('/'))
+            .filter(|b| !b.is_empty())
+            .map(|b| format!("/{}", b))
+            .unwrap_or_default();
+        Self {
+            base,
+            api: cfg.api_prefix.clone(),
+            res: cfg.res_prefix.clone(),
+        }
+    }
+
+    /// The path of the API below the base, e.g. `/api`
+    pub fn api(&self) -> &str {
+        &self.api
+    }
+
+    /// The path of the `lu-res` cache below the base, e.g. `/lu-res`
+    pub fn res(&self) -> &str {
+        &self.res
+    }
+
+    /// Remove the base from `path_and_query`
+    ///
+    /// Paths without the base are kept as they are, because a reverse proxy may
+    /// already have removed it.
+    pub(crate) fn strip_base<'a>(&self, path_and_query: &'a str) -> Cow<'a, str> {
+        if self.base.is_empty() {
+            return Cow::Borrowed(path_and_query);
+        }
+        match path_and_query.strip_prefix(self.base.as_str()) {
+            Some(rest) if rest.starts_with('/') => Cow::Borrowed(rest),
+            Some(rest) if rest.is_empty() || rest.starts_with('?') => {
+                Cow::Owned(format!("/{}", rest))
+            }
+            _ => Cow::Borrowed(path_and_query),
+        }
+    }
+}
+
+/// Copy `uri` with `path_and_query` in place of its own
+fn with_path(uri: &Uri, path_and_query: &str) -> Uri {
+    let mut parts = uri::Parts::default();
+    parts.scheme = uri.scheme().cloned();
+    parts.authority = uri.authority().cloned();
+    let src_path_bytes = Bytes::copy_from_slice(path_and_query.as_bytes());
+    parts.path_and_query = PathAndQuery::from_maybe_shared(src_path_bytes).ok();
+    Uri::from_parts(parts).unwrap()
+}
+
 #[derive(Clone)]
 pub struct BaseRouter<A, P, S> {
     api: A,
     app: P,
     res: S,
     fallback: FallbackService,
+    paths: Arc<RouterPaths>,
 }
 
-pub const RES_PREFIX: &str = "/lu-res";
-pub const API_PREFIX: &str = "/api";
-
 impl<A, P, S> BaseRouter<A, P, S> {
-    pub fn new(api: A, app: P, res: S, fallback: FallbackService) -> Self {
+    pub fn new(api: A, app: P, res: S, fallback: FallbackService, paths: RouterPaths) -> Self {
         Self {
             api,
             app,
             res,
             fallback,
+            paths: Arc::new(paths),
         }
     }
 }
@@ -128,26 +193,25 @@ where
     }
 
     fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let paths = self.paths.clone();
         let uri = req.uri_mut();
-        if let Some(path_and_query) = uri.path_and_query().map(PathAndQuery::as_str) {
-            if FallbackService::requires_fallback(path_and_query) {
-                return self
-                    .fallback
-                    .call(req)
-                    .map(
-                        |r: Result<http::Response<ServeFileSystemResponseBody>, io::Error>| {
-                            r.map(|r| r.map(BaseRouterResponseBody::Fallback))
-                        },
-                    )
-                    .boxed();
-            }
-            if let Some(rest) = path_and_query.strip_prefix(API_PREFIX) {
-                let mut parts = uri::Parts::default();
-                parts.scheme = uri.scheme().cloned();
-                parts.authority = uri.authority().cloned();
-                let src_path_bytes = Bytes::copy_from_slice(rest.as_bytes());
-                parts.path_and_query = PathAndQuery::from_maybe_shared(src_path_bytes).ok();
-                *uri = Uri::from_parts(parts).unwrap();
+        if let Some(full_path_and_query) = uri.path_and_query().map(PathAndQuery::as_str) {
+            let stripped = paths.strip_base(full_path_and_query);
+            let path_and_query = stripped.as_ref();
+            if let Some(rest) = path_and_query.strip_prefix(paths.api()) {
+                let fallback = FallbackService::requires_fallback(rest);
+                *uri = with_path(uri, rest);
+                if fallback {
+                    return self
+                        .fallback
+                        .call(req)
+                        .map(
+                            |r: Result<http::Response<ServeFileSystemResponseBody>, io::Error>| {
+                                r.map(|r| r.map(BaseRouterResponseBody::Fallback))
+                            },
+                        )
+                        .boxed();
+                }
                 return self
                     .api
                     .call(req)
@@ -157,13 +221,8 @@ where
                     })
                     .boxed();
             }
-            if let Some(rest) = path_and_query.strip_prefix(RES_PREFIX) {
-                let mut parts = uri::Parts::default();
-                parts.scheme = uri.scheme().cloned();
-                parts.authority = uri.authority().cloned();
-                parts.path_and_query =
-                    PathAndQuery::from_maybe_shared(Bytes::copy_from_slice(rest.as_bytes())).ok();
-                *uri = Uri::from_parts(parts).unwrap();
+            if let Some(rest) = path_and_query.strip_prefix(paths.res()) {
+                *uri = with_path(uri, rest);
                 return self
                     .res
                     .call(req)
@@ -172,6 +231,9 @@ where
                     })
                     .boxed();
             }
+            if path_and_query != full_path_and_query {
+                *uri = with_path(uri, path_and_query);
+            }
         }
         self.app
             .call(req)
@@ -179,3 +241,23 @@ where
             .boxed()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::RouterPaths;
+
+    #[test]
+    fn test_strip_base() {
+        let paths = RouterPaths {
+            base: String::from("/paradox"),
+            api: String::from("/api"),
+            res: String::from("/lu-res"),
+        };
+        assert_eq!(paths.strip_base("/paradox/api/v0/tables"), "/api/v0/tables");
+        assert_eq!(paths.strip_base("/paradox"), "/");
+        assert_eq!(paths.strip_base("/paradox?x=1"), "/?x=1");
+        // already removed by a proxy
+        assert_eq!(paths.strip_base("/api/v0/tables"), "/api/v0/tables");
+        assert_eq!(paths.strip_base("/paradoxical"), "/paradoxical");
+    }
+}
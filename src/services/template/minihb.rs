@@ -1,5 +1,25 @@
+use std::fmt::Write;
+
 use regex::{Captures, Regex};
 
+/// Escape text for use in HTML content and attribute values
+fn escape_html(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// A minimal handlebars-like template
+///
+/// `{{name}}` is replaced with the HTML-escaped field, `{{{name}}}` with the raw
+/// field, e.g. for a `<script>` tag.
 pub struct Template {
     pattern: Regex,
     text: String,
@@ -15,7 +35,7 @@ impl Template {
     pub fn new() -> Self {
         Self {
             text: String::new(),
-            pattern: Regex::new(r"\{\{([a-z_]+)\}\}").unwrap(),
+            pattern: Regex::new(r"\{\{\{([a-z_]+)\}\}\}|\{\{([a-z_]+)\}\}").unwrap(),
         }
     }
 
@@ -25,7 +45,16 @@ impl Template {
 
     pub fn render<T: Lookup>(&self, data: &T) -> String {
         self.pattern
-            .replace_all(&self.text, |cap: &Captures| data.field(&cap[1]).to_string())
+            .replace_all(&self.text, |cap: &Captures| match cap.get(1) {
+                Some(raw) => data.field(raw.as_str()).to_string(),
+                None => {
+                    let mut text = String::new();
+                    write!(text, "{}", data.field(&cap[2])).unwrap();
+                    let mut out = String::with_capacity(text.len());
+                    escape_html(&text, &mut out);
+                    out
+                }
+            })
             .into_owned()
     }
 }
@@ -43,6 +72,8 @@ mod tests {
             match key {
                 "a" => &"Hello",
                 "b" => &"World",
+                "name" => &"Bob \"the\" <Builder> & 'Co'",
+                "script" => &"<script>{}</script>",
                 _ => &"",
             }
         }
@@ -54,4 +85,14 @@ mod tests {
         template.set_text("{{a}}, {{b}}!");
         assert_eq!(template.render(&A), "Hello, World!");
     }
+
+    #[test]
+    fn test_template_escapes_fields() {
+        let mut template = super::Template::new();
+        template.set_text("<meta content=\"{{name}}\">{{{script}}}");
+        assert_eq!(
+            template.render(&A),
+            "<meta content=\"Bob &quot;the&quot; &lt;Builder&gt; &amp; &#39;Co&#39;\"><script>{}</script>"
+        );
+    }
 }
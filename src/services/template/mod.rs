@@ -1,5 +1,5 @@
 use color_eyre::eyre::Context;
-use http::{uri::PathAndQuery, Response};
+use http::{header::ACCEPT_LANGUAGE, uri::PathAndQuery, Response};
 use notify::{
     event::{AccessKind, AccessMode, EventKind, RemoveKind},
     recommended_watcher, RecursiveMode, Watcher,
@@ -25,14 +25,17 @@ use tracing::{debug, error, info};
 mod minihb;
 pub(crate) use minihb::Template;
 
+use crate::api::rev::ReverseLookup;
+use crate::config::MetaConfig;
 use crate::data::{
-    fs::{cleanup_path, LuRes},
-    locale::LocaleRoot,
+    db::SharedDb,
+    fs::LuRes,
+    locale::{LocaleRoot, Locales},
 };
 
-fn make_meta_template(text: &str) -> Cow<str> {
+fn make_meta_template(text: &str) -> String {
     let re = Regex::new("<meta\\s+(name|property)=\"(.*?)\"\\s+content=\"(.*)\"\\s*/?>").unwrap();
-    re.replace_all(text, |cap: &Captures| {
+    let text = re.replace_all(text, |cap: &Captures| {
         let kind = &cap[1];
         let name = &cap[2];
         let value = match name {
@@ -46,7 +49,9 @@ fn make_meta_template(text: &str) -> Cow<str> {
             _ => &cap[3],
         };
         format!("<meta {}=\"{}\" content=\"{}\">", kind, name, value)
-    })
+    });
+    // Structured data goes at the end of the head
+    text.replacen("</head>", "{{{json_ld}}}</head>", 1)
 }
 
 pub struct FsEventHandler {
@@ -154,7 +159,7 @@ fn mission_get_impl(data: &'_ TypedDatabase<'_>, loc: LocaleRoot, res: LuRes, id
             kind = MissionKind::Achievement;
             if let Some(icon_id) = mission.mission_icon_id {
                 if let Some(path) = data.get_icon_path(icon_id) {
-                    image = cleanup_path(path).map(|p| res.to_res_href(&p));
+                    image = res.image_href(path);
                 }
             }
         }
@@ -169,7 +174,7 @@ fn mission_get_impl(data: &'_ TypedDatabase<'_>, loc: LocaleRoot, res: LuRes, id
         if image.is_none() {
             if let Some(icon_id) = task.icon_id {
                 if let Some(path) = data.get_icon_path(icon_id) {
-                    image = cleanup_path(path).map(|p| res.to_res_href(&p));
+                    image = res.image_href(path);
                 }
             }
         }
@@ -200,14 +205,36 @@ fn mission_get_impl(data: &'_ TypedDatabase<'_>, loc: LocaleRoot, res: LuRes, id
     }
 }
 
+/// Append a list of object names to `desc`, shortened to [`MAX_LIST_ITEMS`]
+fn write_object_list(
+    desc: &mut String,
+    data: &'_ TypedDatabase<'_>,
+    ids: impl ExactSizeIterator<Item = i32>,
+) {
+    let len = ids.len();
+    for id in ids.take(MAX_LIST_ITEMS) {
+        match data.get_object_name_desc(id) {
+            Some((name, _)) => writeln!(desc, "- {}", name).unwrap(),
+            None => writeln!(desc, "- Object #{}", id).unwrap(),
+        }
+    }
+    if len > MAX_LIST_ITEMS {
+        writeln!(desc, "- ... and {} more", len - MAX_LIST_ITEMS).unwrap();
+    }
+    if desc.ends_with('\n') {
+        desc.pop();
+    }
+}
+
+/// Number of objects to list in a description
+const MAX_LIST_ITEMS: usize = 10;
+
 /// Retrieve metadata for /objects/:id
 fn object_get_api(data: &'_ TypedDatabase<'_>, _loc: LocaleRoot, res: LuRes, id: i32) -> Meta {
     let (title, description) = data
         .get_object_name_desc(id)
         .unwrap_or((format!("Missing Object #{}", id), String::new()));
-    let comp = data.get_components(id);
-    let image = comp.render.and_then(|id| data.get_render_image(id));
-    let image = image.and_then(cleanup_path).map(|p| res.to_res_href(&p));
+    let image = res.object_image(data, id);
     Meta {
         title: Cow::Owned(title),
         description: Cow::Owned(description),
@@ -223,8 +250,8 @@ fn item_set_get_impl(data: &'_ TypedDatabase<'_>, loc: LocaleRoot, res: LuRes, i
     if let Some(item_set) = data.item_sets.get_data(id) {
         rank = item_set.kit_rank;
         if let Some(image_id) = item_set.kit_image {
-            if let Some(path) = data.get_icon_path(image_id).and_then(cleanup_path) {
-                image = Some(res.to_res_href(&path));
+            if let Some(path) = data.get_icon_path(image_id) {
+                image = res.image_href(path);
             }
         }
 
@@ -261,8 +288,8 @@ fn skill_get_impl(data: &'_ TypedDatabase<'_>, loc: &LocaleRoot, res: &LuRes, id
             title = Some(format!("Skill #{}", id))
         }
         if let Some(icon_id) = skill.skill_icon {
-            if let Some(path) = data.get_icon_path(icon_id).and_then(cleanup_path) {
-                image = Some(res.to_res_href(&path));
+            if let Some(path) = data.get_icon_path(icon_id) {
+                image = res.image_href(path);
             }
         }
     }
@@ -276,6 +303,76 @@ fn skill_get_impl(data: &'_ TypedDatabase<'_>, loc: &LocaleRoot, res: &LuRes, id
     }
 }
 
+/// Retrieve metadata for /factions/:id
+fn faction_get_impl(rev: &ReverseLookup, loc: &LocaleRoot, id: i32) -> Meta {
+    let title = loc
+        .get_faction_name(id)
+        .unwrap_or(format!("Faction #{}", id));
+    let description = match rev.factions().get(&id) {
+        Some(faction) => {
+            let count = faction
+                .destructible
+                .union(&faction.destructible_list)
+                .count();
+            Cow::Owned(format!("Used by {} destructible components", count))
+        }
+        None => Cow::Borrowed(""),
+    };
+    Meta {
+        title: Cow::Owned(title),
+        description,
+        image: None,
+    }
+}
+
+/// Retrieve metadata for /activities/:id
+fn activity_get_impl(
+    data: &'_ TypedDatabase<'_>,
+    rev: &ReverseLookup,
+    loc: &LocaleRoot,
+    res: &LuRes,
+    id: i32,
+) -> Meta {
+    let title = loc
+        .get_activity_name(id)
+        .unwrap_or(format!("Activity #{}", id));
+    let mut desc = String::new();
+    let mut image = None;
+    if let Some(activity) = rev.activities().get(&id) {
+        let rewards = &activity.activity_rewards;
+        image = rewards
+            .values()
+            .find_map(|&lot| res.object_image(data, lot));
+        write_object_list(&mut desc, data, rewards.values().copied());
+    }
+    Meta {
+        title: Cow::Owned(title),
+        description: Cow::Owned(desc),
+        image,
+    }
+}
+
+/// Retrieve metadata for /loot-tables/:id
+fn loot_table_get_impl(
+    data: &'_ TypedDatabase<'_>,
+    rev: &ReverseLookup,
+    res: &LuRes,
+    id: i32,
+) -> Meta {
+    let mut desc = String::new();
+    let mut image = None;
+    if let Some(loot_table) = rev.loot_table_index().get(&id) {
+        let items = &loot_table.items;
+        image = items.values().find_map(|&lot| res.object_image(data, lot));
+        write_object_list(&mut desc, data, items.values().copied());
+    }
+    Meta {
+        title: Cow::Owned(format!("Loot Table #{}", id)),
+        description: Cow::Owned(desc),
+        image,
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum SpaRoute {
     Dashboard,
@@ -287,6 +384,11 @@ pub enum SpaRoute {
     SkillById { id: i32 },
     ItemSets,
     ItemSetById { id: i32 },
+    Factions,
+    FactionById { id: i32 },
+    Activities,
+    ActivityById { id: i32 },
+    LootTableById { id: i32 },
 }
 
 impl SpaRoute {
@@ -319,11 +421,52 @@ impl SpaRoute {
                 },
                 _ => Some(Self::Skills),
             },
+            Some("factions") => match split.next() {
+                Some(x) => match x.parse::<i32>() {
+                    Ok(id) => Some(Self::FactionById { id }),
+                    Err(_) => None,
+                },
+                _ => Some(Self::Factions),
+            },
+            Some("activities") => match split.next() {
+                Some(x) => match x.parse::<i32>() {
+                    Ok(id) => Some(Self::ActivityById { id }),
+                    Err(_) => None,
+                },
+                _ => Some(Self::Activities),
+            },
+            Some("loot-tables") => match split.next() {
+                Some(x) => match x.parse::<i32>() {
+                    Ok(id) => Some(Self::LootTableById { id }),
+                    Err(_) => None,
+                },
+                _ => None,
+            },
             _ => None,
         }
     }
 
-    fn to_meta(self, data: &'_ TypedDatabase<'_>, loc: &LocaleRoot, res: &LuRes) -> Meta {
+    /// Whether this is the page of a single entity, which gets structured data
+    fn is_item(self) -> bool {
+        matches!(
+            self,
+            Self::ObjectById { .. }
+                | Self::MissionById(_)
+                | Self::SkillById { .. }
+                | Self::ItemSetById { .. }
+                | Self::FactionById { .. }
+                | Self::ActivityById { .. }
+                | Self::LootTableById { .. }
+        )
+    }
+
+    fn to_meta(
+        self,
+        data: &'_ TypedDatabase<'_>,
+        rev: &ReverseLookup,
+        loc: &LocaleRoot,
+        res: &LuRes,
+    ) -> Meta {
         match self {
             Self::Dashboard => Meta::DASHBOARD,
             Self::Objects => Meta::OBJECTS,
@@ -334,6 +477,11 @@ impl SpaRoute {
             Self::SkillById { id } => skill_get_impl(data, loc, res, id),
             Self::ItemSets => Meta::ITEM_SETS,
             Self::ItemSetById { id } => item_set_get_impl(data, loc.clone(), res.clone(), id),
+            Self::Factions => Meta::FACTIONS,
+            Self::FactionById { id } => faction_get_impl(rev, loc, id),
+            Self::Activities => Meta::ACTIVITIES,
+            Self::ActivityById { id } => activity_get_impl(data, rev, loc, res, id),
+            Self::LootTableById { id } => loot_table_get_impl(data, rev, res, id),
         }
     }
 }
@@ -346,6 +494,8 @@ pub struct IndexParams {
     pub url: Cow<'static, str>,
     pub card: &'static str,
     pub site: Cow<'static, str>,
+    /// A `<script>` tag with JSON-LD structured data, or empty
+    pub json_ld: Cow<'static, str>,
 }
 
 impl minihb::Lookup for IndexParams {
@@ -358,13 +508,12 @@ impl minihb::Lookup for IndexParams {
             "url" => &self.url,
             "card" => &self.card,
             "site" => &self.site,
+            "json_ld" => &self.json_ld,
             _ => &"",
         }
     }
 }
 
-static DEFAULT_IMG: &str = "/ui/ingame/freetrialcongratulations_id.png";
-
 #[derive(Debug, Clone)]
 struct Meta {
     title: Cow<'static, str>,
@@ -402,6 +551,18 @@ impl Meta {
         description: Cow::Borrowed("Check out the LEGO Universe Missions"),
         image: None,
     };
+
+    const FACTIONS: Self = Self {
+        title: Cow::Borrowed("Factions"),
+        description: Cow::Borrowed("Check out the LEGO Universe Factions"),
+        image: None,
+    };
+
+    const ACTIVITIES: Self = Self {
+        title: Cow::Borrowed("Activities"),
+        description: Cow::Borrowed("Check out the LEGO Universe Activities"),
+        image: None,
+    };
 }
 
 impl Default for Meta {
@@ -414,6 +575,25 @@ impl Default for Meta {
     }
 }
 
+/// Create a `<script>` tag with a schema.org `Thing` for the page
+fn json_ld(meta: &Meta, url: &str) -> String {
+    let value = serde_json::json!({
+        "@context": "https://schema.org",
+        "@type": "Thing",
+        "name": meta.title,
+        "description": meta.description,
+        "image": meta.image,
+        "url": url,
+        "isPartOf": {
+            "@type": "VideoGame",
+            "name": "LEGO Universe",
+        },
+    });
+    // `</script>` in a string must not end the tag
+    let json = value.to_string().replace("</", "<\\/");
+    format!("<script type=\"application/ld+json\">{}</script>", json)
+}
+
 #[derive(Clone)]
 struct RenderService {
     template: Arc<RwLock<Template>>,
@@ -452,25 +632,28 @@ impl tower_service::Service<IndexParams> for RenderService {
 #[derive(Clone)]
 pub struct SpaDynamic {
     inner: RenderService,
-    data: &'static TypedDatabase<'static>,
+    data: SharedDb,
     default_img: &'static str,
-    locale_root: LocaleRoot,
+    site: &'static str,
+    locales: Locales,
     res: LuRes,
     base_url: &'static str,
 }
 
 impl SpaDynamic {
     pub fn new(
-        data: &'static TypedDatabase<'static>,
-        locale_root: LocaleRoot,
+        data: SharedDb,
+        locales: Locales,
         res: LuRes,
         hb: Arc<RwLock<Template>>,
         base_url: &str,
+        meta: &MetaConfig,
     ) -> Self {
         let base_url = Box::leak(base_url.to_string().into_boxed_str()) as &str;
+        let site = Box::leak(meta.site.clone().into_boxed_str()) as &str;
 
         // Prepare the default image
-        let default_img = res.to_res_href(Path::new(DEFAULT_IMG));
+        let default_img = res.to_res_href(&meta.default_image);
         let default_img: &'static str = Box::leak(default_img.into_boxed_str());
 
         // Create a reusable closure to render template
@@ -478,17 +661,25 @@ impl SpaDynamic {
         Self {
             inner,
             data,
-            locale_root,
+            locales,
             res,
             default_img,
+            site,
             base_url,
         }
     }
 
-    fn meta<ReqBody>(&self, req: &http::Request<ReqBody>) -> Meta {
-        let path = req.uri().path();
-        if let Some(route) = SpaRoute::parse(path) {
-            route.to_meta(self.data, &self.locale_root, &self.res)
+    fn meta<ReqBody>(&self, route: Option<SpaRoute>, req: &http::Request<ReqBody>) -> Meta {
+        if let Some(route) = route {
+            let db = self.data.get();
+            let loc = match req.headers().get(ACCEPT_LANGUAGE) {
+                Some(v) => match v.to_str() {
+                    Ok(v) => self.locales.negotiate(v),
+                    Err(_) => self.locales.default_locale(),
+                },
+                None => self.locales.default_locale(),
+            };
+            route.to_meta(db.tydb(), db.rev(), loc, &self.res)
         } else {
             Meta::default()
         }
@@ -531,23 +722,35 @@ where
     }
 
     fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
-        let meta = self.meta(&req);
+        let route = SpaRoute::parse(req.uri().path());
+        let meta = self.meta(route, &req);
         let full_path = req
             .uri()
             .path_and_query()
             .map(PathAndQuery::as_str)
             .unwrap_or_default();
+        let url = self.base_url.to_string() + full_path;
+        let json_ld = match route {
+            Some(route) if route.is_item() => Cow::Owned(json_ld(&meta, &url)),
+            _ => Cow::Borrowed(""),
+        };
+        // Only a real image is worth the large card, not the default logo
+        let card = match meta.image {
+            Some(_) => "summary_large_image",
+            None => "summary",
+        };
         let params = IndexParams {
             title: meta.title,
             r#type: "website",
-            card: "summary",
+            card,
             description: meta.description,
-            site: Cow::Borrowed("@lu_explorer"),
+            site: Cow::Borrowed(self.site),
             image: meta
                 .image
                 .map(Cow::Owned)
                 .unwrap_or(Cow::Borrowed(self.default_img)),
-            url: Cow::Owned(self.base_url.to_string() + full_path),
+            url: Cow::Owned(url),
+            json_ld,
         };
         SpaFuture {
             inner: self.inner.call(params),